@@ -0,0 +1,40 @@
+//! The Mégra language server, for editors that speak LSP.
+//! It communicates via stdin/stdout, so all the editor needs
+//! to know is how to start it.
+
+use directories_next::ProjectDirs;
+use getopts::Options;
+use std::path::PathBuf;
+
+fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "base",
+        "base folder including samples, sample set names will be known functions",
+        "",
+    );
+    opts.optopt("", "sample-folder", "folder to a collection of samples", "");
+    opts.optflag("h", "help", "Print this help");
+
+    let matches = opts.parse(&args[1..])?;
+
+    if matches.opt_present("h") {
+        print!("{}", opts.usage("Usage: megra-lsp [options]"));
+        return Ok(());
+    }
+
+    // same places the main program looks for samples
+    let samples_path = if let Some(folder) = matches.opt_str("sample-folder") {
+        Some(PathBuf::from(folder))
+    } else if let Some(base) = matches.opt_str("base") {
+        Some(PathBuf::from(base).join("samples"))
+    } else {
+        ProjectDirs::from("de", "parkellipsen", "megra")
+            .map(|dirs| dirs.config_dir().join("samples"))
+    };
+
+    megra_rs::language_server::run(samples_path.as_deref())
+}
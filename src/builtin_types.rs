@@ -2,6 +2,7 @@ use crate::event::*;
 use crate::generator::{GenModFun, Generator};
 use crate::generator_processor::GeneratorProcessor;
 use crate::markov_sequence_generator::Rule;
+use crate::osc_receiver::OscTransport;
 use crate::parameter::*;
 
 use core::fmt;
@@ -83,6 +84,17 @@ pub enum LazyArithmetic {
     Sub(Vec<LazyVal>),
     Modulo(Vec<LazyVal>),
     Pow(Vec<LazyVal>),
+    // comparisons and logic, 1.0 is true and 0.0 is false
+    Lt(Vec<LazyVal>),
+    Gt(Vec<LazyVal>),
+    Lte(Vec<LazyVal>),
+    Gte(Vec<LazyVal>),
+    Eq(Vec<LazyVal>),
+    And(Vec<LazyVal>),
+    Or(Vec<LazyVal>),
+    Not(Vec<LazyVal>),
+    // condition, then, else
+    If(Vec<LazyVal>),
 }
 
 pub type GlobalVariables = DashMap<VariableId, TypedEntity>;
@@ -117,13 +129,25 @@ pub enum Command {
     StopRecording,                                 // stop recording ...
     OscDefineClient(String, String),
     OscSendMessage(String, String, Vec<TypedEntity>),
-    OscStartReceiver(String),
+    OscStartReceiver(String, OscTransport),
+    OscRoute(String, String, String), // tag, sender, osc address
+    OscUnroute(String),
     MidiStartReceiver(usize),
     MidiListPorts,
+    MidiClockStart(usize), // send midi clock to output port
+    MidiClockStop,
+    MidiClockFollow(usize), // follow midi clock from input port
+    MidiClockFollowStop,
+    MidiOpenOutput(usize),
+    MidiRoute(String, usize, u8), // tag, output port, channel
+    MidiUnroute(String),
     Print(TypedEntity),
     Push(VariableId, TypedEntity),
     Insert(VariableId, VariableId, TypedEntity),
     LoadFile(String),
+    Import(String, Option<String>), // file or module name, namespace
+    TempoSyncJoin(String, Vec<String>, f32), // local address, peers, quantum
+    TempoSyncLeave,
 }
 
 #[derive(Clone)]
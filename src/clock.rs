@@ -0,0 +1,201 @@
+use parking_lot::{Condvar, Mutex};
+use ruffbox_synth::ruffbox::RuffboxControls;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{sync, thread};
+
+/// The time source the schedulers follow.
+///
+/// All times are in seconds, relative to some arbitrary point
+/// in the past that's fixed for the lifetime of the clock.
+pub trait SchedulerClock: Send + Sync {
+    /// current time in seconds
+    fn now(&self) -> f64;
+
+    /// block the calling scheduler thread until the given point in time,
+    /// or until the scheduler isn't running anymore
+    fn wait_until(&self, time: f64, running: &AtomicBool);
+
+    /// register a scheduler thread that's about to start
+    fn enter(&self) {}
+
+    /// unregister a scheduler thread that's about to finish
+    fn leave(&self) {}
+
+    /// virtual clocks only move when the schedulers are done,
+    /// so schedulers following them can never be late
+    fn is_virtual(&self) -> bool {
+        false
+    }
+}
+
+/// The default clock, following the system's monotonic clock.
+pub struct WallClock {
+    start: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        WallClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulerClock for WallClock {
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn wait_until(&self, time: f64, _: &AtomicBool) {
+        let now = self.now();
+        if time > now {
+            thread::sleep(Duration::from_secs_f64(time - now));
+        }
+    }
+}
+
+/// A clock following the audio stream time, that is, the number
+/// of samples the output has processed so far.
+///
+/// The stream time only moves in steps of one block, so the schedulers
+/// might wake up slightly late, which is fine as long as it's less than
+/// the latency. The lateness measured against this clock is the actual
+/// drift between the schedulers and the audio stream.
+pub struct AudioClock<const BUFSIZE: usize, const NCHAN: usize> {
+    ruffbox: sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> AudioClock<BUFSIZE, NCHAN> {
+    pub fn new(ruffbox: sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>) -> Self {
+        AudioClock { ruffbox }
+    }
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerClock for AudioClock<BUFSIZE, NCHAN> {
+    fn now(&self) -> f64 {
+        self.ruffbox.get_now()
+    }
+
+    fn wait_until(&self, time: f64, running: &AtomicBool) {
+        let mut now = self.now();
+        while now < time && running.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_secs_f64(time - now));
+            now = self.now();
+        }
+    }
+}
+
+/// A clock that only moves forward when told to, used to drive the
+/// schedulers faster than realtime (i.e. for offline rendering), or
+/// step by step (i.e. in tests).
+///
+/// The clock keeps track of how many scheduler threads are currently
+/// busy, so that whoever advances the clock can wait until every
+/// scheduler has caught up before the corresponding audio is rendered.
+/// Waiting schedulers that become due are marked busy by the advancing
+/// thread, so they can't be missed before they had the chance to wake up.
+pub struct ManualClock {
+    state: Mutex<ManualClockState>,
+    cond: Condvar,
+}
+
+struct ManualClockState {
+    now: f64,
+    busy: usize,
+    // the schedulers waiting for a time, by id
+    waiting: Vec<(usize, f64)>,
+    next_waiter: usize,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            state: Mutex::new(ManualClockState {
+                now: 0.0,
+                busy: 0,
+                waiting: Vec::new(),
+                next_waiter: 0,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// move the time forward and wait until all the schedulers
+    /// that were due in the meantime have done their work
+    pub fn advance_to(&self, time: f64) {
+        let mut state = self.state.lock();
+        state.now = time;
+        let waiting = state.waiting.len();
+        state.waiting.retain(|(_, t)| *t > time);
+        state.busy += waiting - state.waiting.len();
+        self.cond.notify_all();
+        while state.busy > 0 {
+            self.cond.wait(&mut state);
+        }
+    }
+
+    /// move the time forward by the given amount of seconds
+    pub fn advance(&self, dur: f64) {
+        let now = self.now();
+        self.advance_to(now + dur);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulerClock for ManualClock {
+    fn now(&self) -> f64 {
+        self.state.lock().now
+    }
+
+    fn wait_until(&self, time: f64, running: &AtomicBool) {
+        let mut state = self.state.lock();
+        if state.now >= time {
+            return;
+        }
+        let id = state.next_waiter;
+        state.next_waiter = state.next_waiter.wrapping_add(1);
+        state.waiting.push((id, time));
+        debug_assert!(state.busy > 0, "wait_until without enter");
+        state.busy = state.busy.saturating_sub(1);
+        self.cond.notify_all();
+        // a stopped scheduler must be able to finish even if the
+        // clock doesn't move anymore, so check every now and then
+        while state.now < time && running.load(Ordering::SeqCst) {
+            self.cond.wait_for(&mut state, Duration::from_millis(10));
+        }
+        // if it's not waiting anymore, the advancing thread already
+        // marked this one as busy
+        if let Some(idx) = state.waiting.iter().position(|(w, _)| *w == id) {
+            state.waiting.remove(idx);
+            state.busy += 1;
+        }
+    }
+
+    fn enter(&self) {
+        self.state.lock().busy += 1;
+    }
+
+    fn leave(&self) {
+        let mut state = self.state.lock();
+        debug_assert!(state.busy > 0, "leave without enter");
+        state.busy = state.busy.saturating_sub(1);
+        self.cond.notify_all();
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+}
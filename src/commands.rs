@@ -15,6 +15,7 @@ use std::{
 #[allow(deprecated)]
 use sha256::try_digest;
 
+use rosc::{OscMessage, OscTime, OscType};
 use vom_rs::pfa;
 
 use ruffbox_synth::{
@@ -23,17 +24,20 @@ use ruffbox_synth::{
 };
 
 use crate::builtin_types::*;
+use crate::clock::AudioClock;
 use crate::event::*;
 use crate::event_helpers::*;
 use crate::generator::*;
 use crate::load_audio_file;
-use crate::osc_sender::OscSender;
+use crate::midi_clock::{self, MidiClockOutput};
+use crate::osc_sender::{osc_time_at, OscSender};
 use crate::parameter::*;
 use crate::parser::eval;
 use crate::parser::FunctionMap;
 use crate::real_time_streaming;
 use crate::sample_set::SampleAndWavematrixSet;
 use crate::session::*;
+use crate::tempo_sync::TempoSync;
 use chrono::Local;
 use directories_next::ProjectDirs;
 use std::io::Cursor;
@@ -592,6 +596,152 @@ pub fn set_default_duration(globals: &sync::Arc<GlobalVariables>, n: f32) {
     ); // init on first attempt
 }
 
+pub fn start_midi_clock<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+    out_port: usize,
+) {
+    let mut midi_clock = session.midi_clock.lock();
+    if let Some(old) = midi_clock.take() {
+        old.stop();
+    }
+    // start on the next bar of the shared beat grid, like the generators
+    match MidiClockOutput::start(
+        out_port,
+        &session.globals,
+        sync::Arc::clone(&session.clock),
+        tempo_sync_phase(session),
+    ) {
+        Ok(mc) => {
+            midi_clock.replace(mc);
+        }
+        Err(e) => {
+            println!("can't start midi clock: {e}");
+        }
+    }
+}
+
+pub fn stop_midi_clock<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+) {
+    if let Some(mc) = session.midi_clock.lock().take() {
+        mc.stop();
+    }
+}
+
+/// a new follower replaces the old one
+pub fn follow_midi_clock<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+    in_port: usize,
+) {
+    let mut follower = session.midi_clock_follower.lock();
+    // the old connection is closed when it's dropped
+    follower.take();
+    match midi_clock::follow_midi_clock(in_port, sync::Arc::clone(&session.globals)) {
+        Ok(conn) => {
+            follower.replace(conn);
+        }
+        Err(e) => {
+            println!("can't follow midi clock: {e}");
+        }
+    }
+}
+
+pub fn stop_following_midi_clock<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+) {
+    if session.midi_clock_follower.lock().take().is_some() {
+        println!("stopped following midi clock");
+    }
+}
+
+pub fn open_midi_output<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+    out_port: usize,
+) {
+    // midi messages are timed like the audio events
+    let clock = sync::Arc::new(AudioClock::new(sync::Arc::clone(&session.ruffbox)));
+    if let Err(e) = session.midi_out.open(out_port, clock) {
+        println!("can't open midi output: {e}");
+    }
+}
+
+pub fn tempo_sync_join<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+    local: String,
+    peers: Vec<String>,
+    quantum: f32,
+) {
+    let mut tempo_sync = session.tempo_sync.write();
+    if let Some(old) = tempo_sync.take() {
+        old.leave();
+    }
+    match TempoSync::join(&local, &peers, quantum as f64, &session.globals) {
+        Ok(ts) => {
+            tempo_sync.replace(ts);
+        }
+        Err(e) => {
+            println!("can't join tempo sync: {e}");
+        }
+    }
+}
+
+pub fn tempo_sync_leave<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+) {
+    if let Some(ts) = session.tempo_sync.write().take() {
+        ts.leave();
+    }
+}
+
+/// share the local tempo settings with the tempo sync peers, if any
+pub fn publish_tempo<const BUFSIZE: usize, const NCHAN: usize>(session: &Session<BUFSIZE, NCHAN>) {
+    if let Some(ts) = session.tempo_sync.read().as_ref() {
+        let bpm = if let Some(thing) = session.globals.get(&VariableId::DefaultDuration) {
+            if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(dur)) = thing.value() {
+                60000.0 / *dur as f64
+            } else {
+                return;
+            }
+        } else {
+            return;
+        };
+
+        let tmod = if let Some(thing) = session.globals.get(&VariableId::GlobalTimeModifier) {
+            if let TypedEntity::ConfigParameter(ConfigParameter::Dynamic(d)) = thing.value() {
+                d.static_val as f64
+            } else {
+                1.0
+            }
+        } else {
+            1.0
+        };
+
+        ts.set_tempo(bpm, tmod);
+    }
+}
+
+/// the latency in seconds
+pub fn global_latency(globals: &sync::Arc<GlobalVariables>) -> f64 {
+    if let Some(thing) = globals.get(&VariableId::GlobalLatency) {
+        if let TypedEntity::ConfigParameter(ConfigParameter::Dynamic(d)) = thing.value() {
+            return d.static_val as f64;
+        }
+    }
+    0.05
+}
+
+/// the time until the next bar on the shared beat grid,
+/// zero if there's no tempo sync
+pub fn tempo_sync_phase<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+) -> f64 {
+    if let Some(ts) = session.tempo_sync.read().as_ref() {
+        ts.time_to_next_quantum(global_latency(&session.globals))
+    } else {
+        0.0
+    }
+}
+
 pub fn set_global_lifemodel_resources(globals: &sync::Arc<GlobalVariables>, val: f32) {
     globals.insert(
         VariableId::LifemodelGlobalResources,
@@ -662,11 +812,21 @@ pub fn once<const BUFSIZE: usize, const NCHAN: usize>(
         }
     }
 
+    // like the scheduled events, so midi, osc and audio line up
+    let latency = global_latency(&session.globals);
+    let time = session.ruffbox.get_now() + latency;
+    // the session clock might not be the audio clock
+    let osc_time = osc_time_at(&*session.clock, session.clock.now() + latency);
+
     for s in sound_events.iter_mut() {
         if s.name == "silence" {
             continue;
         }
 
+        if session.midi_out.handle_event(s, time) || session.osc_client.handle_event(s, osc_time) {
+            continue;
+        }
+
         // if this is a sampler event and contains a sample lookup,
         // resolve it NOW ... at the very end, finally ...
         let mut bufnum: usize = 0;
@@ -689,11 +849,10 @@ pub fn once<const BUFSIZE: usize, const NCHAN: usize>(
         // the available information ...
         s.build_envelope();
 
-        // latency 0.05, should be made configurable later ...
         if let Some(mut inst) =
             session
                 .ruffbox
-                .prepare_instance(map_synth_type(&s.name, &s.params), 0.0, bufnum)
+                .prepare_instance(map_synth_type(&s.name, &s.params), time, bufnum)
         {
             // set parameters and trigger instance
             for (addr, v) in s.params.iter() {
@@ -760,6 +919,50 @@ pub fn define_osc_client(
     }
 }
 
+/// Send a message to a custom osc client, either right away or
+/// as a bundle to be executed at the given time.
+pub fn send_osc_message<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &Session<BUFSIZE, NCHAN>,
+    client_name: &str,
+    osc_addr: String,
+    args: &[TypedEntity],
+    timetag: Option<OscTime>,
+) {
+    let mut osc_args = Vec::new();
+    for arg in args.iter() {
+        match arg {
+            TypedEntity::Comparable(Comparable::Float(n)) => osc_args.push(OscType::Float(*n)),
+            TypedEntity::Comparable(Comparable::Double(n)) => osc_args.push(OscType::Double(*n)),
+            TypedEntity::Comparable(Comparable::Int32(n)) => osc_args.push(OscType::Int(*n)),
+            TypedEntity::Comparable(Comparable::Int64(n)) => osc_args.push(OscType::Long(*n)),
+            TypedEntity::Comparable(Comparable::String(s)) => {
+                osc_args.push(OscType::String(s.to_string()))
+            }
+            TypedEntity::Comparable(Comparable::Symbol(s)) => {
+                osc_args.push(OscType::String(s.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(thing) = &session.osc_client.custom.get(client_name) {
+        let res = if let Some(timetag) = timetag {
+            thing.value().send_bundle(
+                timetag,
+                vec![OscMessage {
+                    addr: osc_addr,
+                    args: osc_args,
+                }],
+            )
+        } else {
+            thing.value().send_message(osc_addr, osc_args)
+        };
+        if let Err(e) = res {
+            println!("can't send osc message to {client_name}: {e}");
+        }
+    }
+}
+
 pub fn push(id: VariableId, value: TypedEntity, globals: &sync::Arc<GlobalVariables>) {
     if let Some(mut thing) = globals.get_mut(&id) {
         if let TypedEntity::Vec(v) = thing.value_mut() {
@@ -227,7 +227,7 @@ pub fn eval_cyc_from_str(
                             //println!("{}", name);
                             match parse_expr(name.trim()) {
                                 Ok((_, expr)) => {
-                                    if let Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(e))) =
+                                    if let Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(e))) =
                                         eval_expression(
                                             &expr,
                                             functions,
@@ -300,7 +300,7 @@ pub fn eval_cyc_from_str(
                         ev_name = format!("({ev_name})");
                         match parse_expr(ev_name.trim()) {
                             Ok((_, expr)) => {
-                                if let Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(e))) =
+                                if let Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(e))) =
                                     eval_expression(
                                         &expr,
                                         functions,
@@ -1,5 +1,10 @@
 // editor modules
+mod completion;
+mod graph_view;
+mod history;
+mod keymap;
 mod livecode_text_edit;
+mod meters;
 mod syntax_highlighting;
 
 use parking_lot::Mutex;
@@ -7,14 +12,19 @@ use parking_lot::Mutex;
 use std::sync;
 
 mod megra_editor;
-use megra_editor::{EditorFont, MegraEditor};
+use completion::Vocabulary;
+use livecode_text_edit::EvalCallback;
+use megra_editor::{EditorFont, GeneratorAction, MegraEditor};
+use meters::AudioMeters;
 
 use crate::interpreter;
 use crate::parser;
-use crate::parser::FunctionMap;
+use crate::parser::{FunctionMap, SPECIAL_FORMS};
 
 use crate::session::Session;
 
+use std::thread;
+
 pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
     function_map: &sync::Arc<Mutex<FunctionMap>>,
     session: Session<BUFSIZE, NCHAN>,
@@ -27,8 +37,14 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
     let function_map2 = sync::Arc::clone(function_map);
     let globals2 = sync::Arc::clone(&session.globals);
     let base_dir_2 = base_dir.clone();
+    let session_info = session.clone();
+    let session_action = session.clone();
+    let session_graphs = session.clone();
+    let monitor = sync::Arc::clone(&session.monitor);
+    let function_map3 = sync::Arc::clone(function_map);
+    let sample_set = session.sample_set.clone();
 
-    let callback_ref: sync::Arc<Mutex<dyn FnMut(&String)>> =
+    let callback_ref: sync::Arc<Mutex<EvalCallback>> =
         sync::Arc::new(Mutex::new(move |text: &String| {
             let pfa_in = parser::eval_from_str(
                 text,
@@ -45,9 +61,11 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
                         session.clone(),
                         base_dir_2.to_string(),
                     );
+                    Ok(())
                 }
                 Err(e) => {
-                    println!("could not parse this! {text} {e}")
+                    println!("{}", e.report(text));
+                    Err(e)
                 }
             }
         }));
@@ -75,6 +93,64 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
             inner_app.set_font_size(fs);
             inner_app.set_font(ifont);
             inner_app.set_callback(callback_ref);
+            inner_app.set_generator_callbacks(
+                Box::new(move || Session::generator_info(&session_info)),
+                Box::new(move |id_tags, action| match action {
+                    GeneratorAction::Stop => {
+                        // stopping waits for the scheduler thread,
+                        // so don't block the editor
+                        let session2 = session_action.clone();
+                        let id_tags = id_tags.clone();
+                        thread::spawn(move || Session::stop_generator(&session2, &id_tags));
+                    }
+                    GeneratorAction::Mute(mute) => {
+                        Session::mute_generator(&session_action, id_tags, mute)
+                    }
+                    GeneratorAction::Solo(solo) => {
+                        Session::solo_generator(&session_action, id_tags, solo)
+                    }
+                }),
+            );
+            inner_app.set_vocabulary_callback(Box::new(move || {
+                let functions = function_map3.lock();
+                Vocabulary {
+                    std_lib: functions
+                        .std_lib
+                        .keys()
+                        .cloned()
+                        .chain(SPECIAL_FORMS.iter().map(|f| f.to_string()))
+                        .collect(),
+                    usr_lib: functions
+                        .usr_lib
+                        .iter()
+                        .map(|(name, fun)| (name.clone(), fun.arg_names()))
+                        .chain(
+                            functions
+                                .macros
+                                .iter()
+                                .map(|(name, mac)| (name.clone(), mac.arg_names())),
+                        )
+                        .collect(),
+                    sample_sets: sample_set.names().into_iter().collect(),
+                }
+            }));
+            inner_app
+                .set_graph_callback(Box::new(move || Session::generator_graphs(&session_graphs)));
+            let samplerate = monitor.lock().as_ref().map(|m| m.samplerate);
+            if let Some(samplerate) = samplerate {
+                inner_app.set_monitor(
+                    AudioMeters::new(NCHAN, samplerate as f32),
+                    Box::new(move |meters| {
+                        if let Some(m) = monitor.lock().as_ref() {
+                            m.catch.drain(|item| {
+                                let block: Vec<&[f32]> =
+                                    item.buffer.iter().map(|ch| &ch[..item.size]).collect();
+                                meters.feed(&block);
+                            });
+                        }
+                    }),
+                );
+            }
 
             Box::new(inner_app)
         }),
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use egui::{FontId, Id, Order, Pos2, RichText};
+
+use crate::event_helpers::PARAMETER_NAMES;
+use crate::language_server::{
+    enclosing_function, function_doc, function_keywords, tokenize, word_at, Token,
+};
+
+// how many candidates the popup shows at once
+const POPUP_ROWS: usize = 10;
+
+/// What can be completed in the editor. It's taken from the session when
+/// the editor starts and after each evaluation, as that's when functions
+/// are defined and samples are loaded.
+#[derive(Default)]
+pub struct Vocabulary {
+    pub std_lib: BTreeSet<String>,
+    // user-defined functions with their argument names
+    pub usr_lib: BTreeMap<String, Vec<String>>,
+    pub sample_sets: BTreeSet<String>,
+}
+
+impl Vocabulary {
+    /// Candidates for the word that ends at the offset (in bytes), and
+    /// where that word starts. Keywords are completed after a colon,
+    /// nothing is completed in comments, strings and symbols.
+    pub fn completions(&self, text: &str, offset: usize) -> (usize, Vec<String>) {
+        let (prefix, start) = word_at(&text[..offset], offset);
+        let tokens = tokenize(&text[..offset]);
+        let in_word = matches!(tokens.last(), Some(Token::Atom(a, pos)) if pos + a.len() == offset);
+        if prefix.is_empty() || !in_word || text[..start].ends_with('\'') {
+            return (start, Vec::new());
+        }
+
+        let candidates: Vec<String> = if text[..start].ends_with(':') {
+            enclosing_function(&tokens)
+                .and_then(function_keywords)
+                .unwrap_or(PARAMETER_NAMES)
+                .iter()
+                .map(|k| k.to_string())
+                .collect()
+        } else {
+            self.usr_lib
+                .keys()
+                .chain(self.std_lib.iter())
+                .chain(self.sample_sets.iter())
+                .cloned()
+                .collect()
+        };
+
+        // user-defined functions can have the same name as built-in ones
+        let mut seen = BTreeSet::new();
+        let candidates = candidates
+            .into_iter()
+            .filter(|c| c.starts_with(&prefix) && *c != prefix && seen.insert(c.clone()))
+            .collect();
+
+        (start, candidates)
+    }
+
+    /// One line on how to call the function whose argument list the
+    /// offset is in.
+    pub fn signature(&self, text: &str, offset: usize) -> Option<String> {
+        let tokens = tokenize(&text[..offset]);
+        let name = enclosing_function(&tokens)?;
+
+        if let Some(args) = self.usr_lib.get(name) {
+            Some(format!(
+                "({name} {})  user-defined function",
+                args.join(" ")
+            ))
+        } else if let Some(doc) = function_doc(name) {
+            Some(doc.replace('`', ""))
+        } else if self.sample_sets.contains(name) {
+            Some(format!(
+                "({name} keywords ... :param value ...)  sample event"
+            ))
+        } else if self.std_lib.contains(name) {
+            Some(match function_keywords(name) {
+                Some(keywords) => {
+                    let keywords: Vec<String> = keywords.iter().map(|k| format!(":{k}")).collect();
+                    format!("({name} ... {})", keywords.join(" "))
+                }
+                None => format!("({name} ...)"),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// The candidates below the cursor, with the selected one highlighted.
+pub fn show_popup(
+    ctx: &egui::Context,
+    id: Id,
+    pos: Pos2,
+    candidates: &[String],
+    selected: usize,
+    font: FontId,
+) {
+    // keep the selected one in view
+    let first = selected.saturating_sub(POPUP_ROWS - 1);
+
+    egui::Area::new(id.with("completion_popup"))
+        .order(Order::Foreground)
+        .fixed_pos(pos)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for (i, candidate) in candidates.iter().enumerate().skip(first).take(POPUP_ROWS) {
+                    let mut label = RichText::new(candidate).font(font.clone());
+                    if i == selected {
+                        label = label
+                            .background_color(ui.visuals().selection.bg_fill)
+                            .color(ui.visuals().selection.stroke.color);
+                    }
+                    ui.add(egui::Label::new(label).wrap(false));
+                }
+                if candidates.len() > POPUP_ROWS {
+                    ui.label(
+                        RichText::new(format!("{}/{}", selected + 1, candidates.len()))
+                            .font(font.clone())
+                            .color(ui.visuals().weak_text_color()),
+                    );
+                }
+            });
+        });
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        let mut vocabulary = Vocabulary::default();
+        vocabulary.std_lib.insert("saw".to_string());
+        vocabulary.std_lib.insert("sine".to_string());
+        vocabulary.std_lib.insert("infer".to_string());
+        vocabulary.sample_sets.insert("bd".to_string());
+        vocabulary.sample_sets.insert("sn".to_string());
+        vocabulary
+            .usr_lib
+            .insert("snare".to_string(), vec!["a".to_string()]);
+
+        let text = "(sx 'ga #t (infer 'x :ev";
+        let (start, candidates) = vocabulary.completions(text, text.len());
+        assert_eq!(&text[start..], "ev");
+        assert_eq!(candidates, vec!["events"]);
+
+        let text = "(s";
+        let (_, candidates) = vocabulary.completions(text, text.len());
+        assert_eq!(candidates, vec!["snare", "saw", "sine", "sn"]);
+
+        // nothing in comments, strings and symbols
+        for text in [";; (s", "(cyc 'x \"s", "(sx 's"] {
+            assert!(vocabulary.completions(text, text.len()).1.is_empty());
+        }
+
+        let text = "(sx 'ga #t (snare 1) (bd :lp";
+        assert_eq!(
+            vocabulary.signature(text, text.len()).unwrap(),
+            "(bd keywords ... :param value ...)  sample event"
+        );
+        let text = "(sx 'ga #t (snare ";
+        assert_eq!(
+            vocabulary.signature(text, text.len()).unwrap(),
+            "(snare a)  user-defined function"
+        );
+    }
+}
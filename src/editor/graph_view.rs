@@ -0,0 +1,235 @@
+use std::collections::{BTreeSet, HashMap};
+
+use egui::{epaint::CircleShape, Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::editor::syntax_highlighting::Palette;
+use crate::visualizer_client::GeneratorGraph;
+
+/// Node positions of a graph, in a unit square. They're found by a
+/// simple force-directed layout, which runs a few steps per frame and
+/// cools down, so the graph settles after a while. New nodes heat it up
+/// again.
+#[derive(Default)]
+struct Layout {
+    positions: HashMap<u64, Vec2>,
+    temperature: f32,
+}
+
+impl Layout {
+    fn update(&mut self, graph: &GeneratorGraph) {
+        self.positions
+            .retain(|key, _| graph.nodes.iter().any(|(n, _)| n == key));
+
+        for (i, (key, _)) in graph.nodes.iter().enumerate() {
+            if !self.positions.contains_key(key) {
+                // spread new nodes on a spiral, so they don't overlap
+                let angle = i as f32 * 2.4;
+                let radius = 0.1 + 0.3 * (i as f32 / graph.nodes.len() as f32);
+                self.positions.insert(
+                    *key,
+                    Vec2::new(0.5 + radius * angle.cos(), 0.5 + radius * angle.sin()),
+                );
+                self.temperature = 0.1;
+            }
+        }
+
+        for _ in 0..5 {
+            self.step(graph);
+        }
+    }
+
+    fn step(&mut self, graph: &GeneratorGraph) {
+        if self.temperature < 0.001 || self.positions.is_empty() {
+            return;
+        }
+
+        // ideal distance between nodes
+        let k = (1.0 / self.positions.len() as f32).sqrt() * 0.6;
+        let mut disp: HashMap<u64, Vec2> =
+            self.positions.keys().map(|k| (*k, Vec2::ZERO)).collect();
+
+        // all nodes push each other away ...
+        for (a, pa) in self.positions.iter() {
+            for (b, pb) in self.positions.iter() {
+                if a != b {
+                    let delta = *pa - *pb;
+                    let dist = delta.length().max(0.01);
+                    *disp.get_mut(a).unwrap() += delta / dist * (k * k / dist);
+                }
+            }
+        }
+
+        // ... and edges pull them together
+        for (src, dest, _, _) in graph.edges.iter() {
+            if src == dest {
+                continue;
+            }
+            if let (Some(ps), Some(pd)) = (self.positions.get(src), self.positions.get(dest)) {
+                let delta = *ps - *pd;
+                let dist = delta.length().max(0.01);
+                let force = delta / dist * (dist * dist / k);
+                *disp.get_mut(src).unwrap() -= force;
+                *disp.get_mut(dest).unwrap() += force;
+            }
+        }
+
+        for (key, d) in disp {
+            let len = d.length();
+            if len > 0.0 {
+                let p = self.positions.get_mut(&key).unwrap();
+                *p += d / len * len.min(self.temperature);
+                *p = p.clamp(Vec2::splat(0.05), Vec2::splat(0.95));
+            }
+        }
+
+        self.temperature *= 0.97;
+    }
+}
+
+/// Shows the Markov graphs of the running generators, with the
+/// active node highlighted.
+#[derive(Default)]
+pub struct GraphView {
+    layouts: HashMap<BTreeSet<String>, Layout>,
+}
+
+impl GraphView {
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        graphs: &[GeneratorGraph],
+        palette: &Palette,
+        font_size: f32,
+    ) {
+        self.layouts
+            .retain(|id, _| graphs.iter().any(|g| &g.id_tags == id));
+
+        if graphs.is_empty() {
+            ui.label("nothing running");
+        }
+
+        for graph in graphs {
+            let name: Vec<&str> = graph.id_tags.iter().map(|t| t.as_str()).collect();
+            ui.label(egui::RichText::new(name.join(" ")).font(FontId::monospace(font_size)));
+
+            let layout = self.layouts.entry(graph.id_tags.clone()).or_default();
+            layout.update(graph);
+
+            let (rect, _) =
+                ui.allocate_exact_size(Vec2::new(ui.available_width(), 300.0), Sense::hover());
+            paint_graph(ui, rect, graph, layout, palette.active_node, font_size);
+            ui.separator();
+        }
+    }
+}
+
+fn paint_graph(
+    ui: &egui::Ui,
+    rect: Rect,
+    graph: &GeneratorGraph,
+    layout: &Layout,
+    active_color: Color32,
+    font_size: f32,
+) {
+    let painter = ui.painter_at(rect);
+    let to_screen = |p: Vec2| rect.min + p * rect.size();
+    let radius = font_size;
+    let font = FontId::monospace(font_size * 0.8);
+    let text_color = ui.visuals().strong_text_color();
+    // probabilities get too crowded on bigger graphs
+    let show_probs = graph.edges.len() <= 24;
+
+    for (src, dest, _, prob) in graph.edges.iter() {
+        let (Some(ps), Some(pd)) = (layout.positions.get(src), layout.positions.get(dest)) else {
+            continue;
+        };
+        let color = text_color.gamma_multiply(0.25 + 0.75 * prob);
+        let stroke = Stroke::new(1.0 + 3.0 * prob, color);
+        let a = to_screen(*ps);
+        let b = to_screen(*pd);
+
+        let label_pos = if src == dest {
+            let center = a - Vec2::new(0.0, radius * 1.5);
+            painter.add(CircleShape::stroke(center, radius * 0.8, stroke));
+            center - Vec2::new(0.0, radius)
+        } else {
+            let dir = (b - a).normalized();
+            let start = a + dir * radius;
+            let end = b - dir * radius;
+            painter.arrow(start, end - start, stroke);
+            // a little to the side, so both directions can be read
+            start + (end - start) * 0.5 + dir.rot90() * radius * 0.5
+        };
+
+        if show_probs {
+            painter.text(
+                label_pos,
+                Align2::CENTER_CENTER,
+                format!("{:.0}", prob * 100.0),
+                font.clone(),
+                color,
+            );
+        }
+    }
+
+    for (key, label) in graph.nodes.iter() {
+        let Some(p) = layout.positions.get(key) else {
+            continue;
+        };
+        let center: Pos2 = to_screen(*p);
+        let fill = if graph.active == Some(*key) {
+            active_color
+        } else {
+            ui.visuals().widgets.inactive.bg_fill
+        };
+        painter.circle(center, radius, fill, Stroke::new(1.0, text_color));
+        painter.text(
+            center,
+            Align2::CENTER_CENTER,
+            label,
+            font.clone(),
+            text_color,
+        );
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_follows_graph() {
+        let mut graph = GeneratorGraph {
+            id_tags: BTreeSet::new(),
+            nodes: vec![
+                (1, "a".to_string()),
+                (2, "b".to_string()),
+                (3, "c".to_string()),
+            ],
+            edges: vec![
+                (1, 2, 'b', 0.5),
+                (2, 3, 'c', 1.0),
+                (3, 1, 'a', 1.0),
+                (1, 1, 'a', 0.5),
+            ],
+            active: Some(1),
+        };
+
+        let mut layout = Layout::default();
+        for _ in 0..200 {
+            layout.update(&graph);
+        }
+        assert_eq!(layout.positions.len(), 3);
+        // settled, inside the square, and not on top of each other
+        assert!(layout.temperature < 0.001);
+        for p in layout.positions.values() {
+            assert!((0.05..=0.95).contains(&p.x) && (0.05..=0.95).contains(&p.y));
+        }
+        assert!((layout.positions[&1] - layout.positions[&2]).length() > 0.1);
+
+        graph.nodes.pop();
+        layout.update(&graph);
+        assert!(!layout.positions.contains_key(&3));
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use egui::widgets::text_edit::CCursorRange;
+
+// a performance can be long ...
+const MAX_UNDOS: usize = 1000;
+const MAX_SNAPSHOTS: usize = 500;
+// seconds without change before an undo point is made
+const STABLE_TIME: f64 = 1.0;
+// make an undo point at least that often while typing
+const AUTO_SAVE_INTERVAL: f64 = 30.0;
+
+type State = (CCursorRange, String);
+
+/// Undo and redo for the text edit. Works like egui's `Undoer`, which
+/// makes undo points once the text stops changing for a moment, but
+/// undone states can be redone until the text is edited again.
+#[derive(Clone, Default)]
+pub struct UndoHistory {
+    /// New undo points are added to the back, two adjacent ones are never
+    /// equal. The latest one is often the current state.
+    undos: VecDeque<State>,
+    redos: Vec<State>,
+    // (start time, latest change time, latest state) if the text is changing
+    flux: Option<(f64, f64, State)>,
+}
+
+impl UndoHistory {
+    /// Add an undo point, if there has been a change since the latest one.
+    pub fn add_undo(&mut self, current: &State) {
+        if self.undos.back() != Some(current) {
+            self.undos.push_back(current.clone());
+        }
+        while self.undos.len() > MAX_UNDOS {
+            self.undos.pop_front();
+        }
+        self.flux = None;
+    }
+
+    /// Call this every frame, undo points are made when needed.
+    pub fn feed_state(&mut self, time: f64, current: &State) {
+        let Some(latest) = self.undos.back() else {
+            self.add_undo(current);
+            return;
+        };
+
+        if latest == current {
+            self.flux = None;
+            return;
+        }
+
+        // editing the text makes the undone states unreachable,
+        // moving the cursor around doesn't
+        if latest.1 != current.1 {
+            self.redos.clear();
+        }
+
+        match &mut self.flux {
+            None => self.flux = Some((time, time, current.clone())),
+            Some((start, latest_change, latest_state)) => {
+                if latest_state == current {
+                    if time - *latest_change >= STABLE_TIME {
+                        self.add_undo(current);
+                    }
+                } else if time - *start >= AUTO_SAVE_INTERVAL {
+                    self.add_undo(current);
+                } else {
+                    *latest_change = time;
+                    *latest_state = current.clone();
+                }
+            }
+        }
+    }
+
+    pub fn undo(&mut self, current: &State) -> Option<State> {
+        let has_undo = match self.undos.len() {
+            0 => false,
+            1 => self.undos.back() != Some(current),
+            _ => true,
+        };
+        if !has_undo {
+            return None;
+        }
+
+        self.flux = None;
+        if self.undos.back() == Some(current) {
+            self.undos.pop_back();
+        }
+        self.redos.push(current.clone());
+        // the undo point stays, the text is the same as the latest one now
+        self.undos.back().cloned()
+    }
+
+    pub fn redo(&mut self, current: &State) -> Option<State> {
+        let redo = self.redos.pop()?;
+        self.add_undo(current);
+        self.add_undo(&redo);
+        Some(redo)
+    }
+}
+
+/// One evaluation, as shown in the timeline.
+pub struct Snapshot {
+    pub time: String,
+    pub code: String,
+    // the path of the sketch it came from
+    pub sketch: String,
+    pub failed: bool,
+}
+
+/// Everything that has been evaluated, oldest first, so earlier versions
+/// of a block can be brought back during a performance.
+#[derive(Default)]
+pub struct Timeline {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Timeline {
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Snapshot> {
+        self.snapshots.get(idx)
+    }
+
+    pub fn snapshots(&self) -> impl DoubleEndedIterator<Item = (usize, &Snapshot)> {
+        self.snapshots.iter().enumerate()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+/// Byte ranges of the top-level expressions, skipping comments and strings.
+fn toplevel_blocks(text: &str) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+
+    for (i, c) in text.char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+        if in_string {
+            in_string = c != '"';
+            continue;
+        }
+        match c {
+            ';' => in_comment = true,
+            '"' => in_string = true,
+            '(' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    blocks.push(start..i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// What a block is about, i.e. `(sx 'ga` for a generator, `(fun beat`
+/// for a function or `(tempo` for a tempo change. That's the head plus
+/// the name, if the second token is one (a symbol, an identifier or
+/// a string, like the address in `(fun "/foo" ...)`). Versions of a block
+/// all have the same key.
+fn block_key(code: &str) -> String {
+    let code = code.trim_start();
+    let head_end = code
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| c.is_whitespace() || *c == '(' || *c == ')')
+        .map_or(code.len(), |(i, _)| i);
+    let head = &code[..head_end];
+
+    let rest = code[head_end..].trim_start();
+    let name = if let Some(string) = rest.strip_prefix('"') {
+        string.find('"').map(|end| &rest[..end + 2])
+    } else {
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len());
+        let token = &rest[..end];
+        let is_name = token
+            .trim_start_matches('\'')
+            .starts_with(|c: char| c.is_alphabetic());
+        is_name.then_some(token)
+    };
+
+    match name {
+        Some(name) => format!("{head} {name}"),
+        None => head.to_string(),
+    }
+}
+
+/// Puts an earlier version of a block back into the text, in place of
+/// the current version, or at the end if there's no current version.
+pub fn restore_block(text: &str, code: &str) -> String {
+    let key = block_key(code);
+    match toplevel_blocks(text)
+        .into_iter()
+        .find(|block| block_key(&text[block.clone()]) == key)
+    {
+        Some(block) => format!("{}{}{}", &text[..block.start], code, &text[block.end..]),
+        None => {
+            let mut restored = text.to_string();
+            if !restored.is_empty() && !restored.ends_with('\n') {
+                restored.push('\n');
+            }
+            restored.push_str(code);
+            restored.push('\n');
+            restored
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::text::cursor::CCursor;
+
+    fn state(text: &str) -> State {
+        (
+            CCursorRange::one(CCursor::new(text.chars().count())),
+            text.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = UndoHistory::default();
+        history.feed_state(0.0, &state(""));
+        history.feed_state(1.0, &state("(sx"));
+        history.feed_state(2.5, &state("(sx"));
+        history.feed_state(3.0, &state("(sx 'ga"));
+
+        // the unsaved change and the first undo point can be redone
+        assert_eq!(history.undo(&state("(sx 'ga")), Some(state("(sx")));
+        assert_eq!(history.undo(&state("(sx")), Some(state("")));
+        assert_eq!(history.undo(&state("")), None);
+        assert_eq!(history.redo(&state("")), Some(state("(sx")));
+        assert_eq!(history.redo(&state("(sx")), Some(state("(sx 'ga")));
+        assert_eq!(history.redo(&state("(sx 'ga")), None);
+
+        // new edits clear the redos
+        history.feed_state(4.0, &state("(sx 'ga"));
+        assert_eq!(history.undo(&state("(sx 'ga")), Some(state("(sx")));
+        history.feed_state(5.0, &state("(sx 'gb"));
+        assert_eq!(history.redo(&state("(sx 'gb")), None);
+    }
+
+    #[test]
+    fn test_restore_block() {
+        let text = ";; (sx 'ga in a comment\n(sx 'ga #t (nuc 'a (saw 100)))\n\n(sx 'gb #t (nuc 'b (sqr 100)))\n";
+
+        let restored = restore_block(text, "(sx 'gb #t (nuc 'b (sqr 200)))");
+        assert_eq!(
+            restored,
+            ";; (sx 'ga in a comment\n(sx 'ga #t (nuc 'a (saw 100)))\n\n(sx 'gb #t (nuc 'b (sqr 200)))\n"
+        );
+
+        let restored = restore_block(text, "(sx 'gc #t (nuc 'c (tri 100)))");
+        assert!(restored.starts_with(text));
+        assert!(restored.ends_with("(sx 'gc #t (nuc 'c (tri 100)))\n"));
+
+        assert_eq!(block_key("(tempo 120)"), "(tempo");
+        assert_eq!(block_key("(defpart 'drums)"), "(defpart 'drums");
+        assert_eq!(block_key("(fun beat (a) (saw a))"), "(fun beat");
+        assert_eq!(
+            block_key("(import \"team/drums\" :as 'td)"),
+            "(import \"team/drums\""
+        );
+        assert_eq!(block_key("(let ((a 1)) a)"), "(let");
+    }
+
+    #[test]
+    fn test_restore_function() {
+        let text = "(fun bass (n) (saw (mtof n)))\n(fun beat (a) (nuc 'a (sqr a)))\n";
+
+        // only the function with the same name is replaced
+        let restored = restore_block(text, "(fun beat (a) (nuc 'a (tri a)))");
+        assert_eq!(
+            restored,
+            "(fun bass (n) (saw (mtof n)))\n(fun beat (a) (nuc 'a (tri a)))\n"
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Key bindings for the editor. Plain text editing (typing, deleting,
+//! the arrow keys, copy and paste) is built in, everything else is an
+//! [`Action`] bound to a key, so it can be changed.
+//!
+//! The bindings are read from `keymap.json` in the base folder, if it's
+//! there, i.e.:
+//!
+//! ```json
+//! {
+//!   "preset": "vim",
+//!   "insert": { "ctrl+e": "evaluate" },
+//!   "normal": { "x": "delete-char", "shift+x": "none" }
+//! }
+//! ```
+//!
+//! The preset is `default`, `emacs` or `vim`, and the bindings are added
+//! on top of it (`none` removes a binding). `vim` has a normal mode, in
+//! which typing doesn't insert text, and the `normal` bindings are used
+//! instead of the `insert` ones. Keys are written like `ctrl+shift+z`,
+//! where `cmd` means command on mac and ctrl elsewhere, and the key names
+//! are the ones egui uses (`a` ... `z`, `0` ... `9`, `enter`, `tab`,
+//! `escape`, `space`, `up`, `f1`, ...).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+/// What a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Evaluate,
+    FormatSexp,
+    ToggleComment,
+    SelectSexp,
+    ToggleSelection,
+    ClearSelection,
+    SelectAll,
+    CutSelection,
+    KillLine,
+    KillLineBackward,
+    DeleteChar,
+    Undo,
+    Redo,
+    ForwardChar,
+    BackwardChar,
+    NextLine,
+    PreviousLine,
+    ForwardWord,
+    BackwardWord,
+    LineStart,
+    LineEnd,
+    ForwardSexp,
+    BackwardSexp,
+    SexpStart,
+    InsertMode,
+    Append,
+    InsertAtLineStart,
+    AppendAtLineEnd,
+    OpenLineBelow,
+    NormalMode,
+}
+
+const ACTION_NAMES: &[(&str, Action)] = &[
+    ("evaluate", Action::Evaluate),
+    ("format-sexp", Action::FormatSexp),
+    ("toggle-comment", Action::ToggleComment),
+    ("select-sexp", Action::SelectSexp),
+    ("toggle-selection", Action::ToggleSelection),
+    ("clear-selection", Action::ClearSelection),
+    ("select-all", Action::SelectAll),
+    ("cut-selection", Action::CutSelection),
+    ("kill-line", Action::KillLine),
+    ("kill-line-backward", Action::KillLineBackward),
+    ("delete-char", Action::DeleteChar),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("forward-char", Action::ForwardChar),
+    ("backward-char", Action::BackwardChar),
+    ("next-line", Action::NextLine),
+    ("previous-line", Action::PreviousLine),
+    ("forward-word", Action::ForwardWord),
+    ("backward-word", Action::BackwardWord),
+    ("line-start", Action::LineStart),
+    ("line-end", Action::LineEnd),
+    ("forward-sexp", Action::ForwardSexp),
+    ("backward-sexp", Action::BackwardSexp),
+    ("sexp-start", Action::SexpStart),
+    ("insert-mode", Action::InsertMode),
+    ("append", Action::Append),
+    ("insert-at-line-start", Action::InsertAtLineStart),
+    ("append-at-line-end", Action::AppendAtLineEnd),
+    ("open-line-below", Action::OpenLineBelow),
+    ("normal-mode", Action::NormalMode),
+];
+
+// the keys that can be bound, by name
+const KEYS: &[Key] = &[
+    Key::ArrowDown,
+    Key::ArrowLeft,
+    Key::ArrowRight,
+    Key::ArrowUp,
+    Key::Escape,
+    Key::Tab,
+    Key::Backspace,
+    Key::Enter,
+    Key::Space,
+    Key::Insert,
+    Key::Delete,
+    Key::Home,
+    Key::End,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Minus,
+    Key::PlusEquals,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+// what the editor did before keymaps could be configured
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("cmd+enter", Action::Evaluate),
+    ("tab", Action::FormatSexp),
+    ("cmd+t", Action::ToggleComment),
+    ("ctrl+s", Action::SelectSexp),
+    ("cmd+space", Action::ToggleSelection),
+    ("ctrl+g", Action::ClearSelection),
+    ("escape", Action::ClearSelection),
+    ("cmd+a", Action::SelectAll),
+    ("ctrl+w", Action::CutSelection),
+    ("ctrl+k", Action::KillLine),
+    ("ctrl+u", Action::KillLineBackward),
+    ("cmd+z", Action::Undo),
+    ("cmd+shift+z", Action::Redo),
+    ("cmd+y", Action::Redo),
+    ("cmd+f", Action::ForwardChar),
+];
+
+const EMACS_BINDINGS: &[(&str, Action)] = &[
+    ("ctrl+alt+x", Action::Evaluate),
+    ("ctrl+f", Action::ForwardChar),
+    ("ctrl+b", Action::BackwardChar),
+    ("ctrl+n", Action::NextLine),
+    ("ctrl+p", Action::PreviousLine),
+    ("alt+f", Action::ForwardWord),
+    ("alt+b", Action::BackwardWord),
+    ("ctrl+a", Action::LineStart),
+    ("ctrl+e", Action::LineEnd),
+    ("ctrl+d", Action::DeleteChar),
+    ("ctrl+alt+f", Action::ForwardSexp),
+    ("ctrl+alt+b", Action::BackwardSexp),
+    ("ctrl+alt+a", Action::SexpStart),
+    ("alt+w", Action::SelectSexp),
+];
+
+const VIM_INSERT_BINDINGS: &[(&str, Action)] = &[("escape", Action::NormalMode)];
+
+const VIM_NORMAL_BINDINGS: &[(&str, Action)] = &[
+    ("enter", Action::Evaluate),
+    ("h", Action::BackwardChar),
+    ("j", Action::NextLine),
+    ("k", Action::PreviousLine),
+    ("l", Action::ForwardChar),
+    ("backspace", Action::BackwardChar),
+    ("w", Action::ForwardWord),
+    ("b", Action::BackwardWord),
+    ("0", Action::LineStart),
+    ("shift+4", Action::LineEnd),      // $
+    ("shift+0", Action::ForwardSexp),  // )
+    ("shift+9", Action::BackwardSexp), // (
+    ("shift+h", Action::SexpStart),
+    ("i", Action::InsertMode),
+    ("a", Action::Append),
+    ("shift+i", Action::InsertAtLineStart),
+    ("shift+a", Action::AppendAtLineEnd),
+    ("o", Action::OpenLineBelow),
+    ("x", Action::DeleteChar),
+    ("shift+d", Action::KillLine),
+    ("u", Action::Undo),
+    ("ctrl+r", Action::Redo),
+    ("v", Action::ToggleSelection),
+    ("shift+c", Action::ToggleComment),
+];
+
+/// "ctrl+shift+z" and the like
+fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(|p| p.trim()).collect();
+    let key_name = parts.pop()?;
+    let key = KEYS
+        .iter()
+        .find(|k| k.name().eq_ignore_ascii_case(key_name))?;
+
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        modifiers = modifiers
+            | match part.to_lowercase().as_str() {
+                "ctrl" => Modifiers::CTRL,
+                "cmd" | "command" => Modifiers::COMMAND,
+                "alt" | "meta" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                _ => return None,
+            };
+    }
+
+    Some(KeyboardShortcut::new(modifiers, *key))
+}
+
+fn parse_action(name: &str) -> Option<Action> {
+    ACTION_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, action)| *action)
+}
+
+#[derive(serde::Deserialize)]
+struct KeymapConfig {
+    preset: Option<String>,
+    #[serde(default)]
+    insert: BTreeMap<String, String>,
+    #[serde(default)]
+    normal: BTreeMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    insert: Vec<(KeyboardShortcut, Action)>,
+    normal: Vec<(KeyboardShortcut, Action)>,
+    // whether there's a normal mode
+    modal: bool,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap {
+            insert: Vec::new(),
+            normal: Vec::new(),
+            modal: false,
+        };
+        keymap.bind_all(false, DEFAULT_BINDINGS);
+        keymap
+    }
+}
+
+impl Keymap {
+    pub fn emacs() -> Self {
+        let mut keymap = Keymap::default();
+        keymap.bind_all(false, EMACS_BINDINGS);
+        keymap
+    }
+
+    pub fn vim() -> Self {
+        let mut keymap = Keymap::default();
+        keymap.modal = true;
+        keymap.normal = keymap.insert.clone();
+        keymap.bind_all(false, VIM_INSERT_BINDINGS);
+        keymap.bind_all(true, VIM_NORMAL_BINDINGS);
+        keymap
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Keymap::default()),
+            "emacs" => Some(Keymap::emacs()),
+            "vim" => Some(Keymap::vim()),
+            _ => None,
+        }
+    }
+
+    pub fn is_modal(&self) -> bool {
+        self.modal
+    }
+
+    /// The action bound to the key, if any. Later bindings take
+    /// precedence, so they can override the ones of a preset.
+    pub fn action(&self, normal_mode: bool, key: Key, modifiers: Modifiers) -> Option<Action> {
+        let bindings = if normal_mode && self.modal {
+            &self.normal
+        } else {
+            &self.insert
+        };
+        bindings
+            .iter()
+            .find(|(shortcut, _)| shortcut.key == key && modifiers.matches(shortcut.modifiers))
+            .map(|(_, action)| *action)
+    }
+
+    fn bind(&mut self, normal: bool, shortcut: KeyboardShortcut, action: Option<Action>) {
+        let bindings = if normal {
+            &mut self.normal
+        } else {
+            &mut self.insert
+        };
+        bindings.retain(|(s, _)| *s != shortcut);
+        if let Some(action) = action {
+            bindings.insert(0, (shortcut, action));
+        }
+    }
+
+    fn bind_all(&mut self, normal: bool, bindings: &[(&str, Action)]) {
+        for (shortcut, action) in bindings {
+            self.bind(normal, parse_shortcut(shortcut).unwrap(), Some(*action));
+        }
+    }
+
+    /// a keymap from the JSON described above
+    pub fn from_config(config: &str) -> Result<Self, String> {
+        let config: KeymapConfig = serde_json::from_str(config).map_err(|e| e.to_string())?;
+
+        let preset = config.preset.as_deref().unwrap_or("default");
+        let mut keymap =
+            Keymap::preset(preset).ok_or_else(|| format!("unknown preset '{preset}'"))?;
+
+        for (normal, bindings) in [(false, &config.insert), (true, &config.normal)] {
+            for (shortcut, action) in bindings {
+                let parsed_shortcut =
+                    parse_shortcut(shortcut).ok_or_else(|| format!("unknown key '{shortcut}'"))?;
+                let parsed_action = if action == "none" {
+                    None
+                } else {
+                    Some(parse_action(action).ok_or_else(|| format!("unknown action '{action}'"))?)
+                };
+                keymap.bind(normal, parsed_shortcut, parsed_action);
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// the keymap from `keymap.json` in the base folder, or the default
+    pub fn load(base_dir: &Path) -> Self {
+        let path = base_dir.join("keymap.json");
+        if !path.exists() {
+            return Keymap::default();
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|config| Keymap::from_config(&config))
+        {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                println!("couldn't load keymap {}: {e}", path.display());
+                Keymap::default()
+            }
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymaps() {
+        let keymap = Keymap::default();
+        assert!(!keymap.is_modal());
+        assert_eq!(
+            keymap.action(false, Key::Enter, Modifiers::COMMAND | Modifiers::CTRL),
+            Some(Action::Evaluate)
+        );
+        assert_eq!(keymap.action(false, Key::Enter, Modifiers::NONE), None);
+        assert_eq!(
+            keymap.action(false, Key::Z, Modifiers::COMMAND | Modifiers::SHIFT),
+            Some(Action::Redo)
+        );
+
+        // emacs keys take precedence over select all
+        let keymap = Keymap::emacs();
+        assert_eq!(
+            keymap.action(false, Key::A, Modifiers::COMMAND | Modifiers::CTRL),
+            Some(Action::LineStart)
+        );
+
+        let keymap = Keymap::vim();
+        assert_eq!(
+            keymap.action(false, Key::Escape, Modifiers::NONE),
+            Some(Action::NormalMode)
+        );
+        assert_eq!(
+            keymap.action(true, Key::J, Modifiers::NONE),
+            Some(Action::NextLine)
+        );
+        assert_eq!(
+            keymap.action(true, Key::A, Modifiers::SHIFT),
+            Some(Action::AppendAtLineEnd)
+        );
+        assert_eq!(keymap.action(false, Key::J, Modifiers::NONE), None);
+    }
+
+    #[test]
+    fn test_keymap_config() {
+        let keymap = Keymap::from_config(
+            r#"{"preset": "vim", "insert": {"ctrl+e": "evaluate"}, "normal": {"x": "none"}}"#,
+        )
+        .unwrap();
+        assert!(keymap.is_modal());
+        assert_eq!(
+            keymap.action(false, Key::E, Modifiers::CTRL),
+            Some(Action::Evaluate)
+        );
+        assert_eq!(keymap.action(true, Key::X, Modifiers::NONE), None);
+
+        assert!(Keymap::from_config(r#"{"preset": "ed"}"#).is_err());
+        assert!(Keymap::from_config(r#"{"insert": {"hyper+x": "undo"}}"#).is_err());
+        assert!(Keymap::from_config(r#"{"insert": {"ctrl+x": "explode"}}"#).is_err());
+    }
+}
@@ -8,7 +8,14 @@ use egui::widgets::text_edit::{CCursorRange, CursorRange};
 
 use parking_lot::Mutex;
 
+use crate::editor::completion::{self, Vocabulary};
+use crate::editor::history::UndoHistory;
+use crate::editor::keymap::{Action, Keymap};
 use crate::file_interpreter;
+use crate::parser::EvalError;
+
+/// Evaluates the code it's given, the error spans are relative to that code.
+pub type EvalCallback = dyn FnMut(&String) -> Result<(), EvalError>;
 
 /// The text edit state stored between frames.
 #[derive(Clone, Default)]
@@ -26,7 +33,15 @@ pub struct LivecodeTextEditState {
     #[serde(skip)]
     pub flash_alpha: u8, // soft fade out
     #[serde(skip)]
+    pub error_cursor_range: Option<CursorRange>, // underlined until the next edit
+    #[serde(skip)]
+    pub eval_result: Option<(String, Result<(), EvalError>)>, // handed out once per evaluation
+    #[serde(skip)]
     pub selection_toggle: bool, // toggle selection emacs-style
+    #[serde(skip)]
+    pub completion: Option<usize>, // the selected candidate, if the popup is open
+    #[serde(skip)]
+    pub normal_mode: bool, // vim-style, if the keymap has one
 
     #[serde(skip)]
     pub opening_paren_range: Option<CursorRange>, // mark parenthesis
@@ -36,7 +51,7 @@ pub struct LivecodeTextEditState {
 
     /// Wrapped in Arc for cheaper clones.
     #[cfg_attr(feature = "serde", serde(skip))]
-    pub undoer: Arc<Mutex<Undoer>>,
+    pub undoer: Arc<Mutex<UndoHistory>>,
 
     // If IME candidate window is shown on this text edit.
     #[cfg_attr(feature = "serde", serde(skip))]
@@ -66,9 +81,11 @@ pub struct LivecodeTextEditOutput {
 
     /// Where the text cursor is.
     pub cursor_range: Option<egui::widgets::text_edit::CursorRange>,
-}
 
-type Undoer = egui::util::undoer::Undoer<(CCursorRange, String)>;
+    /// If something has been evaluated in this frame, the code and the
+    /// result, with error spans relative to the whole text.
+    pub eval_result: Option<(String, Result<(), EvalError>)>,
+}
 
 impl LivecodeTextEditState {
     pub fn load(ctx: &Context, id: Id) -> Option<Self> {
@@ -113,14 +130,20 @@ pub struct LivecodeTextEdit<'t> {
     text: &'t mut dyn TextBuffer,
     id: Option<Id>,
     id_source: Option<Id>,
+    history_id: Option<Id>,
     font_selection: FontSelection,
     text_color: Option<Color32>,
     layouter: Option<&'t mut dyn FnMut(&Ui, &str, f32) -> Arc<Galley>>,
     desired_width: Option<f32>,
     desired_height_rows: usize,
     cursor_at_end: bool,
-    eval_callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    eval_callback: Option<Arc<Mutex<EvalCallback>>>,
     karl_yerkes_mode: bool,
+    vocabulary: Option<&'t Vocabulary>,
+    keymap: Option<&'t Keymap>,
+    paren_color: Color32,
+    error_color: Color32,
+    cursor_color: Color32,
 }
 
 impl<'t> WidgetWithState for LivecodeTextEdit<'t> {
@@ -134,6 +157,7 @@ impl<'t> LivecodeTextEdit<'t> {
             text,
             id: None,
             id_source: None,
+            history_id: None,
             font_selection: Default::default(),
             text_color: None,
             layouter: None,
@@ -142,6 +166,11 @@ impl<'t> LivecodeTextEdit<'t> {
             cursor_at_end: true,
             eval_callback: None,
             karl_yerkes_mode: false,
+            vocabulary: None,
+            keymap: None,
+            paren_color: Color32::from_rgba_unmultiplied(226, 33, 115, 190),
+            error_color: Color32::from_rgb(230, 40, 40),
+            cursor_color: Color32::from_rgba_unmultiplied(220, 220, 220, 120),
         }
     }
 
@@ -153,7 +182,21 @@ impl<'t> LivecodeTextEdit<'t> {
         self.font(FontId::monospace(15.0))
     }
 
-    pub fn eval_callback(mut self, callback: &Arc<Mutex<dyn FnMut(&String)>>) -> Self {
+    /// A source for the unique [`Id`], so several editors can have their
+    /// own state (cursor, undo history etc.).
+    pub fn id_source(mut self, id_source: impl std::hash::Hash) -> Self {
+        self.id_source = Some(Id::new(id_source));
+        self
+    }
+
+    /// Editors with the same history source share their undo history,
+    /// i.e. if they show the same text, each with its own cursor.
+    pub fn history_id_source(mut self, history_id_source: impl std::hash::Hash) -> Self {
+        self.history_id = Some(Id::new(history_id_source));
+        self
+    }
+
+    pub fn eval_callback(mut self, callback: &Arc<Mutex<EvalCallback>>) -> Self {
         self.eval_callback = Some(Arc::clone(callback));
         self
     }
@@ -168,6 +211,36 @@ impl<'t> LivecodeTextEdit<'t> {
         self
     }
 
+    /// How matching parens are highlighted.
+    pub fn paren_color(mut self, color: Color32) -> Self {
+        self.paren_color = color;
+        self
+    }
+
+    /// How the place of an error is underlined.
+    pub fn error_color(mut self, color: Color32) -> Self {
+        self.error_color = color;
+        self
+    }
+
+    /// The block cursor in normal mode.
+    pub fn cursor_color(mut self, color: Color32) -> Self {
+        self.cursor_color = color;
+        self
+    }
+
+    /// Key bindings, the default ones if not set.
+    pub fn keymap(mut self, keymap: &'t Keymap) -> Self {
+        self.keymap = Some(keymap);
+        self
+    }
+
+    /// Offer completions from this vocabulary while typing.
+    pub fn vocabulary(mut self, vocabulary: &'t Vocabulary) -> Self {
+        self.vocabulary = Some(vocabulary);
+        self
+    }
+
     /// Override how text is being shown inside the `LivecodeTextEdit`.
     ///
     /// This can be used to implement things like syntax highlighting.
@@ -179,7 +252,7 @@ impl<'t> LivecodeTextEdit<'t> {
     /// The arguments is the enclosing [`Ui`] (so you can access e.g. [`Ui::fonts`]),
     /// the text and the wrap width.
     ///
-    /// ```
+    /// ```ignore
     /// # egui::__run_test_ui(|ui| {
     /// # let mut my_code = String::new();
     /// # fn my_memoized_highlighter(s: &str) -> egui::text::LayoutJob { Default::default() }
@@ -245,6 +318,7 @@ impl<'t> LivecodeTextEdit<'t> {
             text,
             id,
             id_source,
+            history_id,
             font_selection,
             text_color,
             layouter,
@@ -253,6 +327,11 @@ impl<'t> LivecodeTextEdit<'t> {
             cursor_at_end,
             eval_callback,
             karl_yerkes_mode,
+            vocabulary,
+            keymap,
+            paren_color,
+            error_color,
+            cursor_color,
         } = self;
 
         let text_color = text_color
@@ -263,6 +342,7 @@ impl<'t> LivecodeTextEdit<'t> {
         let prev_text = text.as_str().to_owned();
         let font_id = font_selection.resolve(ui.style());
         let row_height = ui.fonts(|f| f.row_height(&font_id));
+        let popup_font = font_id.clone();
 
         const MIN_WIDTH: f32 = 24.0; // Never make a `LivecodeTextEdit` more narrow than this.
         let available_width = ui.available_width().at_least(MIN_WIDTH);
@@ -307,9 +387,27 @@ impl<'t> LivecodeTextEdit<'t> {
             }
         });
         let mut state = LivecodeTextEditState::load(ui.ctx(), id).unwrap_or_default();
+        if let Some(history_id) = history_id {
+            state.undoer = ui.ctx().data_mut(|d| {
+                d.get_temp_mut_or_default::<Arc<Mutex<UndoHistory>>>(history_id)
+                    .clone()
+            });
+        }
 
         state.karl_yerkes_mode = karl_yerkes_mode;
 
+        let default_keymap;
+        let keymap = match keymap {
+            Some(keymap) => keymap,
+            None => {
+                default_keymap = Keymap::default();
+                &default_keymap
+            }
+        };
+        if !keymap.is_modal() {
+            state.normal_mode = false;
+        }
+
         // On touch screens (e.g. mobile in egui_web), should
         // dragging select text, or scroll the enclosing `ScrollArea` (if any)?
         // Since currently copying selected text in not supported on `egui_web`,
@@ -362,6 +460,7 @@ impl<'t> LivecodeTextEdit<'t> {
                 }));
             } else if allow_drag_to_select {
                 if response.hovered() && ui.input(|i| i.pointer.any_pressed()) {
+                    state.completion = None;
                     ui.memory_mut(|mem| mem.request_focus(id));
                     if ui.input(|i| i.modifiers.shift) {
                         if let Some(mut cursor_range) = state.cursor_range(&galley) {
@@ -417,6 +516,8 @@ impl<'t> LivecodeTextEdit<'t> {
                 wrap_width,
                 default_cursor_range,
                 eval_callback,
+                vocabulary,
+                keymap,
             );
 
             if changed {
@@ -439,6 +540,10 @@ impl<'t> LivecodeTextEdit<'t> {
         if ui.is_rect_visible(rect) {
             painter.galley(text_draw_pos, galley.clone());
 
+            if let Some(error_range) = state.error_cursor_range {
+                paint_underline(&painter, text_draw_pos, &galley, &error_range, error_color);
+            }
+
             if ui.memory(|mem| mem.has_focus(id)) {
                 if let Some(cursor_range) = state.cursor_range(&galley) {
                     // We paint the cursor on top of the text, in case
@@ -479,7 +584,7 @@ impl<'t> LivecodeTextEdit<'t> {
                                 text_draw_pos,
                                 &galley,
                                 &opening_cursor,
-                                Some(Color32::from_rgba_unmultiplied(226, 33, 115, 190)),
+                                Some(paren_color),
                             );
                             paint_cursor_selection(
                                 ui,
@@ -487,11 +592,24 @@ impl<'t> LivecodeTextEdit<'t> {
                                 text_draw_pos,
                                 &galley,
                                 &closing_cursor,
-                                Some(Color32::from_rgba_unmultiplied(226, 33, 115, 190)),
+                                Some(paren_color),
                             );
                         }
                     }
 
+                    if state.normal_mode {
+                        // block cursor, so the mode can be seen
+                        let next = galley.cursor_right_one_character(&cursor_range.primary);
+                        paint_cursor_selection(
+                            ui,
+                            &painter,
+                            text_draw_pos,
+                            &galley,
+                            &CursorRange::two(cursor_range.primary, next),
+                            Some(cursor_color),
+                        );
+                    }
+
                     let cursor_pos = paint_cursor_end(
                         ui,
                         row_height,
@@ -505,6 +623,20 @@ impl<'t> LivecodeTextEdit<'t> {
                         ui.scroll_to_rect(cursor_pos, None); // keep cursor in view
                     }
 
+                    if let (Some(selected), Some(vocabulary)) = (state.completion, vocabulary) {
+                        let offset =
+                            text.byte_index_from_char_index(cursor_range.primary.ccursor.index);
+                        let (_, candidates) = vocabulary.completions(text.as_str(), offset);
+                        completion::show_popup(
+                            ui.ctx(),
+                            id,
+                            cursor_pos.left_bottom(),
+                            &candidates,
+                            selected,
+                            popup_font,
+                        );
+                    }
+
                     if text.is_mutable() {
                         // egui_web uses `text_cursor_pos` when showing IME,
                         // so only set it when text is editable and visible!
@@ -516,6 +648,7 @@ impl<'t> LivecodeTextEdit<'t> {
             }
         }
 
+        let eval_result = state.eval_result.take();
         state.clone().store(ui.ctx(), id);
 
         if response.changed() {
@@ -535,6 +668,7 @@ impl<'t> LivecodeTextEdit<'t> {
             galley,
             state,
             cursor_range,
+            eval_result,
         }
     }
 }
@@ -546,7 +680,7 @@ fn call_callback(
     cursor_range: &CursorRange,
     text: &dyn TextBuffer,
     galley: &Galley,
-    eval_callback: &Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    eval_callback: &Option<Arc<Mutex<EvalCallback>>>,
     flash: bool,
 ) {
     if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
@@ -555,17 +689,46 @@ fn call_callback(
             secondary: galley.from_ccursor(sexp_cursors.secondary),
         };
 
-        // flash selected sexp ...
         let sel = selected_str(text, &cup);
 
-        if flash {
-            state.flash_cursor_range = Some(cup);
-            state.flash_alpha = 240; // set flash alpha ()
-        }
-
         if let Some(cb) = eval_callback {
             let mut cb_loc = cb.lock();
-            cb_loc(&sel.to_string());
+            let result = cb_loc(&sel.to_string());
+            match &result {
+                Ok(_) => {
+                    state.error_cursor_range = None;
+                    // flash selected sexp ...
+                    if flash {
+                        state.flash_cursor_range = Some(cup);
+                        state.flash_alpha = 240; // set flash alpha ()
+                    }
+                }
+                Err(e) => {
+                    // underline the part that failed, or the whole
+                    // sexp if we don't know where it failed
+                    let [sexp_start, _] = cup.sorted_cursors();
+                    state.error_cursor_range = Some(
+                        e.span()
+                            .map(|span| {
+                                let start = sexp_start.ccursor.index
+                                    + sel.get(..span.start).unwrap_or(sel).chars().count();
+                                let len = sel
+                                    .get(span.start..span.end)
+                                    .map(|s| s.chars().count())
+                                    .unwrap_or(0)
+                                    .max(1);
+                                CursorRange::two(
+                                    galley.from_ccursor(CCursor::new(start)),
+                                    galley.from_ccursor(CCursor::new(start + len)),
+                                )
+                            })
+                            .unwrap_or(cup),
+                    );
+                }
+            }
+            // make the spans point into the whole text
+            let offset = text.byte_index_from_char_index(cup.sorted_cursors()[0].ccursor.index);
+            state.eval_result = Some((sel.to_string(), result.map_err(|e| e.shifted(offset))));
         } else {
             println!("no callback!");
         }
@@ -582,7 +745,9 @@ fn livecode_events(
     layouter: &mut dyn FnMut(&Ui, &str, f32) -> Arc<Galley>,
     wrap_width: f32,
     default_cursor_range: CursorRange,
-    eval_callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    eval_callback: Option<Arc<Mutex<EvalCallback>>>,
+    vocabulary: Option<&Vocabulary>,
+    keymap: &Keymap,
 ) -> (bool, CursorRange) {
     let mut cursor_range = state.cursor_range(&*galley).unwrap_or(default_cursor_range);
 
@@ -598,10 +763,54 @@ fn livecode_events(
     };
 
     let mut any_change = false;
+    // egui sends the text of a key after the key itself, so the letter
+    // that switched into insert mode must not be inserted
+    let mut swallow_text = false;
+    let mut typed = false;
 
     let events = ui.input(|i| i.events.clone()); // avoid dead-lock by cloning. TODO: optimize
     for event in &events {
+        let was_normal = state.normal_mode;
+        let action = match event {
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => keymap
+                .action(state.normal_mode, *key, *modifiers)
+                // everything is evaluated anyway
+                .filter(|a| !(*a == Action::Evaluate && state.karl_yerkes_mode)),
+            _ => None,
+        };
+
         let did_mutate_text = match event {
+            Event::Key {
+                key: key @ (Key::ArrowUp | Key::ArrowDown | Key::Tab | Key::Enter | Key::Escape),
+                pressed: true,
+                modifiers,
+                ..
+            } if modifiers.is_none() && state.completion.is_some() && vocabulary.is_some() => {
+                completion_key(state, vocabulary.unwrap(), text, &cursor_range, *key)
+            }
+            Event::Key { .. } if action.is_some() => match action.unwrap() {
+                Action::Evaluate => {
+                    // clear selection
+                    state.selection_toggle = false;
+                    state.clear_paren_selection();
+                    // every evaluated version can be undone to
+                    state
+                        .undoer
+                        .lock()
+                        .add_undo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()));
+                    call_callback(state, &cursor_range, text, galley, &eval_callback, true);
+                    break; // need to break here because of callback move ...
+                }
+                action => run_action(ui, action, &mut cursor_range, text, galley, state),
+            },
+            // in normal mode, typing doesn't insert anything
+            Event::Text(_) if swallow_text => None,
+            Event::Text(_) | Event::Paste(_) if state.normal_mode => None,
             Event::Copy => {
                 // clear selection
                 state.selection_toggle = false;
@@ -633,57 +842,8 @@ fn livecode_events(
                     None
                 }
             }
-            Event::Key {
-                key: Key::W,
-                pressed: true,
-                modifiers,
-                ..
-            } => {
-                if modifiers.ctrl {
-                    // clear selection
-                    state.selection_toggle = false;
-                    state.clear_paren_selection();
-                    copy_if_not_password(ui, selected_str(text, &cursor_range).to_owned());
-                    Some(CCursorRange::one(delete_selected(text, &cursor_range)))
-                } else {
-                    None
-                }
-            }
-            Event::Key {
-                key: Key::S,
-                pressed: true,
-                modifiers,
-                ..
-            } => {
-                if modifiers.ctrl {
-                    if let Some(sexp_cursors) =
-                        find_current_sexp(text.as_str(), &cursor_range.as_ccursor_range().primary)
-                    {
-                        //let old_cursor = cursor_range.as_ccursor_range();
-                        state.selection_toggle = true;
-                        Some(CCursorRange::two(sexp_cursors.0, sexp_cursors.1))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }
-
-            Event::Key {
-                key: Key::G,
-                pressed: true,
-                modifiers,
-                ..
-            } => {
-                if modifiers.ctrl {
-                    // clear selection
-                    state.selection_toggle = false;
-                    cursor_range.secondary = cursor_range.primary;
-                }
-                None
-            }
             Event::Text(text_to_insert) => {
+                typed = true;
                 // clear selection
                 state.selection_toggle = false;
                 state.clear_paren_selection();
@@ -740,121 +900,23 @@ fn livecode_events(
                     None
                 }
             }
-            Event::Key {
-                key: Key::Tab,
-                pressed: true,
-                ..
-            } => {
-                if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
-                    let old_cursor = cursor_range.as_ccursor_range();
-                    let cup = CursorRange {
-                        primary: galley.from_ccursor(sexp_cursors.primary),
-                        secondary: galley.from_ccursor(sexp_cursors.secondary),
-                    };
-
-                    let formatted = { format_sexp(selected_str(text, &cup)) };
-
-                    let mut ccursor = delete_selected(text, &cup);
-                    insert_text(&mut ccursor, text, &formatted);
-                    Some(CCursorRange::one(old_cursor.primary))
-                } else {
-                    None
-                }
-            }
-            Event::Key {
-                key: Key::F,
-                pressed: true,
-                modifiers,
-                ..
-            } if modifiers.command => {
-                cursor_range.primary = galley.cursor_right_one_character(&cursor_range.primary);
-                Some(CCursorRange::one(cursor_range.primary.ccursor))
-            }
             Event::Key {
                 key: Key::Enter,
                 pressed: true,
-                modifiers,
                 ..
             } => {
                 // clear selection
                 state.selection_toggle = false;
                 state.clear_paren_selection();
-
-                if modifiers.command && !state.karl_yerkes_mode {
-                    call_callback(state, &cursor_range, text, galley, &eval_callback, true);
-                    break; // need to break here because of callback move ...
-                } else {
-                    // let's check if we're in an s-expression
-                    // auto-indent in that case
-                    if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
-                        let mut ccursorp = cursor_range.as_ccursor_range();
-                        // only need indentation, so let's get the text
-                        // from the beginning of the current s-expression
-                        // to the current cursor pos
-                        let cup = CursorRange {
-                            primary: galley.from_ccursor(sexp_cursors.primary),
-                            secondary: galley.from_ccursor(ccursorp.primary),
-                        };
-
-                        // get indentation level
-                        let indent_level = sexp_indent_level(selected_str(text, &cup));
-                        // insert line break and indentation ...
-                        insert_text(&mut ccursorp.secondary, text, "\n");
-                        if indent_level > 0 {
-                            for _ in 0..indent_level {
-                                insert_text(&mut ccursorp.secondary, text, "  ");
-                            }
-                        }
-                        Some(CCursorRange::one(ccursorp.secondary))
-                    } else {
-                        let mut ccursor = delete_selected(text, &cursor_range);
-                        insert_text(&mut ccursor, text, "\n");
-                        Some(CCursorRange::one(ccursor))
-                    }
-                }
-            }
-            Event::Key {
-                key: Key::Z,
-                pressed: true,
-                modifiers,
-                ..
-            } if modifiers.command && !modifiers.shift => {
-                // TODO: redo
-                if let Some((undo_ccursor_range, undo_txt)) = state
-                    .undoer
-                    .lock()
-                    .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()))
-                {
-                    text.replace(undo_txt);
-                    Some(*undo_ccursor_range)
-                } else {
-                    None
-                }
-            }
-            Event::Key {
-                key: Key::Escape,
-                pressed: true,
-                ..
-            } => {
-                // clear selection
-                state.selection_toggle = false;
-
-                cursor_range.secondary = cursor_range.primary;
-                //ui.memory().surrender_focus(id);
-                break;
+                Some(insert_newline(text, galley, &cursor_range))
             }
             Event::Key {
                 key: Key::Space,
                 pressed: true,
                 modifiers,
                 ..
-            } => {
-                if modifiers.command {
-                    state.selection_toggle = !state.selection_toggle;
-                } else {
-                    state.clear_paren_selection();
-                }
-
+            } if !modifiers.command => {
+                state.clear_paren_selection();
                 None
             }
             Event::Key {
@@ -862,7 +924,13 @@ fn livecode_events(
                 pressed: true,
                 modifiers,
                 ..
-            } => on_key_press(&mut cursor_range, text, galley, *key, modifiers, state),
+            } => {
+                // keep completing while the word is corrected
+                if *key != Key::Backspace {
+                    state.completion = None;
+                }
+                on_key_press(&mut cursor_range, text, galley, *key, modifiers, state)
+            }
 
             Event::CompositionStart => {
                 state.has_ime = true;
@@ -899,12 +967,21 @@ fn livecode_events(
             _ => None,
         };
 
+        match event {
+            Event::Key { pressed: true, .. } => swallow_text = was_normal && !state.normal_mode,
+            Event::Text(_) => swallow_text = false,
+            _ => {}
+        }
+
         if let Some(new_ccursor_range) = did_mutate_text {
             any_change = true;
 
             // Layout again to avoid frame delay, and to keep `text` and `galley` in sync.
             *galley = layouter(ui, text.as_str(), wrap_width);
 
+            // the error position is most likely outdated now
+            state.error_cursor_range = None;
+
             // Set cursor_range using new galley:
             cursor_range = CursorRange {
                 primary: galley.from_ccursor(new_ccursor_range.primary),
@@ -919,6 +996,18 @@ fn livecode_events(
 
     state.set_cursor_range(Some(cursor_range));
 
+    if let Some(vocabulary) = vocabulary {
+        // typing opens the completion popup, if there's anything to complete
+        if typed && !state.normal_mode {
+            state.completion = Some(0);
+        }
+        let offset = text.byte_index_from_char_index(cursor_range.primary.ccursor.index);
+        if state.completion.is_some() && vocabulary.completions(text.as_str(), offset).1.is_empty()
+        {
+            state.completion = None;
+        }
+    }
+
     state.undoer.lock().feed_state(
         ui.input(|i| i.time),
         &(cursor_range.as_ccursor_range(), text.as_str().to_owned()),
@@ -927,6 +1016,260 @@ fn livecode_events(
     (any_change, cursor_range)
 }
 
+/// Line break, indented if it's inside an s-expression.
+fn insert_newline(
+    text: &mut dyn TextBuffer,
+    galley: &Galley,
+    cursor_range: &CursorRange,
+) -> CCursorRange {
+    // let's check if we're in an s-expression
+    // auto-indent in that case
+    if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
+        let mut ccursorp = cursor_range.as_ccursor_range();
+        // only need indentation, so let's get the text
+        // from the beginning of the current s-expression
+        // to the current cursor pos
+        let cup = CursorRange {
+            primary: galley.from_ccursor(sexp_cursors.primary),
+            secondary: galley.from_ccursor(ccursorp.primary),
+        };
+
+        // get indentation level
+        let indent_level = sexp_indent_level(selected_str(text, &cup));
+        // insert line break and indentation ...
+        insert_text(&mut ccursorp.secondary, text, "\n");
+        if indent_level > 0 {
+            for _ in 0..indent_level {
+                insert_text(&mut ccursorp.secondary, text, "  ");
+            }
+        }
+        CCursorRange::one(ccursorp.secondary)
+    } else {
+        let mut ccursor = delete_selected(text, cursor_range);
+        insert_text(&mut ccursor, text, "\n");
+        CCursorRange::one(ccursor)
+    }
+}
+
+/// Runs an action from the keymap, except for evaluation, which
+/// has to leave the event loop.
+fn run_action(
+    ui: &Ui,
+    action: Action,
+    cursor_range: &mut CursorRange,
+    text: &mut dyn TextBuffer,
+    galley: &Galley,
+    state: &mut LivecodeTextEditState,
+) -> Option<CCursorRange> {
+    // moving around works like the arrow keys
+    let mut move_cursor = |key: Key, modifiers: Modifiers, state: &mut LivecodeTextEditState| {
+        on_key_press(cursor_range, text, galley, key, &modifiers, state)
+    };
+
+    match action {
+        Action::Evaluate => None,
+        Action::ForwardChar => move_cursor(Key::ArrowRight, Modifiers::NONE, state),
+        Action::BackwardChar => move_cursor(Key::ArrowLeft, Modifiers::NONE, state),
+        Action::NextLine => move_cursor(Key::ArrowDown, Modifiers::NONE, state),
+        Action::PreviousLine => move_cursor(Key::ArrowUp, Modifiers::NONE, state),
+        Action::ForwardWord => move_cursor(Key::ArrowRight, Modifiers::ALT, state),
+        Action::BackwardWord => move_cursor(Key::ArrowLeft, Modifiers::ALT, state),
+        Action::LineStart => move_cursor(Key::Home, Modifiers::NONE, state),
+        Action::LineEnd => move_cursor(Key::End, Modifiers::NONE, state),
+        Action::InsertMode => {
+            state.normal_mode = false;
+            None
+        }
+        Action::Append => {
+            state.normal_mode = false;
+            move_cursor(Key::ArrowRight, Modifiers::NONE, state)
+        }
+        Action::InsertAtLineStart => {
+            state.normal_mode = false;
+            move_cursor(Key::Home, Modifiers::NONE, state)
+        }
+        Action::AppendAtLineEnd => {
+            state.normal_mode = false;
+            move_cursor(Key::End, Modifiers::NONE, state)
+        }
+        Action::OpenLineBelow => {
+            state.normal_mode = false;
+            move_cursor(Key::End, Modifiers::NONE, state);
+            Some(insert_newline(text, galley, cursor_range))
+        }
+        Action::NormalMode => {
+            state.normal_mode = true;
+            state.completion = None;
+            None
+        }
+        Action::ForwardSexp | Action::BackwardSexp | Action::SexpStart => {
+            let ccursor = cursor_range.primary.ccursor;
+            let target = match action {
+                Action::ForwardSexp => forward_sexp(text.as_str(), ccursor),
+                Action::BackwardSexp => backward_sexp(text.as_str(), ccursor),
+                _ => find_toplevel_sexp(text.as_str(), cursor_range)
+                    .map(|sexp| sexp.secondary)
+                    .unwrap_or(ccursor),
+            };
+            cursor_range.primary = galley.from_ccursor(target);
+            if !state.selection_toggle {
+                cursor_range.secondary = cursor_range.primary;
+            }
+            None
+        }
+        Action::FormatSexp => {
+            if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
+                let old_cursor = cursor_range.as_ccursor_range();
+                let cup = CursorRange {
+                    primary: galley.from_ccursor(sexp_cursors.primary),
+                    secondary: galley.from_ccursor(sexp_cursors.secondary),
+                };
+
+                let formatted = { format_sexp(selected_str(text, &cup)) };
+
+                let mut ccursor = delete_selected(text, &cup);
+                insert_text(&mut ccursor, text, &formatted);
+                Some(CCursorRange::one(old_cursor.primary))
+            } else {
+                None
+            }
+        }
+        Action::ToggleComment => {
+            state.clear_paren_selection();
+            let old_cursor = cursor_range.as_ccursor_range().primary;
+            let on = toggle_sexp(text, galley, &cursor_range.primary.ccursor);
+            if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
+                let cup = CursorRange {
+                    primary: galley.from_ccursor(sexp_cursors.primary),
+                    secondary: galley.from_ccursor(sexp_cursors.secondary),
+                };
+
+                let formatted = { format_sexp(selected_str(text, &cup)) };
+
+                let mut ccursor = delete_selected(text, &cup);
+                insert_text(&mut ccursor, text, &formatted);
+            }
+            if on {
+                Some(CCursorRange::one(CCursor {
+                    index: old_cursor.index + 2,
+                    prefer_next_row: false,
+                }))
+            } else {
+                Some(CCursorRange::one(CCursor {
+                    index: old_cursor.index - 2,
+                    prefer_next_row: false,
+                }))
+            }
+        }
+        Action::SelectSexp => {
+            if let Some(sexp_cursors) =
+                find_current_sexp(text.as_str(), &cursor_range.as_ccursor_range().primary)
+            {
+                state.selection_toggle = true;
+                Some(CCursorRange::two(sexp_cursors.0, sexp_cursors.1))
+            } else {
+                None
+            }
+        }
+        Action::ToggleSelection => {
+            state.selection_toggle = !state.selection_toggle;
+            None
+        }
+        Action::ClearSelection => {
+            state.selection_toggle = false;
+            cursor_range.secondary = cursor_range.primary;
+            None
+        }
+        Action::SelectAll => {
+            *cursor_range = CursorRange::two(Cursor::default(), galley.end());
+            None
+        }
+        Action::CutSelection => {
+            // clear selection
+            state.selection_toggle = false;
+            state.clear_paren_selection();
+            let selected = selected_str(text, cursor_range).to_owned();
+            ui.ctx().output_mut(|o| o.copied_text = selected);
+            Some(CCursorRange::one(delete_selected(text, cursor_range)))
+        }
+        Action::KillLine => {
+            state.clear_paren_selection();
+            let ccursor = delete_paragraph_after_cursor(text, galley, cursor_range);
+            Some(CCursorRange::one(ccursor))
+        }
+        Action::KillLineBackward => {
+            state.clear_paren_selection();
+            let ccursor = delete_paragraph_before_cursor(text, galley, cursor_range);
+            Some(CCursorRange::one(ccursor))
+        }
+        Action::DeleteChar => {
+            state.clear_paren_selection();
+            let ccursor = if let Some(cursor) = cursor_range.single() {
+                delete_next_char(text, cursor.ccursor)
+            } else {
+                delete_selected(text, cursor_range)
+            };
+            Some(CCursorRange::one(ccursor))
+        }
+        Action::Undo => state
+            .undoer
+            .lock()
+            .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()))
+            .map(|(undo_ccursor_range, undo_txt)| {
+                text.replace(&undo_txt);
+                undo_ccursor_range
+            }),
+        Action::Redo => state
+            .undoer
+            .lock()
+            .redo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()))
+            .map(|(redo_ccursor_range, redo_txt)| {
+                text.replace(&redo_txt);
+                redo_ccursor_range
+            }),
+    }
+}
+
+/// Moves through the completion candidates, or inserts the selected one.
+fn completion_key(
+    state: &mut LivecodeTextEditState,
+    vocabulary: &Vocabulary,
+    text: &mut dyn TextBuffer,
+    cursor_range: &CursorRange,
+    key: Key,
+) -> Option<CCursorRange> {
+    let cursor = cursor_range.primary.ccursor;
+    let (start, candidates) =
+        vocabulary.completions(text.as_str(), text.byte_index_from_char_index(cursor.index));
+    let selected = state
+        .completion
+        .unwrap_or(0)
+        .min(candidates.len().saturating_sub(1));
+
+    match key {
+        Key::ArrowDown if !candidates.is_empty() => {
+            state.completion = Some((selected + 1) % candidates.len());
+            None
+        }
+        Key::ArrowUp if !candidates.is_empty() => {
+            state.completion = Some((selected + candidates.len() - 1) % candidates.len());
+            None
+        }
+        Key::Tab | Key::Enter if !candidates.is_empty() => {
+            state.completion = None;
+            let start = CCursor::new(text.as_str()[..start].chars().count());
+            text.delete_char_range(start.index..cursor.index);
+            let mut ccursor = start;
+            insert_text(&mut ccursor, text, &candidates[selected]);
+            Some(CCursorRange::one(ccursor))
+        }
+        _ => {
+            state.completion = None;
+            None
+        }
+    }
+}
+
 // ----------------------------------------------------------------------------
 
 fn paint_cursor_selection(
@@ -977,6 +1320,40 @@ fn paint_cursor_selection(
     }
 }
 
+/// Draw a line below the text in the cursor range.
+fn paint_underline(
+    painter: &Painter,
+    pos: Pos2,
+    galley: &Galley,
+    cursor_range: &CursorRange,
+    color: Color32,
+) {
+    let [min, max] = cursor_range.sorted_cursors();
+    let min = min.rcursor;
+    let max = max.rcursor;
+
+    for ri in min.row..=max.row.min(galley.rows.len().saturating_sub(1)) {
+        let row = &galley.rows[ri];
+        let left = if ri == min.row {
+            row.x_offset(min.column)
+        } else {
+            row.rect.left()
+        };
+        let right = if ri == max.row {
+            row.x_offset(max.column)
+        } else {
+            row.rect.right()
+        };
+        painter.line_segment(
+            [
+                pos + vec2(left, row.max_y() - 1.0),
+                pos + vec2(right, row.max_y() - 1.0),
+            ],
+            (2.0, color),
+        );
+    }
+}
+
 fn paint_cursor_end(
     ui: &Ui,
     row_height: f32,
@@ -1181,50 +1558,6 @@ fn on_key_press(
             Some(CCursorRange::one(ccursor))
         }
 
-        Key::A if modifiers.command => {
-            // select all
-            *cursor_range = CursorRange::two(Cursor::default(), galley.end());
-            None
-        }
-
-        Key::T if modifiers.command => {
-            // select all
-            let old_cursor = cursor_range.as_ccursor_range().primary;
-            let on = toggle_sexp(text, galley, &cursor_range.primary.ccursor);
-            if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
-                let cup = CursorRange {
-                    primary: galley.from_ccursor(sexp_cursors.primary),
-                    secondary: galley.from_ccursor(sexp_cursors.secondary),
-                };
-
-                let formatted = { format_sexp(selected_str(text, &cup)) };
-
-                let mut ccursor = delete_selected(text, &cup);
-                insert_text(&mut ccursor, text, &formatted);
-            }
-            if on {
-                Some(CCursorRange::one(CCursor {
-                    index: old_cursor.index + 2,
-                    prefer_next_row: false,
-                }))
-            } else {
-                Some(CCursorRange::one(CCursor {
-                    index: old_cursor.index - 2,
-                    prefer_next_row: false,
-                }))
-            }
-        }
-
-        Key::K if modifiers.ctrl => {
-            let ccursor = delete_paragraph_after_cursor(text, galley, cursor_range);
-            Some(CCursorRange::one(ccursor))
-        }
-
-        Key::U if modifiers.ctrl => {
-            let ccursor = delete_paragraph_before_cursor(text, galley, cursor_range);
-            Some(CCursorRange::one(ccursor))
-        }
-
         Key::ArrowLeft | Key::ArrowRight | Key::ArrowUp | Key::ArrowDown | Key::Home | Key::End => {
             if text.as_str().is_empty() {
                 return None;
@@ -1746,6 +2079,64 @@ fn find_end_of_row(text: &str, ccursor: &CCursor) -> Option<(CCursor, bool)> {
     None
 }
 
+/// where the expression after the cursor ends
+fn forward_sexp(text: &str, ccursor: CCursor) -> CCursor {
+    let chars: Vec<char> = text.chars().collect();
+    let mut idx = ccursor.index;
+    while idx < chars.len() && chars[idx].is_whitespace() {
+        idx += 1;
+    }
+
+    let end = match chars.get(idx) {
+        None => chars.len(),
+        Some('(') => find_closing_paren(text, &CCursor::new(idx + 1))
+            .map(|c| c.index)
+            .unwrap_or(chars.len()),
+        // leave the current expression
+        Some(')') => idx + 1,
+        Some(_) => {
+            while idx < chars.len()
+                && !chars[idx].is_whitespace()
+                && chars[idx] != '('
+                && chars[idx] != ')'
+            {
+                idx += 1;
+            }
+            idx
+        }
+    };
+    CCursor::new(end)
+}
+
+/// where the expression before the cursor starts
+fn backward_sexp(text: &str, ccursor: CCursor) -> CCursor {
+    let chars: Vec<char> = text.chars().collect();
+    let mut idx = ccursor.index.min(chars.len());
+    while idx > 0 && chars[idx - 1].is_whitespace() {
+        idx -= 1;
+    }
+
+    let start = match idx.checked_sub(1).map(|i| chars[i]) {
+        None => 0,
+        Some(')') => find_opening_paren(text, &CCursor::new(idx - 1))
+            .map(|c| c.index)
+            .unwrap_or(0),
+        // leave the current expression
+        Some('(') => idx - 1,
+        Some(_) => {
+            while idx > 0
+                && !chars[idx - 1].is_whitespace()
+                && chars[idx - 1] != '('
+                && chars[idx - 1] != ')'
+            {
+                idx -= 1;
+            }
+            idx
+        }
+    };
+    CCursor::new(start)
+}
+
 fn find_current_sexp(text: &str, ccursor: &CCursor) -> Option<(CCursor, CCursor)> {
     if let Some(cursor_open) = find_opening_paren(text, ccursor) {
         if let Some(cursor_close) = find_closing_paren(text, ccursor) {
@@ -1875,3 +2266,80 @@ fn toggle_sexp(text: &mut dyn TextBuffer, galley: &Galley, ccursor: &CCursor) ->
     }
     on
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sexp_motion() {
+        let text = "(sx 'ga #t\n  (nuc 'a (saw 100)) (nuc 'b (sqr 200)))";
+        let nuc = text.find("(nuc").unwrap();
+        let after_nuc = text.find(" (nuc 'b").unwrap();
+
+        assert_eq!(forward_sexp(text, CCursor::new(nuc)).index, after_nuc);
+        // whitespace is skipped
+        assert_eq!(forward_sexp(text, CCursor::new(nuc - 2)).index, after_nuc);
+        assert_eq!(backward_sexp(text, CCursor::new(after_nuc)).index, nuc);
+        // atoms
+        assert_eq!(forward_sexp(text, CCursor::new(1)).index, 3);
+        assert_eq!(backward_sexp(text, CCursor::new(3)).index, 1);
+        // the whole thing
+        let len = text.chars().count();
+        assert_eq!(forward_sexp(text, CCursor::new(0)).index, len);
+        assert_eq!(backward_sexp(text, CCursor::new(len)).index, 0);
+    }
+
+    fn run_frame(ctx: &Context, text: &mut String, keymap: &Keymap, events: Vec<Event>) -> Id {
+        let input = RawInput {
+            events,
+            ..Default::default()
+        };
+        let mut id = Id::null();
+        let _ = ctx.run(input, |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                id = LivecodeTextEdit::multiline(text)
+                    .id_source("test")
+                    .keymap(keymap)
+                    .show(ui)
+                    .response
+                    .id;
+            });
+        });
+        id
+    }
+
+    #[test]
+    fn test_insert_mode_key() {
+        let ctx = Context::default();
+        let keymap = Keymap::vim();
+        let mut text = String::from("(sx 'ga)");
+
+        let id = run_frame(&ctx, &mut text, &keymap, vec![]);
+        ctx.memory_mut(|m| m.request_focus(id));
+        let mut state = LivecodeTextEditState::load(&ctx, id).unwrap_or_default();
+        state.normal_mode = true;
+        state.store(&ctx, id);
+
+        // egui sends the key first, then the text it typed
+        let key_i = Event::Key {
+            key: Key::I,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::NONE,
+        };
+        run_frame(
+            &ctx,
+            &mut text,
+            &keymap,
+            vec![key_i, Event::Text("i".into())],
+        );
+        assert_eq!(text, "(sx 'ga)");
+        assert!(!LivecodeTextEditState::load(&ctx, id).unwrap().normal_mode);
+
+        // now in insert mode, typing inserts
+        run_frame(&ctx, &mut text, &keymap, vec![Event::Text("i".into())]);
+        assert_eq!(text, "(sx 'ga)i");
+    }
+}
@@ -1,58 +1,170 @@
 use chrono::*;
 use egui::ScrollArea;
 use parking_lot::Mutex;
+use std::collections::BTreeSet;
 use std::{fs, path, sync::*};
 
+use crate::editor::completion::Vocabulary;
+use crate::editor::graph_view::GraphView;
+use crate::editor::history::{self, Snapshot, Timeline};
+use crate::editor::keymap::Keymap;
+use crate::editor::meters::{self, AudioMeters};
 use egui::style::Margin;
 use egui::FontId;
 use epaint::text::{FontData, FontDefinitions, FontFamily};
 // custom text edit window
-use crate::editor::livecode_text_edit::LivecodeTextEdit;
+use crate::editor::livecode_text_edit::{EvalCallback, LivecodeTextEdit};
 use crate::editor::syntax_highlighting::*;
+use crate::session::GeneratorInfo;
+use crate::visualizer_client::GeneratorGraph;
 
-#[derive(PartialEq)]
-enum SketchNumber {
-    Num(usize),
+// so the tabs can be told apart, even if they have no path
+static NEXT_TAB_ID: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+
+/// A sketch that's open in the editor.
+struct Tab {
+    content: String,
+    // empty if the sketch isn't stored anywhere
+    path: String,
+    id: usize,
 }
 
+impl Tab {
+    fn new(content: String, path: String) -> Self {
+        Tab {
+            content,
+            path,
+            id: NEXT_TAB_ID.fetch_add(1, atomic::Ordering::Relaxed),
+        }
+    }
+
+    fn open(path: &str) -> Self {
+        let content = match fs::read_to_string(path::Path::new(path)) {
+            Ok(mut s) => {
+                if !s.ends_with('\n') {
+                    s.push('\n');
+                }
+                s
+            }
+            Err(e) => {
+                println!("couldn't read sketch {e}");
+                String::new()
+            }
+        };
+        Tab::new(content, path.to_string())
+    }
+
+    fn name(&self) -> String {
+        path::Path::new(&self.path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "untitled".to_string())
+    }
+
+    fn save(&self) {
+        if !self.path.is_empty() {
+            if let Err(e) = fs::write(path::Path::new(&self.path), self.content.as_bytes()) {
+                println!("couldn't save sketch {e}");
+            }
+        }
+    }
+}
+
+/// What happened on the last evaluation, shown in the status panel.
+enum EvalStatus {
+    Evaluated(String), // the time of evaluation
+    Failed(String),    // the error report
+}
+
+/// What can be done to a generator from the generator panel.
+pub enum GeneratorAction {
+    Stop,
+    Mute(bool),
+    Solo(bool),
+}
+
+pub type GeneratorInfoCallback = dyn Fn() -> Vec<GeneratorInfo>;
+pub type GeneratorActionCallback = dyn Fn(&BTreeSet<String>, GeneratorAction);
+pub type GeneratorGraphCallback = dyn Fn() -> Vec<GeneratorGraph>;
+// fills the meters with the output that has been played since the last call
+pub type MonitorCallback = dyn FnMut(&mut AudioMeters);
+// what can be completed, as of now
+pub type VocabularyCallback = dyn Fn() -> Vocabulary;
+
 pub enum EditorFont {
     ComicMono,
     Mononoki,
     Custom(String),
 }
 
-/// We derive Deserialize/Serialize so we can persist app state on shutdown.
-#[derive(serde::Deserialize, serde::Serialize)]
+/// The sketches are saved to their files, there's no other state
+/// that's kept between sessions.
 pub struct MegraEditor {
-    content: String,
-    #[serde(skip)]
-    callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
-    #[serde(skip)]
+    tabs: Vec<Tab>,
+    callback: Option<Arc<Mutex<EvalCallback>>>,
     sketch_list: Vec<String>,
-    #[serde(skip)]
-    current_sketch: String,
-    #[serde(skip)]
-    sketch_number: usize,
-    #[serde(skip)]
+    // the tab shown in each pane (the second one only if split)
+    panes: [usize; 2],
+    focused_pane: usize,
+    split: bool,
     font: Option<EditorFont>,
-    #[serde(skip)]
     font_size: f32,
-    #[serde(skip)]
     karl_yerkes_mode: bool,
+    status: Option<EvalStatus>,
+    generator_info: Option<Box<GeneratorInfoCallback>>,
+    generator_action: Option<Box<GeneratorActionCallback>>,
+    show_generators: bool,
+    generator_graphs: Option<Box<GeneratorGraphCallback>>,
+    graph_view: GraphView,
+    show_graphs: bool,
+    meters: Option<(AudioMeters, Box<MonitorCallback>)>,
+    show_scope: bool,
+    timeline: Timeline,
+    show_timeline: bool,
+    vocabulary: Vocabulary,
+    vocabulary_callback: Option<Box<VocabularyCallback>>,
+    signature: Option<String>, // of the function at the cursor
+    keymap: Keymap,
+    themes: Vec<(String, Palette)>, // the built-in ones and the custom one, if any
+    theme: usize,
 }
 
 impl Default for MegraEditor {
     fn default() -> Self {
         Self {
-            content: "(sx 'ga #t (infer 'troll :events 'a (saw 400) :rules (rule 'a 'a 100 400)))"
-                .to_owned(),
+            tabs: vec![Tab::new(
+                "(sx 'ga #t (infer 'troll :events 'a (saw 400) :rules (rule 'a 'a 100 400)))"
+                    .to_owned(),
+                "".to_string(),
+            )],
             callback: None,
             sketch_list: Vec::new(),
-            current_sketch: "".to_string(),
-            sketch_number: 0,
+            panes: [0, 0],
+            focused_pane: 0,
+            split: false,
             font: None,
             font_size: 15.0,
             karl_yerkes_mode: false,
+            status: None,
+            generator_info: None,
+            generator_action: None,
+            show_generators: false,
+            generator_graphs: None,
+            graph_view: GraphView::default(),
+            show_graphs: false,
+            meters: None,
+            show_scope: false,
+            timeline: Timeline::default(),
+            show_timeline: false,
+            vocabulary: Vocabulary::default(),
+            vocabulary_callback: None,
+            signature: None,
+            keymap: Keymap::default(),
+            themes: THEMES
+                .iter()
+                .map(|name| (name.to_string(), Palette::preset(name).unwrap()))
+                .collect(),
+            theme: 0,
         }
     }
 }
@@ -66,10 +178,382 @@ impl MegraEditor {
         self.font_size = *font_size;
     }
 
-    pub fn set_callback(&mut self, callback: Arc<Mutex<dyn FnMut(&String)>>) {
+    pub fn set_callback(&mut self, callback: Arc<Mutex<EvalCallback>>) {
         self.callback = Some(callback);
     }
 
+    /// to show the running generators and stop, mute or solo them
+    pub fn set_generator_callbacks(
+        &mut self,
+        info: Box<GeneratorInfoCallback>,
+        action: Box<GeneratorActionCallback>,
+    ) {
+        self.generator_info = Some(info);
+        self.generator_action = Some(action);
+    }
+
+    /// to show the markov graphs of the running generators
+    pub fn set_graph_callback(&mut self, graphs: Box<GeneratorGraphCallback>) {
+        self.generator_graphs = Some(graphs);
+    }
+
+    /// to complete function names and keywords while typing
+    pub fn set_vocabulary_callback(&mut self, vocabulary: Box<VocabularyCallback>) {
+        self.vocabulary = vocabulary();
+        self.vocabulary_callback = Some(vocabulary);
+    }
+
+    /// to show the output levels, and the waveform and spectrum if needed
+    pub fn set_monitor(&mut self, meters: AudioMeters, monitor: Box<MonitorCallback>) {
+        self.meters = Some((meters, monitor));
+    }
+
+    /// the buttons, windows etc. follow the theme
+    fn set_visuals(&self, ctx: &egui::Context) {
+        if self.themes[self.theme].1.is_light() {
+            ctx.set_visuals(egui::Visuals::light());
+        } else {
+            ctx.set_visuals(egui::Visuals::dark());
+        }
+    }
+
+    fn generator_panel(&self, ui: &mut egui::Ui) {
+        let (Some(info), Some(action)) = (&self.generator_info, &self.generator_action) else {
+            return;
+        };
+
+        let font = FontId::monospace(self.font_size * 0.8);
+        let palette = &self.themes[self.theme].1;
+        let mut actions = Vec::new();
+
+        ScrollArea::vertical().show(ui, |ui| {
+            let gens = info();
+            if gens.is_empty() {
+                ui.label(egui::RichText::new("nothing running").font(font.clone()));
+            }
+
+            for gen in gens {
+                let name: Vec<&str> = gen.id_tags.iter().map(|t| t.as_str()).collect();
+                let color = if gen.muted {
+                    palette.comment
+                } else if gen.soloed {
+                    palette.string
+                } else {
+                    palette.normal
+                };
+                ui.label(
+                    egui::RichText::new(name.join(" "))
+                        .font(font.clone())
+                        .color(color),
+                );
+
+                let mut details = Vec::new();
+                if let Some(ctx) = &gen.context {
+                    details.push(format!("context: {ctx}"));
+                }
+                if let Some(sym) = &gen.current_symbol {
+                    details.push(format!("symbol: {sym}"));
+                }
+                if !gen.block_tags.is_empty() {
+                    let tags: Vec<&str> = gen.block_tags.iter().map(|t| t.as_str()).collect();
+                    details.push(format!("block: {}", tags.join(" ")));
+                }
+                if !gen.solo_tags.is_empty() {
+                    let tags: Vec<&str> = gen.solo_tags.iter().map(|t| t.as_str()).collect();
+                    details.push(format!("solo: {}", tags.join(" ")));
+                }
+                for d in details {
+                    ui.label(egui::RichText::new(d).font(font.clone()));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("stop").clicked() {
+                        actions.push((gen.id_tags.clone(), GeneratorAction::Stop));
+                    }
+                    if ui.selectable_label(gen.muted, "mute").clicked() {
+                        actions.push((gen.id_tags.clone(), GeneratorAction::Mute(!gen.muted)));
+                    }
+                    if ui.selectable_label(gen.soloed, "solo").clicked() {
+                        actions.push((gen.id_tags.clone(), GeneratorAction::Solo(!gen.soloed)));
+                    }
+                });
+                ui.separator();
+            }
+        });
+
+        for (id_tags, a) in actions {
+            action(&id_tags, a);
+        }
+    }
+
+    /// Shows the sketch in the focused pane, opens it if it isn't open yet.
+    fn open_sketch(&mut self, path: &str) {
+        let idx = match self.tabs.iter().position(|t| t.path == path) {
+            Some(idx) => idx,
+            None => {
+                self.tabs.push(Tab::open(path));
+                self.tabs.len() - 1
+            }
+        };
+        self.panes[self.focused_pane] = idx;
+    }
+
+    fn close_tab(&mut self, idx: usize) {
+        // there's always one tab left
+        if self.tabs.len() < 2 {
+            return;
+        }
+        self.tabs.remove(idx).save();
+        for pane in self.panes.iter_mut() {
+            if *pane > idx || *pane == self.tabs.len() {
+                *pane -= 1;
+            }
+        }
+    }
+
+    fn tab_row(&mut self, ui: &mut egui::Ui) {
+        let mut show = None;
+        let mut close = None;
+
+        ui.horizontal_wrapped(|ui| {
+            for (i, tab) in self.tabs.iter().enumerate() {
+                let shown = self.panes[self.focused_pane] == i;
+                if ui
+                    .selectable_label(
+                        shown,
+                        egui::RichText::new(tab.name()).font(FontId::monospace(self.font_size)),
+                    )
+                    .clicked()
+                {
+                    show = Some(i);
+                }
+                if self.tabs.len() > 1 && ui.small_button("x").clicked() {
+                    close = Some(i);
+                }
+            }
+        });
+
+        if let Some(i) = show {
+            self.panes[self.focused_pane] = i;
+        }
+        if let Some(i) = close {
+            self.close_tab(i);
+        }
+    }
+
+    fn pane(&mut self, ui: &mut egui::Ui, pane: usize) {
+        let tab = &mut self.tabs[self.panes[pane]];
+        let palette = &self.themes[self.theme].1;
+
+        if self.split {
+            let color = if pane == self.focused_pane {
+                ui.visuals().strong_text_color()
+            } else {
+                ui.visuals().weak_text_color()
+            };
+            ui.label(
+                egui::RichText::new(tab.name())
+                    .font(FontId::monospace(self.font_size))
+                    .color(color),
+            );
+        }
+
+        // each sketch has its own cursor in each pane, but only one
+        // undo history, as both panes edit the same text
+        let editor_id = (pane, tab.id);
+        let history_id = ("history", tab.id);
+        let mut focused = false;
+        let mut cursor = None;
+        let mut eval_result = None;
+
+        ScrollArea::vertical()
+            .id_source(("pane", pane))
+            .show(ui, |ui| {
+                let num_lines = tab.content.lines().count() + 1;
+
+                let theme = CodeTheme::new(palette, self.font_size);
+                let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
+                    let layout_job = highlight(ui.ctx(), &theme, string);
+                    ui.fonts(|f| f.layout_job(layout_job))
+                };
+
+                let tx = if let Some(cb) = self.callback.as_ref() {
+                    LivecodeTextEdit::multiline(&mut tab.content)
+                        .id_source(editor_id)
+                        .history_id_source(history_id)
+                        .desired_rows(30)
+                        .code_editor()
+                        .desired_width(800.0)
+                        .eval_callback(cb)
+                        .karl_yerkes_mode(self.karl_yerkes_mode)
+                        .vocabulary(&self.vocabulary)
+                        .keymap(&self.keymap)
+                        .paren_color(palette.paren_match)
+                        .error_color(palette.error)
+                        .cursor_color(palette.cursor)
+                        .layouter(&mut layouter)
+                } else {
+                    LivecodeTextEdit::multiline(&mut tab.content)
+                        .id_source(editor_id)
+                        .history_id_source(history_id)
+                        .desired_rows(30)
+                        .code_editor()
+                        .desired_width(800.0)
+                        .vocabulary(&self.vocabulary)
+                        .keymap(&self.keymap)
+                        .paren_color(palette.paren_match)
+                        .error_color(palette.error)
+                        .cursor_color(palette.cursor)
+                        .layouter(&mut layouter)
+                };
+
+                let mut linenums = "".to_owned();
+                for i in 1..num_lines {
+                    linenums.push_str(format!("{i}\n").as_str());
+                }
+
+                let ln = egui::Label::new(
+                    egui::RichText::new(linenums)
+                        .font(FontId::monospace(self.font_size))
+                        .color(palette.comment),
+                );
+
+                ui.horizontal(|ui| {
+                    ui.add(ln);
+                    let output = tx.show(ui);
+                    focused = output.response.has_focus();
+                    cursor = output.cursor_range;
+                    eval_result = output.eval_result;
+                });
+            });
+
+        if focused {
+            self.focused_pane = pane;
+
+            let signature = cursor.and_then(|cursor| {
+                let offset = tab
+                    .content
+                    .char_indices()
+                    .nth(cursor.primary.ccursor.index)
+                    .map(|(i, _)| i)
+                    .unwrap_or(tab.content.len());
+                self.vocabulary.signature(&tab.content, offset)
+            });
+            if signature != self.signature {
+                self.signature = signature;
+                ui.ctx().request_repaint();
+            }
+        }
+
+        if let Some((code, result)) = eval_result {
+            let time = Local::now().format("%H:%M:%S").to_string();
+            self.status = Some(match &result {
+                Ok(_) => EvalStatus::Evaluated(time.clone()),
+                Err(e) => EvalStatus::Failed(e.report(&tab.content)),
+            });
+            self.timeline.push(Snapshot {
+                time,
+                code,
+                sketch: tab.path.clone(),
+                failed: result.is_err(),
+            });
+            // there might be new functions or samples
+            if let Some(vocabulary) = &self.vocabulary_callback {
+                self.vocabulary = vocabulary();
+            }
+            // the status panel has been drawn already
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Evaluates an earlier version of a block again. If `restore` is set,
+    /// it replaces the current version in the sketch it came from.
+    fn replay(&mut self, idx: usize, restore: bool) {
+        let Some(snapshot) = self.timeline.get(idx) else {
+            return;
+        };
+        let code = snapshot.code.clone();
+        let sketch = snapshot.sketch.clone();
+
+        if restore {
+            let tab_idx = self
+                .tabs
+                .iter()
+                .position(|t| t.path == sketch)
+                .unwrap_or(self.panes[self.focused_pane]);
+            let tab = &mut self.tabs[tab_idx];
+            tab.content = history::restore_block(&tab.content, &code);
+
+            let visible = if self.split { 2 } else { 1 };
+            if !self.panes[..visible].contains(&tab_idx) {
+                self.panes[self.focused_pane] = tab_idx;
+            }
+        }
+
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        let result = (callback.lock())(&code);
+        let time = Local::now().format("%H:%M:%S").to_string();
+        self.status = Some(match &result {
+            Ok(_) => EvalStatus::Evaluated(time.clone()),
+            Err(e) => EvalStatus::Failed(e.report(&code)),
+        });
+        self.timeline.push(Snapshot {
+            time,
+            code,
+            sketch,
+            failed: result.is_err(),
+        });
+        if let Some(vocabulary) = &self.vocabulary_callback {
+            self.vocabulary = vocabulary();
+        }
+    }
+
+    /// Lists the evaluations, newest first. Returns the one that should be
+    /// replayed, if any, and whether it should be restored.
+    fn timeline_panel(&self, ui: &mut egui::Ui) -> Option<(usize, bool)> {
+        let font = FontId::monospace(self.font_size * 0.8);
+        let mut replay = None;
+
+        ScrollArea::vertical().show(ui, |ui| {
+            if self.timeline.is_empty() {
+                ui.label(egui::RichText::new("nothing evaluated yet").font(font.clone()));
+            }
+
+            for (i, snapshot) in self.timeline.snapshots().rev() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(&snapshot.time).font(font.clone()));
+                    if ui
+                        .small_button("restore")
+                        .on_hover_text("put this version back into the sketch and evaluate it")
+                        .clicked()
+                    {
+                        replay = Some((i, true));
+                    }
+                    if ui.small_button("eval").clicked() {
+                        replay = Some((i, false));
+                    }
+                });
+
+                let color = if snapshot.failed {
+                    ui.visuals().error_fg_color
+                } else {
+                    ui.visuals().strong_text_color()
+                };
+                ui.label(
+                    egui::RichText::new(snapshot.code.lines().next().unwrap_or(""))
+                        .font(font.clone())
+                        .color(color),
+                )
+                .on_hover_text(egui::RichText::new(&snapshot.code).font(font.clone()));
+                ui.separator();
+            }
+        });
+
+        replay
+    }
+
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         base_dir: String,
@@ -142,20 +626,29 @@ impl MegraEditor {
 
         let base_dir_buf = std::path::PathBuf::from(base_dir);
 
+        ed.keymap = Keymap::load(&base_dir_buf);
+        // a custom theme is the one to use, if there is one
+        if let Some(custom) = Palette::load(&base_dir_buf) {
+            ed.themes.push(custom);
+            ed.theme = ed.themes.len() - 1;
+        }
+        ed.set_visuals(&cc.egui_ctx);
+
         let sketchbook_path = base_dir_buf.join("sketchbook");
         if sketchbook_path.exists() {
             if *create_sketch {
                 // prepare sketch marked with date
                 let id = format!("sketch_{}.megra3", Local::now().format("%Y%m%d_%H%M_%S"));
                 let file_path = sketchbook_path.join(id);
-                ed.current_sketch = file_path.to_str().unwrap().to_string();
-                // push current sketch so it'll be the one visible
-                ed.sketch_list.push(ed.current_sketch.clone());
-
-                ed.content = format!(
-                    ";; Created {}",
-                    Local::now().format("%A, %F, %H:%M:%S ... good luck!")
+                ed.tabs[0] = Tab::new(
+                    format!(
+                        ";; Created {}",
+                        Local::now().format("%A, %F, %H:%M:%S ... good luck!")
+                    ),
+                    file_path.to_str().unwrap().to_string(),
                 );
+                // push current sketch so it'll be the one visible
+                ed.sketch_list.push(ed.tabs[0].path.clone());
             }
 
             if let Ok(entries) = fs::read_dir(sketchbook_path) {
@@ -178,19 +671,7 @@ impl MegraEditor {
             }
 
             if !*create_sketch && !ed.sketch_list.is_empty() {
-                ed.current_sketch = ed.sketch_list[0].clone();
-                let p = path::Path::new(&ed.current_sketch);
-                match fs::read_to_string(p) {
-                    Ok(mut s) => {
-                        if !s.ends_with('\n') {
-                            s.push('\n');
-                        }
-                        ed.content = s
-                    }
-                    Err(e) => {
-                        println!("couldn't read sketch {e}");
-                    }
-                }
+                ed.tabs[0] = Tab::open(&ed.sketch_list[0]);
             }
         }
 
@@ -203,11 +684,92 @@ impl eframe::App for MegraEditor {
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
         // some frame options ...
+        let palette = &self.themes[self.theme].1;
         let mut frame = egui::Frame::none();
-        frame.fill = egui::Color32::BLACK;
+        frame.fill = palette.background;
         frame.inner_margin = Margin::symmetric(3.0, 3.0);
+
+        if self.status.is_some() || self.signature.is_some() {
+            egui::TopBottomPanel::bottom("status_panel")
+                .frame(frame)
+                .show(ctx, |ui| {
+                    if let Some(signature) = &self.signature {
+                        ui.add(
+                            egui::Label::new(
+                                egui::RichText::new(signature)
+                                    .font(FontId::monospace(self.font_size))
+                                    .color(palette.symbol),
+                            )
+                            .wrap(false),
+                        );
+                    }
+                    if let Some(status) = &self.status {
+                        let (text, color) = match status {
+                            EvalStatus::Evaluated(time) => {
+                                (format!("evaluated at {time}"), palette.command)
+                            }
+                            EvalStatus::Failed(report) => (report.clone(), palette.error),
+                        };
+                        ui.add(
+                            egui::Label::new(
+                                egui::RichText::new(text)
+                                    .font(FontId::monospace(self.font_size))
+                                    .color(color),
+                            )
+                            .wrap(false),
+                        );
+                    }
+                });
+        }
+
+        if self.show_generators {
+            egui::SidePanel::right("generator_panel")
+                .frame(frame)
+                .show(ctx, |ui| self.generator_panel(ui));
+            // keep the current symbols up to date
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        if let Some((meters, monitor)) = &mut self.meters {
+            monitor(meters);
+            egui::Window::new("scope")
+                .open(&mut self.show_scope)
+                .default_size([400.0, 300.0])
+                .show(ctx, |ui| meters::show_scope(ui, meters, palette));
+            // the meters move all the time
+            ctx.request_repaint_after(std::time::Duration::from_millis(33));
+        }
+
+        if let Some(graphs) = &self.generator_graphs {
+            let graph_view = &mut self.graph_view;
+            let font_size = self.font_size;
+            egui::Window::new("graphs")
+                .open(&mut self.show_graphs)
+                .default_size([400.0, 600.0])
+                .show(ctx, |ui| {
+                    ScrollArea::vertical().show(ui, |ui| {
+                        graph_view.show(ui, &graphs(), palette, font_size);
+                    });
+                });
+            if self.show_graphs {
+                // follow the active nodes and let the layout settle
+                ctx.request_repaint_after(std::time::Duration::from_millis(50));
+            }
+        }
+
+        let mut show_timeline = self.show_timeline;
+        let mut replay = None;
+        egui::Window::new("timeline")
+            .open(&mut show_timeline)
+            .default_size([400.0, 500.0])
+            .show(ctx, |ui| replay = self.timeline_panel(ui));
+        self.show_timeline = show_timeline;
+        if let Some((idx, restore)) = replay {
+            self.replay(idx, restore);
+        }
+
         egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
-            let mut sketch_number = SketchNumber::Num(self.sketch_number);
+            let mut open = None;
 
             ui.horizontal(|ui| {
                 ui.add(
@@ -219,94 +781,67 @@ impl eframe::App for MegraEditor {
 
                 let id = ui.make_persistent_id("file_chooser_box");
                 egui::ComboBox::from_id_source(id)
-                    .selected_text(&self.sketch_list[self.sketch_number])
+                    .selected_text("open sketch")
                     .show_ui(ui, |ui| {
-                        for i in 0..self.sketch_list.len() {
-                            ui.selectable_value(
-                                &mut sketch_number,
-                                SketchNumber::Num(i),
-                                &self.sketch_list[i],
-                            );
+                        for sketch in self.sketch_list.iter() {
+                            if ui.selectable_label(false, sketch).clicked() {
+                                open = Some(sketch.clone());
+                            }
                         }
                     });
-            });
-
-            let SketchNumber::Num(sk_num) = sketch_number;
-
-            //let mut sketch_switched = false;
-            if sk_num != self.sketch_number {
-                println!("switch sketch from {} to {}", self.sketch_number, sk_num);
-                self.sketch_number = sk_num;
 
-                // store content explicitly when changing ...
-                if !self.current_sketch.is_empty() {
-                    let p = path::Path::new(&self.current_sketch);
-                    match fs::write(p, self.content.as_bytes()) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("couldn't save sketch {e}");
-                        }
+                if ui.toggle_value(&mut self.split, "split").changed() {
+                    if self.split {
+                        // show another sketch on the right, if there is one
+                        self.panes[1] = (self.panes[0] + 1) % self.tabs.len();
+                    } else {
+                        self.panes[0] = self.panes[self.focused_pane];
+                        self.focused_pane = 0;
                     }
                 }
-
-                self.current_sketch = self.sketch_list[sk_num].clone();
-                let p = path::Path::new(&self.current_sketch);
-                match fs::read_to_string(p) {
-                    Ok(mut s) => {
-                        if !s.ends_with('\n') {
-                            s.push('\n');
+                let theme_id = ui.make_persistent_id("theme_chooser_box");
+                let mut theme = self.theme;
+                egui::ComboBox::from_id_source(theme_id)
+                    .selected_text(&self.themes[theme].0)
+                    .show_ui(ui, |ui| {
+                        for (i, (name, _)) in self.themes.iter().enumerate() {
+                            ui.selectable_value(&mut theme, i, name);
                         }
-                        self.content = s
-                    }
-                    Err(e) => {
-                        println!("couldn't read sketch {e}");
-                    }
+                    });
+                if theme != self.theme {
+                    self.theme = theme;
+                    self.set_visuals(ui.ctx());
                 }
-            }
 
-            ui.separator();
-
-            ScrollArea::vertical().show(ui, |ui| {
-                let num_lines = self.content.lines().count() + 1;
-
-                let theme = CodeTheme::dark(self.font_size);
-                let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-                    let layout_job = highlight(ui.ctx(), &theme, string);
-                    ui.fonts(|f| f.layout_job(layout_job))
-                };
+                ui.toggle_value(&mut self.show_timeline, "timeline");
+                if self.generator_info.is_some() {
+                    ui.toggle_value(&mut self.show_generators, "generators");
+                }
+                if self.generator_graphs.is_some() {
+                    ui.toggle_value(&mut self.show_graphs, "graphs");
+                }
+                if let Some((meters, _)) = &self.meters {
+                    ui.toggle_value(&mut self.show_scope, "scope");
+                    meters::show_meters(ui, meters, &self.themes[self.theme].1, self.font_size);
+                }
+            });
 
-                let tx = if let Some(cb) = self.callback.as_ref() {
-                    LivecodeTextEdit::multiline(&mut self.content)
-                        .desired_rows(30)
-                        //.reset_cursor(sketch_switched)
-                        .code_editor()
-                        .desired_width(800.0)
-                        .eval_callback(cb)
-                        .karl_yerkes_mode(self.karl_yerkes_mode)
-                        .layouter(&mut layouter)
-                } else {
-                    LivecodeTextEdit::multiline(&mut self.content)
-                        .desired_rows(30)
-                        .code_editor()
-                        //.reset_cursor(!sketch_switched)
-                        .desired_width(800.0)
-                        .layouter(&mut layouter)
-                };
+            if let Some(sketch) = open {
+                self.open_sketch(&sketch);
+            }
 
-                let mut linenums = "".to_owned();
-                for i in 1..num_lines {
-                    linenums.push_str(format!("{i}\n").as_str());
-                }
+            self.tab_row(ui);
 
-                let ln = egui::Label::new(
-                    egui::RichText::new(linenums).font(FontId::monospace(self.font_size)),
-                );
+            ui.separator();
 
-                ui.horizontal(|ui| {
-                    ui.add(ln);
-                    ui.add(tx);
+            if self.split {
+                ui.columns(2, |columns| {
+                    self.pane(&mut columns[0], 0);
+                    self.pane(&mut columns[1], 1);
                 });
-            });
+            } else {
+                self.pane(ui, 0);
+            }
         });
     }
 
@@ -314,17 +849,50 @@ impl eframe::App for MegraEditor {
         std::time::Duration::from_secs(5)
     }
 
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        if !self.current_sketch.is_empty() {
-            let p = path::Path::new(&self.current_sketch);
-            match fs::write(p, self.content.as_bytes()) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("couldn't save sketch {e}");
-                }
-            }
+    fn save(&mut self, _: &mut dyn eframe::Storage) {
+        for tab in self.tabs.iter() {
+            tab.save();
         }
+    }
+}
 
-        eframe::set_value(storage, eframe::APP_KEY, self);
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_tab() {
+        let mut ed = MegraEditor::default();
+        for name in ["a", "b"] {
+            ed.tabs.push(Tab::new(
+                "".to_string(),
+                std::env::temp_dir()
+                    .join(format!("test_close_tab_{name}.megra3"))
+                    .to_string_lossy()
+                    .to_string(),
+            ));
+        }
+        assert_eq!(ed.tabs[1].name(), "test_close_tab_a");
+        assert_eq!(ed.tabs[0].name(), "untitled");
+
+        ed.panes = [2, 0];
+        ed.close_tab(1);
+        // still the same sketches in both panes
+        assert_eq!(ed.tabs[ed.panes[0]].name(), "test_close_tab_b");
+        assert_eq!(ed.panes[1], 0);
+
+        ed.close_tab(1);
+        assert_eq!(ed.panes, [0, 0]);
+        // the last one stays open
+        ed.close_tab(0);
+        assert_eq!(ed.tabs.len(), 1);
+
+        // closing saves the sketch
+        for name in ["a", "b"] {
+            let p = std::env::temp_dir().join(format!("test_close_tab_{name}.megra3"));
+            assert!(p.exists());
+            fs::remove_file(p).unwrap();
+        }
     }
 }
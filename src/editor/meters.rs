@@ -0,0 +1,231 @@
+use egui::{Color32, Pos2, Rect, Sense, Shape, Stroke, Vec2};
+
+use crate::editor::syntax_highlighting::Palette;
+
+// how many samples per channel are kept for the scope and spectrum
+const SCOPE_LEN: usize = 2048;
+// levels below that are shown as silence
+const MIN_DB: f32 = -60.0;
+
+/// Levels and the latest samples of the output, per channel.
+pub struct AudioMeters {
+    samplerate: f32,
+    peak: Vec<f32>,
+    mean_square: Vec<f32>,
+    scope: Vec<Vec<f32>>,
+}
+
+fn to_db(amp: f32) -> f32 {
+    20.0 * amp.max(0.000001).log10()
+}
+
+/// Spectrum of the samples in dB, in `bands` log-spaced bands from 40Hz up
+/// to 16kHz (or nyquist). Goertzel filters are cheap enough for a few dozen
+/// bands, so there's no need for a full FFT.
+fn spectrum(samples: &[f32], samplerate: f32, bands: usize) -> Vec<(f32, f32)> {
+    let len = samples.len();
+    if len == 0 || bands < 2 {
+        return Vec::new();
+    }
+
+    // hann window
+    let window: Vec<f32> = (0..len)
+        .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / len as f32).cos())
+        .collect();
+    let window_sum: f32 = window.iter().sum();
+
+    let max_freq = 16000.0_f32.min(samplerate * 0.5);
+    (0..bands)
+        .map(|band| {
+            let freq = 40.0 * (max_freq / 40.0).powf(band as f32 / (bands - 1) as f32);
+            let coeff = 2.0 * (2.0 * std::f32::consts::PI * freq / samplerate).cos();
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for (x, w) in samples.iter().zip(window.iter()) {
+                let s = x * w + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s;
+            }
+            let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+            (freq, to_db(2.0 * power.sqrt() / window_sum))
+        })
+        .collect()
+}
+
+impl AudioMeters {
+    pub fn new(channels: usize, samplerate: f32) -> Self {
+        AudioMeters {
+            samplerate,
+            peak: vec![0.0; channels],
+            mean_square: vec![0.0; channels],
+            scope: vec![Vec::with_capacity(SCOPE_LEN * 2); channels],
+        }
+    }
+
+    /// a block of output, one slice per channel
+    pub fn feed(&mut self, block: &[&[f32]]) {
+        for (ch, samples) in block.iter().enumerate().take(self.peak.len()) {
+            if samples.is_empty() {
+                continue;
+            }
+            let block_peak = samples.iter().fold(0.0_f32, |p, s| p.max(s.abs()));
+            let block_ms = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+
+            // peaks fall back slowly, so they can be seen
+            self.peak[ch] = block_peak.max(self.peak[ch] * 0.97);
+            self.mean_square[ch] = 0.7 * self.mean_square[ch] + 0.3 * block_ms;
+
+            let scope = &mut self.scope[ch];
+            scope.extend_from_slice(samples);
+            if scope.len() > SCOPE_LEN {
+                scope.drain(..scope.len() - SCOPE_LEN);
+            }
+        }
+    }
+
+    pub fn peak(&self) -> &[f32] {
+        &self.peak
+    }
+
+    pub fn rms(&self) -> Vec<f32> {
+        self.mean_square.iter().map(|ms| ms.sqrt()).collect()
+    }
+
+    /// spectrum of all channels mixed down, as (frequency, dB)
+    pub fn spectrum(&self, bands: usize) -> Vec<(f32, f32)> {
+        let len = self.scope.iter().map(|s| s.len()).min().unwrap_or(0);
+        let mut mix = vec![0.0; len];
+        for scope in self.scope.iter() {
+            for (m, s) in mix.iter_mut().zip(scope[scope.len() - len..].iter()) {
+                *m += s / self.scope.len() as f32;
+            }
+        }
+        spectrum(&mix, self.samplerate, bands)
+    }
+}
+
+/// where a level ends up on a meter of the given width
+fn level_to_x(amp: f32, width: f32) -> f32 {
+    ((to_db(amp) - MIN_DB) / -MIN_DB).clamp(0.0, 1.0) * width
+}
+
+/// A small bar per channel, the RMS filled and the peak as a line,
+/// which gets the error color when it clips.
+pub fn show_meters(ui: &mut egui::Ui, meters: &AudioMeters, palette: &Palette, height: f32) {
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 1.0;
+        let bar_height = (height / meters.peak.len() as f32).clamp(2.0, 8.0);
+        for (peak, rms) in meters.peak().iter().zip(meters.rms()) {
+            let (rect, _) = ui.allocate_exact_size(Vec2::new(100.0, bar_height), Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, palette.meter_background);
+
+            let rms_rect = Rect::from_min_size(
+                rect.min,
+                Vec2::new(level_to_x(rms, rect.width()), rect.height()),
+            );
+            painter.rect_filled(rms_rect, 0.0, palette.meter_level);
+
+            let x = rect.min.x + level_to_x(*peak, rect.width());
+            let color = if *peak >= 1.0 {
+                palette.error
+            } else {
+                palette.meter_peak
+            };
+            painter.line_segment(
+                [Pos2::new(x, rect.min.y), Pos2::new(x, rect.max.y)],
+                Stroke::new(2.0, color),
+            );
+        }
+    });
+}
+
+/// The waveform of each channel and the spectrum of the mix.
+pub fn show_scope(ui: &mut egui::Ui, meters: &AudioMeters, palette: &Palette) {
+    let width = ui.available_width();
+    // the channels borrow the accent colors of the theme
+    let channel_colors: [Color32; 4] = [
+        palette.function,
+        palette.symbol,
+        palette.string,
+        palette.boolean,
+    ];
+
+    // oscilloscope, the latest samples
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, 150.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, palette.meter_background);
+    for (ch, scope) in meters.scope.iter().enumerate() {
+        let shown = &scope[scope.len().saturating_sub(512)..];
+        if shown.len() < 2 {
+            continue;
+        }
+        let points = shown
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                Pos2::new(
+                    rect.min.x + rect.width() * i as f32 / (shown.len() - 1) as f32,
+                    rect.center().y - s.clamp(-1.0, 1.0) * rect.height() * 0.5,
+                )
+            })
+            .collect();
+        painter.add(Shape::line(
+            points,
+            Stroke::new(1.0, channel_colors[ch % channel_colors.len()]),
+        ));
+    }
+
+    ui.add_space(4.0);
+
+    // spectrum, -90dB to 0dB
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, 150.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, palette.meter_background);
+    let bands = meters.spectrum(64);
+    let band_width = rect.width() / bands.len().max(1) as f32;
+    for (i, (_, db)) in bands.iter().enumerate() {
+        let level = ((db + 90.0) / 90.0).clamp(0.0, 1.0);
+        let bar = Rect::from_min_max(
+            Pos2::new(
+                rect.min.x + i as f32 * band_width,
+                rect.max.y - level * rect.height(),
+            ),
+            Pos2::new(rect.min.x + (i + 1) as f32 * band_width - 1.0, rect.max.y),
+        );
+        painter.rect_filled(bar, 0.0, palette.meter_level);
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meters_sine() {
+        let mut meters = AudioMeters::new(2, 44100.0);
+        // right on one of the bands, around 1kHz
+        let sine_freq = 40.0 * 400.0_f32.powf(34.0 / 63.0);
+        let sine: Vec<f32> = (0..512 * 32)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * sine_freq * n as f32 / 44100.0).sin())
+            .collect();
+        let silence = vec![0.0; 512];
+
+        for block in sine.chunks(512) {
+            meters.feed(&[block, &silence]);
+        }
+
+        assert!((meters.peak()[0] - 0.5).abs() < 0.01);
+        assert!((meters.rms()[0] - 0.354).abs() < 0.01);
+        assert_eq!(meters.peak()[1], 0.0);
+
+        // the loudest band is around 1kHz, the mix is half as loud (-12dB)
+        let bands = meters.spectrum(64);
+        let (freq, db) = bands
+            .iter()
+            .cloned()
+            .fold((0.0, f32::MIN), |a, b| if b.1 > a.1 { b } else { a });
+        assert!((freq - sine_freq).abs() < 1.0, "{freq}");
+        assert!((db + 12.0).abs() < 1.5, "{db}");
+    }
+}
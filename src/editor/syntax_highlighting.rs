@@ -1,6 +1,8 @@
 use crate::parser;
 use egui::text::LayoutJob;
-use egui::FontId;
+use egui::{Color32, FontId, TextFormat};
+use std::collections::BTreeMap;
+use std::path::Path;
 
 /// Memoized Code highlighting
 pub fn highlight(ctx: &egui::Context, theme: &CodeTheme, code: &str) -> LayoutJob {
@@ -27,12 +29,226 @@ enum TokenType {
     Keyword,
     Boolean,
     StringLiteral,
+    Symbol,
     Function,
     Command,
     GenMod,
     Whitespace,
 }
 
+/// The built-in themes, by name.
+pub const THEMES: &[&str] = &["dark", "light", "high-contrast"];
+
+/// The colors of a theme. Custom themes are read from `theme.toml` in the
+/// base folder, i.e.:
+///
+/// ```toml
+/// name = "stage"
+/// base = "high-contrast"
+/// keyword = "#ff8800"
+/// background = "#101010"
+/// ```
+///
+/// The colors that aren't given are taken from the base theme (`dark` if
+/// there's none). Colors are `#rrggbb` or `#rrggbbaa`, the names are
+/// `comment`, `normal`, `keyword` (`:keyword`), `boolean`, `string`,
+/// `symbol` (`'symbol`), `function`, `command`, `genmod`, `paren-match`,
+/// `background`, `error`, `cursor` (the block cursor in normal mode),
+/// `active-node` (in the graph view), `meter-background`, `meter-level`
+/// and `meter-peak`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub comment: Color32,
+    pub normal: Color32,
+    pub keyword: Color32,
+    pub boolean: Color32,
+    pub string: Color32,
+    pub symbol: Color32,
+    pub function: Color32,
+    pub command: Color32,
+    pub genmod: Color32,
+    pub paren_match: Color32,
+    pub background: Color32,
+    pub error: Color32,
+    pub cursor: Color32,
+    pub active_node: Color32,
+    pub meter_background: Color32,
+    pub meter_level: Color32,
+    pub meter_peak: Color32,
+}
+
+#[derive(serde::Deserialize)]
+struct ThemeConfig {
+    name: Option<String>,
+    base: Option<String>,
+    #[serde(flatten)]
+    colors: BTreeMap<String, String>,
+}
+
+/// "#rrggbb" or "#rrggbbaa"
+fn parse_color(text: &str) -> Option<Color32> {
+    let hex = text.strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let mut channels = Vec::new();
+    for i in (0..hex.len()).step_by(2) {
+        channels.push(u8::from_str_radix(&hex[i..i + 2], 16).ok()?);
+    }
+    let alpha = channels.get(3).copied().unwrap_or(255);
+    Some(Color32::from_rgba_unmultiplied(
+        channels[0],
+        channels[1],
+        channels[2],
+        alpha,
+    ))
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Palette {
+    pub fn dark() -> Self {
+        Palette {
+            comment: Color32::from_gray(120),
+            normal: Color32::from_gray(200),
+            keyword: Color32::from_rgb(200, 20, 200),
+            boolean: Color32::from_rgb(0, 200, 100),
+            string: Color32::from_rgb(200, 200, 10),
+            symbol: Color32::from_rgb(120, 170, 220),
+            function: Color32::from_rgb(220, 20, 100),
+            command: Color32::from_rgb(100, 220, 110),
+            genmod: Color32::from_rgb(190, 190, 140),
+            paren_match: Color32::from_rgba_unmultiplied(226, 33, 115, 190),
+            background: Color32::BLACK,
+            error: Color32::from_rgb(230, 40, 40),
+            cursor: Color32::from_rgba_unmultiplied(220, 220, 220, 120),
+            active_node: Color32::from_rgb(220, 80, 20),
+            meter_background: Color32::from_gray(30),
+            meter_level: Color32::from_rgb(40, 180, 60),
+            meter_peak: Color32::WHITE,
+        }
+    }
+
+    pub fn light() -> Self {
+        Palette {
+            comment: Color32::from_gray(130),
+            normal: Color32::from_gray(30),
+            keyword: Color32::from_rgb(150, 0, 150),
+            boolean: Color32::from_rgb(0, 130, 60),
+            string: Color32::from_rgb(150, 110, 0),
+            symbol: Color32::from_rgb(0, 90, 170),
+            function: Color32::from_rgb(190, 0, 70),
+            command: Color32::from_rgb(0, 130, 50),
+            genmod: Color32::from_rgb(110, 100, 40),
+            paren_match: Color32::from_rgba_unmultiplied(226, 33, 115, 110),
+            background: Color32::from_gray(250),
+            error: Color32::from_rgb(200, 0, 0),
+            cursor: Color32::from_rgba_unmultiplied(40, 40, 40, 100),
+            active_node: Color32::from_rgb(240, 120, 40),
+            meter_background: Color32::from_gray(220),
+            meter_level: Color32::from_rgb(0, 140, 50),
+            meter_peak: Color32::from_gray(30),
+        }
+    }
+
+    /// bright colors on black, for projectors
+    pub fn high_contrast() -> Self {
+        Palette {
+            comment: Color32::from_gray(170),
+            normal: Color32::WHITE,
+            keyword: Color32::from_rgb(255, 80, 255),
+            boolean: Color32::from_rgb(0, 255, 120),
+            string: Color32::from_rgb(255, 255, 0),
+            symbol: Color32::from_rgb(0, 220, 255),
+            function: Color32::from_rgb(255, 60, 60),
+            command: Color32::from_rgb(0, 255, 0),
+            genmod: Color32::from_rgb(255, 200, 100),
+            paren_match: Color32::from_rgba_unmultiplied(255, 0, 160, 220),
+            background: Color32::BLACK,
+            error: Color32::from_rgb(255, 40, 40),
+            cursor: Color32::from_rgba_unmultiplied(255, 255, 255, 150),
+            active_node: Color32::from_rgb(255, 120, 0),
+            meter_background: Color32::from_gray(50),
+            meter_level: Color32::from_rgb(0, 255, 0),
+            meter_peak: Color32::WHITE,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Palette::dark()),
+            "light" => Some(Palette::light()),
+            "high-contrast" => Some(Palette::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// whether the rest of the ui should be light as well
+    pub fn is_light(&self) -> bool {
+        let bg = self.background;
+        bg.r() as u32 + bg.g() as u32 + bg.b() as u32 > 384
+    }
+
+    /// a named theme from the TOML described above
+    pub fn from_toml(config: &str) -> Result<(String, Self), String> {
+        let config: ThemeConfig = toml::from_str(config).map_err(|e| e.to_string())?;
+
+        let base = config.base.as_deref().unwrap_or("dark");
+        let mut palette =
+            Palette::preset(base).ok_or_else(|| format!("unknown base theme '{base}'"))?;
+
+        for (name, value) in config.colors.iter() {
+            let color = parse_color(value)
+                .ok_or_else(|| format!("{name}: '{value}' isn't a color like #ff8800"))?;
+            let field = match name.as_str() {
+                "comment" => &mut palette.comment,
+                "normal" => &mut palette.normal,
+                "keyword" => &mut palette.keyword,
+                "boolean" => &mut palette.boolean,
+                "string" => &mut palette.string,
+                "symbol" => &mut palette.symbol,
+                "function" => &mut palette.function,
+                "command" => &mut palette.command,
+                "genmod" => &mut palette.genmod,
+                "paren-match" => &mut palette.paren_match,
+                "background" => &mut palette.background,
+                "error" => &mut palette.error,
+                "cursor" => &mut palette.cursor,
+                "active-node" => &mut palette.active_node,
+                "meter-background" => &mut palette.meter_background,
+                "meter-level" => &mut palette.meter_level,
+                "meter-peak" => &mut palette.meter_peak,
+                _ => return Err(format!("unknown color '{name}'")),
+            };
+            *field = color;
+        }
+
+        Ok((config.name.unwrap_or_else(|| "custom".to_string()), palette))
+    }
+
+    /// the theme from `theme.toml` in the base folder, if there is one
+    pub fn load(base_dir: &Path) -> Option<(String, Self)> {
+        let path = base_dir.join("theme.toml");
+        if !path.exists() {
+            return None;
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|config| Palette::from_toml(&config))
+        {
+            Ok(theme) => Some(theme),
+            Err(e) => {
+                println!("couldn't load theme {}: {e}", path.display());
+                None
+            }
+        }
+    }
+}
+
 #[derive(Clone, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "serde", serde(default))]
@@ -48,21 +264,26 @@ impl Default for CodeTheme {
 
 impl CodeTheme {
     pub fn dark(font_size: f32) -> Self {
+        Self::new(&Palette::dark(), font_size)
+    }
+
+    pub fn new(palette: &Palette, font_size: f32) -> Self {
         let text_style = FontId::monospace(font_size);
-        use egui::{Color32, TextFormat};
+        let format = |color: Color32| TextFormat::simple(text_style.clone(), color);
         Self {
             formats: enum_map::enum_map![
-                        TokenType::Comment => TextFormat::simple(text_style.clone(), Color32::from_gray(120)),
-                TokenType::Normal => TextFormat::simple(text_style.clone(), Color32::from_gray(200)),
-                TokenType::Boolean => TextFormat::simple(text_style.clone(), Color32::from_rgb(0, 200, 100)),
-                        TokenType::Keyword => TextFormat::simple(text_style.clone(), Color32::from_rgb(200, 20, 200)),
-                        TokenType::StringLiteral => TextFormat::simple(text_style.clone(), egui::Color32::from_rgb(200, 200, 10)),
-                    TokenType::Function => TextFormat::simple(text_style.clone(), Color32::from_rgb(220, 20, 100)),
-            TokenType::Command => TextFormat::simple(text_style.clone(), Color32::from_rgb(100, 220, 110)),
-            TokenType::GenMod => TextFormat::simple(text_style.clone(), Color32::from_rgb(190, 190, 140)),
-                        TokenType::Whitespace => TextFormat::simple(text_style.clone(), Color32::TRANSPARENT),
-                TokenType::Linebreak => TextFormat::simple(text_style.clone(), Color32::TRANSPARENT),
-                    ],
+                TokenType::Comment => format(palette.comment),
+                TokenType::Normal => format(palette.normal),
+                TokenType::Boolean => format(palette.boolean),
+                TokenType::Keyword => format(palette.keyword),
+                TokenType::StringLiteral => format(palette.string),
+                TokenType::Symbol => format(palette.symbol),
+                TokenType::Function => format(palette.function),
+                TokenType::Command => format(palette.command),
+                TokenType::GenMod => format(palette.genmod),
+                TokenType::Whitespace => format(Color32::TRANSPARENT),
+                TokenType::Linebreak => format(Color32::TRANSPARENT),
+            ],
         }
     }
 }
@@ -98,6 +319,12 @@ impl Highlighter {
                     .map_or_else(|| text.len(), |i| i + 1);
                 job.append(&text[..end], 0.0, theme.formats[TokenType::Keyword].clone());
                 text = &text[end..];
+            } else if text.starts_with('\'') {
+                let end = text[1..]
+                    .find(|c: char| !parser::valid_identifier_name_char(c))
+                    .map_or_else(|| text.len(), |i| i + 1);
+                job.append(&text[..end], 0.0, theme.formats[TokenType::Symbol].clone());
+                text = &text[end..];
             } else if text.starts_with('#') {
                 // avoid crash by checking text length
                 let end = if text.len() > 1 { 2 } else { 1 };
@@ -199,6 +426,15 @@ fn is_command(word: &str) -> bool {
             | "mod"
             | "pow"
             | "fun"
+            | "lambda"
+            | "defmacro"
+            | "macroexpand"
+            | "if"
+            | "when"
+            | "cond"
+            | "and"
+            | "or"
+            | "not"
             | "callback"
             | "osc-send"
             | "osc-sender"
@@ -206,6 +442,14 @@ fn is_command(word: &str) -> bool {
             | "map"
             | "pair"
             | "vec"
+            | "filter"
+            | "reduce"
+            | "range"
+            | "nth"
+            | "len"
+            | "zip"
+            | "shuffle"
+            | "choose"
             | "let"
             | "progn"
             | "match"
@@ -220,6 +464,7 @@ fn is_command(word: &str) -> bool {
             | "mtosym"
             | "veltodyn"
             | "load-file"
+            | "import"
     )
 }
 
@@ -242,3 +487,51 @@ fn is_genmod(word: &str) -> bool {
             | "solidify"
     )
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_theme() {
+        let (name, palette) = Palette::from_toml(
+            "name = \"stage\"\nbase = \"light\"\nkeyword = \"#ff8800\"\nparen-match = \"#ff000080\"\n",
+        )
+        .unwrap();
+        assert_eq!(name, "stage");
+        assert_eq!(palette.keyword, Color32::from_rgb(255, 136, 0));
+        assert_eq!(
+            palette.paren_match,
+            Color32::from_rgba_unmultiplied(255, 0, 0, 128)
+        );
+        // the rest is from the base theme
+        assert_eq!(palette.string, Palette::light().string);
+        assert_eq!(palette.meter_background, Palette::light().meter_background);
+        assert!(palette.is_light());
+
+        let (_, palette) = Palette::from_toml("active-node = \"#00ff00\"").unwrap();
+        assert_eq!(palette.active_node, Color32::from_rgb(0, 255, 0));
+
+        assert_eq!(Palette::from_toml("").unwrap().1, Palette::dark());
+        assert!(Palette::from_toml("base = \"pink\"").is_err());
+        assert!(Palette::from_toml("keyword = \"orange\"").is_err());
+        assert!(Palette::from_toml("keywords = \"#ff8800\"").is_err());
+    }
+
+    #[test]
+    fn test_highlight_symbols() {
+        let palette = Palette::dark();
+        let theme = CodeTheme::new(&palette, 15.0);
+        let job = Highlighter::default().highlight(&theme, "(sx 'ga #t (saw 100 :lpf 'a))");
+        let colored: Vec<(&str, Color32)> = job
+            .sections
+            .iter()
+            .map(|s| (&job.text[s.byte_range.clone()], s.format.color))
+            .collect();
+        assert!(colored.contains(&("'ga", palette.symbol)));
+        assert!(colored.contains(&("'a", palette.symbol)));
+        assert!(colored.contains(&(":lpf", palette.keyword)));
+        assert!(colored.contains(&("sx", palette.function)));
+    }
+}
@@ -0,0 +1,347 @@
+//! A local socket server to evaluate Mégra code from other editors.
+//!
+//! Clients connect via TCP (`127.0.0.1:57400`) or, on unix-like systems,
+//! a unix domain socket (`unix:/tmp/megra.sock`), and send one JSON
+//! object per line:
+//!
+//! `{"id": 1, "code": "(sx 'ga #t (nuc 'da (saw 100)))"}`
+//!
+//! The code can contain several top-level expressions, which are evaluated
+//! in order. For each request, the server answers with one line:
+//!
+//! `{"id": 1, "ok": true, "results": [{"type": "context", "name": "ga"}], "errors": []}`
+//!
+//! `ok` is false if any of the expressions couldn't be evaluated, in
+//! which case `errors` contains an object with a `message` for each,
+//! and, if known, the `start` and `end` of the failing part of the
+//! expression (as byte offsets).
+//! Any number of clients can be connected at the same time.
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::sync;
+
+use crate::builtin_types::{Comparable, TypedEntity};
+use crate::file_interpreter::segment_expressions;
+use crate::interpreter;
+use crate::parser::{self, EvalError, EvaluatedExpr, FunctionMap, Span};
+use crate::session::Session;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerAddress {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl FromStr for ServerAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(ServerAddress::Unix(path.into()));
+            #[cfg(not(unix))]
+            return Err(anyhow::anyhow!(
+                "unix sockets aren't available on this system: {path}"
+            ));
+        }
+        Ok(ServerAddress::Tcp(SocketAddr::from_str(s)?))
+    }
+}
+
+/// get id and code from a request line
+fn parse_request(line: &str) -> Result<(Value, String), String> {
+    let req: Value = serde_json::from_str(line).map_err(|e| format!("invalid request: {e}"))?;
+    let id = req.get("id").cloned().unwrap_or(Value::Null);
+    if let Some(code) = req.get("code").and_then(|c| c.as_str()) {
+        Ok((id, code.to_string()))
+    } else {
+        Err("invalid request: no code".to_string())
+    }
+}
+
+/// a short description of an evaluation result for the client
+fn describe(expr: &EvaluatedExpr) -> Value {
+    match expr {
+        EvaluatedExpr::SyncContext(s) => json!({"type": "context", "name": s.name}),
+        EvaluatedExpr::Typed(TypedEntity::Generator(g)) => json!({
+            "type": "generator",
+            "name": g.id_tags.iter().cloned().collect::<Vec<String>>().join(" "),
+        }),
+        EvaluatedExpr::Typed(TypedEntity::Comparable(c)) => match c {
+            Comparable::Float(f) => json!({"type": "number", "value": f}),
+            Comparable::Double(f) => json!({"type": "number", "value": f}),
+            Comparable::Int32(i) => json!({"type": "number", "value": i}),
+            Comparable::Int64(i) => json!({"type": "number", "value": i}),
+            Comparable::String(s) => json!({"type": "string", "value": s}),
+            Comparable::Symbol(s) => json!({"type": "symbol", "value": s}),
+            Comparable::Character(c) => json!({"type": "character", "value": c}),
+            Comparable::Boolean(b) => json!({"type": "boolean", "value": b}),
+        },
+        EvaluatedExpr::Typed(_) => json!({"type": "value"}),
+        EvaluatedExpr::Command(_) => json!({"type": "command"}),
+        EvaluatedExpr::Progn(exprs) => {
+            json!({"type": "progn", "results": exprs.iter().map(describe).collect::<Vec<Value>>()})
+        }
+        EvaluatedExpr::FunctionDefinition(name, _) => {
+            json!({"type": "function-definition", "name": name})
+        }
+        EvaluatedExpr::Closure(_) => json!({"type": "function"}),
+        EvaluatedExpr::MacroDefinition(name, _) => {
+            json!({"type": "macro-definition", "name": name})
+        }
+        EvaluatedExpr::VariableDefinition(_, _) => json!({"type": "variable-definition"}),
+        EvaluatedExpr::Keyword(k) => json!({"type": "keyword", "value": k}),
+        EvaluatedExpr::Identifier(i) => json!({"type": "identifier", "value": i}),
+        EvaluatedExpr::Match(_, _) => json!({"type": "match"}),
+    }
+}
+
+/// an expression that isn't finished, i.e. at the end of the code,
+/// with the innermost unclosed paren marked
+fn unclosed_error(code: &str) -> Option<Value> {
+    let pos = parser::find_unclosed_paren(code)?;
+    // the expression starts at the outermost one
+    let mut start = pos;
+    while let Some(outer) = parser::find_unclosed_paren(&code[..start]) {
+        start = outer;
+    }
+    let e = EvalError::UnclosedParen {
+        span: Span::new(pos - start, pos - start + 1),
+    };
+    Some(json!({
+        "expr": &code[start..],
+        "message": e.to_string(),
+        "start": pos - start,
+        "end": pos - start + 1,
+    }))
+}
+
+struct EvalServer<const BUFSIZE: usize, const NCHAN: usize> {
+    function_map: sync::Arc<Mutex<FunctionMap>>,
+    session: Session<BUFSIZE, NCHAN>,
+    base_dir: String,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> EvalServer<BUFSIZE, NCHAN> {
+    fn handle_request(&self, line: &str) -> Value {
+        let (id, code) = match parse_request(line) {
+            Ok(req) => req,
+            Err(e) => {
+                return json!({"id": Value::Null, "ok": false, "results": [], "errors": [{"message": e}]})
+            }
+        };
+
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+
+        let exprs = segment_expressions(code.clone());
+        // the complete expressions are evaluated anyway
+        if let Some(err) = unclosed_error(&code) {
+            errors.push(err);
+        } else if exprs.is_empty() && !code.trim().is_empty() {
+            errors.push(json!({"message": "no complete expression (unbalanced parens?)"}));
+        }
+
+        for expr in exprs {
+            let res = {
+                let funs = self.function_map.lock();
+                parser::eval_from_str(
+                    &expr,
+                    &funs,
+                    &self.session.globals,
+                    self.session.sample_set.clone(),
+                    self.session.output_mode,
+                )
+            };
+
+            match res {
+                Ok(res) => {
+                    results.push(describe(&res));
+                    interpreter::interpret(
+                        res,
+                        &self.function_map,
+                        self.session.clone(),
+                        self.base_dir.clone(),
+                    );
+                }
+                Err(e) => {
+                    let mut err = json!({"expr": expr, "message": e.to_string()});
+                    if let Some(span) = e.span() {
+                        err["start"] = json!(span.start);
+                        err["end"] = json!(span.end);
+                    }
+                    errors.push(err);
+                }
+            }
+        }
+
+        json!({"id": id, "ok": errors.is_empty(), "results": results, "errors": errors})
+    }
+
+    /// answer requests until the client disconnects
+    fn serve(&self, mut stream: impl Write, reader: impl Read) {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_request(&line);
+            if writeln!(stream, "{response}").is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> Clone for EvalServer<BUFSIZE, NCHAN> {
+    fn clone(&self) -> Self {
+        EvalServer {
+            function_map: sync::Arc::clone(&self.function_map),
+            session: self.session.clone(),
+            base_dir: self.base_dir.clone(),
+        }
+    }
+}
+
+/// A stale socket from a previous run would block the address, so it's
+/// removed. Anything else at that path is left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<(), anyhow::Error> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => anyhow::bail!("{} exists and isn't a socket", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Start listening in the background, each client gets its own thread.
+pub fn start_eval_server<const BUFSIZE: usize, const NCHAN: usize>(
+    addr: &ServerAddress,
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: Session<BUFSIZE, NCHAN>,
+    base_dir: String,
+) -> Result<(), anyhow::Error> {
+    let server = EvalServer {
+        function_map: sync::Arc::clone(function_map),
+        session,
+        base_dir,
+    };
+
+    match addr {
+        ServerAddress::Tcp(a) => {
+            let listener = TcpListener::bind(a)?;
+            println!("eval server listening on {a}");
+            std::thread::Builder::new()
+                .name("eval server".to_string())
+                .spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let server = server.clone();
+                        std::thread::spawn(move || {
+                            if let Ok(reader) = stream.try_clone() {
+                                server.serve(stream, reader);
+                            }
+                        });
+                    }
+                })?;
+        }
+        #[cfg(unix)]
+        ServerAddress::Unix(path) => {
+            remove_stale_socket(path)?;
+            let listener = std::os::unix::net::UnixListener::bind(path)?;
+            println!("eval server listening on {}", path.display());
+            std::thread::Builder::new()
+                .name("eval server".to_string())
+                .spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let server = server.clone();
+                        std::thread::spawn(move || {
+                            if let Ok(reader) = stream.try_clone() {
+                                server.serve(stream, reader);
+                            }
+                        });
+                    }
+                })?;
+        }
+    }
+
+    Ok(())
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            ServerAddress::from_str("127.0.0.1:57400").unwrap(),
+            ServerAddress::Tcp("127.0.0.1:57400".parse().unwrap())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            ServerAddress::from_str("unix:/tmp/megra.sock").unwrap(),
+            ServerAddress::Unix("/tmp/megra.sock".into())
+        );
+        assert!(ServerAddress::from_str("nonsense").is_err());
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let (id, code) = parse_request(r#"{"id": 3, "code": "(sx 'a #t)"}"#).unwrap();
+        assert_eq!(id, json!(3));
+        assert_eq!(code, "(sx 'a #t)");
+
+        assert!(parse_request(r#"{"id": 3}"#).is_err());
+        assert!(parse_request("(sx 'a #t)").is_err());
+    }
+
+    #[test]
+    fn test_unclosed_error() {
+        assert!(unclosed_error("(sx 'a #t (nuc 'a (saw 100)))").is_none());
+        assert!(unclosed_error(";; (unfinished\n(sx 'a #t)").is_none());
+
+        let err = unclosed_error(";; Mégra\n(sx 'a #t) (nuc 'b (saw 100").unwrap();
+        assert_eq!(err["expr"], json!("(nuc 'b (saw 100"));
+        assert_eq!(err["start"], json!(8));
+        assert_eq!(err["end"], json!(9));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_stale_socket() {
+        let dir = std::env::temp_dir().join("test_remove_stale_socket");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // nothing there
+        let sock = dir.join("megra.sock");
+        let _ = std::fs::remove_file(&sock);
+        assert!(remove_stale_socket(&sock).is_ok());
+
+        // an old socket is removed
+        drop(std::os::unix::net::UnixListener::bind(&sock).unwrap());
+        assert!(sock.exists());
+        assert!(remove_stale_socket(&sock).is_ok());
+        assert!(!sock.exists());
+
+        // a regular file isn't
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "don't delete me").unwrap();
+        assert!(remove_stale_socket(&file).is_err());
+        assert!(file.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
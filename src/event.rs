@@ -33,6 +33,8 @@ pub struct Event {
     // sample lookup is handled apart from the
     // parameters, as this makes things much easier ...
     pub sample_lookup: Option<SampleLookup>,
+    // the channel of midi-note events, 1 to 16
+    pub midi_channel: Option<u8>,
 }
 
 impl Debug for Event {
@@ -53,6 +55,7 @@ pub struct StaticEvent {
     pub tags: BTreeSet<String>,
     pub op: EventOperation,
     pub sample_lookup: Option<SampleLookup>,
+    pub midi_channel: Option<u8>,
 }
 
 /// A ControlEvent can call any function when interpreted.
@@ -355,6 +358,7 @@ impl Event {
             tags,
             op,
             sample_lookup: None,
+            midi_channel: None,
         }
     }
 
@@ -367,6 +371,7 @@ impl Event {
             tags,
             op: EventOperation::Replace,
             sample_lookup: None,
+            midi_channel: None,
         }
     }
 
@@ -398,6 +403,7 @@ impl Event {
             tags: self.tags.clone(),
             op: self.op,
             sample_lookup: self.sample_lookup.clone(),
+            midi_channel: self.midi_channel,
         }
     }
 }
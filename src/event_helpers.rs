@@ -293,6 +293,54 @@ pub fn map_synth_type(
     }
 }
 
+/// the parameter names map_parameter understands (without index),
+/// i.e. for completion in editors
+pub const PARAMETER_NAMES: &[&str] = &[
+    "freq",
+    "osc",
+    "note",
+    "atk",
+    "atkt",
+    "atkp",
+    "dec",
+    "dect",
+    "rel",
+    "relt",
+    "sus",
+    "env",
+    "pos",
+    "lvl",
+    "amp",
+    "gain",
+    "dur",
+    "lpf",
+    "lpd",
+    "lpq",
+    "lpt",
+    "hpf",
+    "hpq",
+    "hpt",
+    "pff",
+    "pfbw",
+    "pfg",
+    "pw",
+    "rate",
+    "start",
+    "loop",
+    "bufnum",
+    "rev",
+    "del",
+    "azi",
+    "ele",
+    "wt",
+    "wavetable",
+    "wm",
+    "wavematrix",
+    "ti",
+    "tableindex",
+    "dist",
+];
+
 pub fn map_parameter(name: &str) -> SynthParameterAddress {
     let mut id_str = "".to_string();
     let mut idx_str = "".to_string();
@@ -368,3 +416,73 @@ pub fn map_parameter(name: &str) -> SynthParameterAddress {
         label.into()
     }
 }
+
+/// The inverse of map_parameter, for when events leave megra
+/// (i.e. via OSC). Parameters that only make sense internally
+/// don't have a name.
+pub fn parameter_name(addr: &SynthParameterAddress) -> Option<String> {
+    let name = match addr.label {
+        SynthParameterLabel::PitchFrequency => "freq",
+        SynthParameterLabel::PitchNote => "note",
+        SynthParameterLabel::Attack => "atk",
+        SynthParameterLabel::AttackPeakLevel => "atkp",
+        SynthParameterLabel::Decay => "dec",
+        SynthParameterLabel::Release => "rel",
+        SynthParameterLabel::Sustain => "sus",
+        SynthParameterLabel::ChannelPosition => "pos",
+        SynthParameterLabel::EnvelopeLevel => "lvl",
+        SynthParameterLabel::OscillatorAmplitude => "amp",
+        SynthParameterLabel::Duration => "dur",
+        SynthParameterLabel::LowpassCutoffFrequency => "lpf",
+        SynthParameterLabel::LowpassFilterDistortion => "lpd",
+        SynthParameterLabel::LowpassQFactor => "lpq",
+        SynthParameterLabel::HighpassCutoffFrequency => "hpf",
+        SynthParameterLabel::HighpassQFactor => "hpq",
+        SynthParameterLabel::PeakFrequency => "pff",
+        SynthParameterLabel::PeakBandwidth => "pfbw",
+        SynthParameterLabel::PeakGain => "pfg",
+        SynthParameterLabel::Pulsewidth => "pw",
+        SynthParameterLabel::PlaybackRate => "rate",
+        SynthParameterLabel::PlaybackStart => "start",
+        SynthParameterLabel::PlaybackLoop => "loop",
+        SynthParameterLabel::ReverbMix => "rev",
+        SynthParameterLabel::DelayMix => "del",
+        SynthParameterLabel::AmbisonicAzimuth => "azi",
+        SynthParameterLabel::AmbisonicElevation => "ele",
+        SynthParameterLabel::WavematrixTableIndex => "ti",
+        SynthParameterLabel::WaveshaperMix => "dist",
+        _ => return None,
+    };
+
+    // indices start at one, see above
+    Some(match addr.idx {
+        Some(idx) => format!("{}{}", name, idx + 1),
+        None => name.to_string(),
+    })
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_names() {
+        // unknown names fall back to frequency, so every
+        // other name must map to something else
+        for name in PARAMETER_NAMES.iter().filter(|n| **n != "freq") {
+            assert!(
+                map_parameter(name).label != SynthParameterLabel::PitchFrequency,
+                "{name}"
+            );
+        }
+
+        for name in ["lvl", "rate", "lpf", "pos"] {
+            assert_eq!(parameter_name(&map_parameter(name)), Some(name.to_string()));
+        }
+        assert_eq!(
+            parameter_name(&map_parameter("lpf2")),
+            Some("lpf2".to_string())
+        );
+    }
+}
@@ -45,30 +45,39 @@ pub fn find_opening_paren(text: &str, pos: usize) -> Option<usize> {
     None
 }
 
-/// take a string and segment it into expressions
+/// take a string and segment it into expressions, skipping comments
+/// (the positions are byte offsets, so any text can be segmented)
 pub fn segment_expressions(text: String) -> Vec<String> {
-    let mut expr_bounds = Vec::new();
-    let mut last_close = 0;
-    let mut ignore = false;
-
-    for (pos, next_char) in text.chars().enumerate() {
-        if pos == last_close {
-            ignore = false;
+    let mut single_exprs = Vec::new();
+    let mut par_lvl = 0;
+    let mut open = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+
+    for (pos, next_char) in text.char_indices() {
+        if in_comment {
+            in_comment = next_char != '\n';
+            continue;
         }
-
-        if !ignore && next_char == '(' {
-            if let Some(close) = find_closing_paren(&text, pos + 1) {
-                expr_bounds.push((pos, close));
-                ignore = true;
-                last_close = close - 1;
+        match next_char {
+            '"' => in_string = !in_string,
+            ';' if !in_string => in_comment = true,
+            '(' if !in_string => {
+                if par_lvl == 0 {
+                    open = pos;
+                }
+                par_lvl += 1;
             }
+            ')' if !in_string && par_lvl > 0 => {
+                par_lvl -= 1;
+                if par_lvl == 0 {
+                    single_exprs.push(text[open..pos + 1].to_string());
+                }
+            }
+            _ => {}
         }
     }
 
-    let mut single_exprs = Vec::new();
-    for (open, close) in expr_bounds {
-        single_exprs.push(text[open..close].to_string());
-    }
     single_exprs
 }
 
@@ -95,8 +104,11 @@ pub fn parse_file<const BUFSIZE: usize, const NCHAN: usize>(
                     )
                 };
 
-                if let Ok(res) = res {
-                    interpreter::interpret(res, functions, session.clone(), base_dir.clone());
+                match res {
+                    Ok(res) => {
+                        interpreter::interpret(res, functions, session.clone(), base_dir.clone())
+                    }
+                    Err(e) => println!("{}", e.report(&expr)),
                 }
             }
         }
@@ -125,4 +137,13 @@ mod test {
 
         assert!(single_exprs.len() == 3);
     }
+
+    #[test]
+    fn test_file_segmentation_non_ascii() {
+        let a = ";; Mégra (old version)\n(sx 'ba #t (nuc 'hi \"(ü\"))\n(sx 'bb";
+
+        let single_exprs = segment_expressions(a.to_string());
+
+        assert_eq!(single_exprs, vec!["(sx 'ba #t (nuc 'hi \"(ü\"))"]);
+    }
 }
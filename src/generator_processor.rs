@@ -1,3 +1,4 @@
+use rosc::OscTime;
 use std::collections::HashMap;
 use std::sync::*;
 
@@ -65,7 +66,7 @@ pub trait GeneratorProcessor: GeneratorProcessorClone {
 
     /// if the processor holds something that can be visualized
     /// such as a markov chain ...
-    fn visualize_if_possible(&mut self, _vis_client: &VisualizerClient) {
+    fn visualize_if_possible(&mut self, _vis_client: &VisualizerClient, _timetag: OscTime) {
         /* most won't need this */
     }
 
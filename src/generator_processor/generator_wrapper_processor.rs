@@ -84,14 +84,14 @@ impl GeneratorProcessor for GeneratorWrapperProcessor {
         }
     }
 
-    fn visualize_if_possible(&mut self, vis_client: &VisualizerClient) {
+    fn visualize_if_possible(&mut self, vis_client: &VisualizerClient, timetag: OscTime) {
         if self.wrapped_generator.root_generator.is_modified() {
-            vis_client.create_or_update(&self.wrapped_generator);
+            vis_client.create_or_update(&self.wrapped_generator, timetag);
             self.wrapped_generator.root_generator.clear_modified();
         }
-        vis_client.update_active_node(&self.wrapped_generator);
+        vis_client.update_active_node(&self.wrapped_generator, timetag);
         for (_, proc) in self.wrapped_generator.processors.iter_mut() {
-            proc.visualize_if_possible(vis_client);
+            proc.visualize_if_possible(vis_client, timetag);
         }
     }
 
@@ -1,5 +1,4 @@
 use parking_lot::Mutex;
-use rosc::OscType;
 
 use std::sync;
 use std::thread;
@@ -8,7 +7,9 @@ use crate::builtin_types::*;
 
 use crate::commands;
 use crate::file_interpreter;
+use crate::midi_clock;
 use crate::midi_input;
+use crate::modules;
 use crate::osc_receiver::OscReceiver;
 use crate::parser::{EvaluatedExpr, FunctionMap};
 
@@ -25,6 +26,9 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
         Command::LoadFile(f) => {
             file_interpreter::parse_file(f, function_map, session, base_dir);
         }
+        Command::Import(name, namespace) => {
+            modules::import(&name, namespace, function_map, session, base_dir);
+        }
         Command::Push(id, te) => {
             commands::push(id, te, &session.globals);
         }
@@ -146,15 +150,18 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
         }
         Command::Tmod(p) => {
             commands::set_global_tmod(&session.globals, p);
+            commands::publish_tempo(session);
         }
         Command::Latency(p) => {
             commands::set_global_latency(&session.globals, p);
         }
         Command::DefaultDuration(d) => {
             commands::set_default_duration(&session.globals, d);
+            commands::publish_tempo(session);
         }
         Command::Bpm(b) => {
             commands::set_default_duration(&session.globals, b);
+            commands::publish_tempo(session);
         }
         Command::GlobRes(v) => {
             commands::set_global_lifemodel_resources(&session.globals, v);
@@ -183,38 +190,12 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
             );
         }
         Command::OscSendMessage(client_name, osc_addr, args) => {
-            let mut osc_args = Vec::new();
-            for arg in args.iter() {
-                match arg {
-                    TypedEntity::Comparable(Comparable::Float(n)) => {
-                        osc_args.push(OscType::Float(*n))
-                    }
-                    TypedEntity::Comparable(Comparable::Double(n)) => {
-                        osc_args.push(OscType::Double(*n))
-                    }
-                    TypedEntity::Comparable(Comparable::Int32(n)) => {
-                        osc_args.push(OscType::Int(*n))
-                    }
-                    TypedEntity::Comparable(Comparable::Int64(n)) => {
-                        osc_args.push(OscType::Long(*n))
-                    }
-                    TypedEntity::Comparable(Comparable::String(s)) => {
-                        osc_args.push(OscType::String(s.to_string()))
-                    }
-                    TypedEntity::Comparable(Comparable::Symbol(s)) => {
-                        osc_args.push(OscType::String(s.to_string()))
-                    }
-                    _ => {}
-                }
-            }
-            if let Some(thing) = &session.osc_client.custom.get(&client_name) {
-                let _ = thing.value().send_message(osc_addr, osc_args);
-            }
-            //println!("send msg {client_name} {osc_addr}");
+            // sent right away, there's no logical time here
+            commands::send_osc_message(session, &client_name, osc_addr, &args, None);
         }
-        Command::OscStartReceiver(target) => {
+        Command::OscStartReceiver(target, transport) => {
             let fmap2 = sync::Arc::clone(function_map);
-            OscReceiver::start_receiver_thread_udp(target, fmap2, session.clone(), base_dir);
+            OscReceiver::start_receiver_thread(target, transport, fmap2, session.clone(), base_dir);
         }
         Command::MidiStartReceiver(midi_in_port) => {
             let function_map_midi = sync::Arc::clone(function_map);
@@ -230,6 +211,40 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
         }
         Command::MidiListPorts => {
             midi_input::list_midi_input_ports();
+            midi_clock::list_midi_output_ports();
+        }
+        Command::MidiClockStart(midi_out_port) => {
+            commands::start_midi_clock(session, midi_out_port);
+        }
+        Command::MidiOpenOutput(midi_out_port) => {
+            commands::open_midi_output(session, midi_out_port);
+        }
+        Command::OscRoute(tag, sender, osc_addr) => {
+            session.osc_client.add_route(tag, sender, osc_addr);
+        }
+        Command::OscUnroute(tag) => {
+            session.osc_client.remove_route(&tag);
+        }
+        Command::MidiRoute(tag, midi_out_port, channel) => {
+            session.midi_out.add_route(tag, midi_out_port, channel);
+        }
+        Command::MidiUnroute(tag) => {
+            session.midi_out.remove_route(&tag);
+        }
+        Command::MidiClockStop => {
+            commands::stop_midi_clock(session);
+        }
+        Command::MidiClockFollow(midi_in_port) => {
+            commands::follow_midi_clock(session, midi_in_port);
+        }
+        Command::MidiClockFollowStop => {
+            commands::stop_following_midi_clock(session);
+        }
+        Command::TempoSyncJoin(local, peers, quantum) => {
+            commands::tempo_sync_join(session, local, peers, quantum);
+        }
+        Command::TempoSyncLeave => {
+            commands::tempo_sync_leave(session);
         }
     };
 }
@@ -314,9 +329,16 @@ pub fn interpret<const BUFSIZE: usize, const NCHAN: usize>(
         EvaluatedExpr::Typed(TypedEntity::Vec(v)) => {
             println!("a vec: {v:?}")
         }
-        EvaluatedExpr::FunctionDefinition(name, pos_args, body) => {
-            println!("a function definition: {name} positional args: {pos_args:?}");
-            function_map.lock().usr_lib.insert(name, (pos_args, body));
+        EvaluatedExpr::FunctionDefinition(name, fun) => {
+            println!("a function definition: {name} args: {:?}", fun.arg_names());
+            function_map.lock().usr_lib.insert(name, fun);
+        }
+        EvaluatedExpr::Closure(fun) => {
+            println!("a function, args: {:?}", fun.arg_names())
+        }
+        EvaluatedExpr::MacroDefinition(name, mac) => {
+            println!("a macro definition: {name} args: {:?}", mac.arg_names());
+            function_map.lock().macros.insert(name, mac);
         }
         EvaluatedExpr::VariableDefinition(name, var) => {
             println!("a variable definition {name:#?}");
@@ -0,0 +1,1035 @@
+//! A language server for Mégra, so external editors get completion,
+//! hover docs, diagnostics and go-to-definition. It's started via the
+//! `megra-lsp` binary and talks LSP on stdin/stdout.
+//!
+//! The server doesn't evaluate anything, it only looks at the text.
+//! Syntax errors are found by the regular parser, function names are
+//! checked against the standard library, the sample sets in the sample
+//! folder and the functions defined in the document itself.
+
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as RequestTrait};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use crate::event_helpers::PARAMETER_NAMES;
+use crate::parser::{parse_expr, syntax_error, valid_identifier_name_char, Span, SPECIAL_FORMS};
+use crate::standard_library::define_standard_library;
+
+// keywords that aren't synth parameters
+const KEYWORDS: &[&str] = &[
+    "dur", "keep", "events", "rules", "rep", "max-rep", "durs", "sync", "shift", "solo", "block",
+    "resync", "ch", "vel", "quantum", "proto", "id", "p", "cyc", "global", "relate", "boost",
+];
+
+// the forms that define something
+const FUNCTION_DEFINITIONS: &[&str] = &["fun", "callback", "defmacro"];
+const VARIABLE_DEFINITIONS: &[&str] = &["let", "defpart"];
+
+// short docs for the most common functions
+const FUNCTION_DOCS: &[(&str, &str)] = &[
+    (
+        "sx",
+        "`(sx 'name #t gen ...)` sync context, starts the generators in it (`#f` stops them)",
+    ),
+    (
+        "nuc",
+        "`(nuc 'name event ...)` nucleus, a generator that repeats its events",
+    ),
+    (
+        "cyc",
+        "`(cyc 'name \"bd ~ sn ~\")` a generator from a cycle string",
+    ),
+    (
+        "infer",
+        "`(infer 'name :events ... :rules ...)` a generator from explicit rules",
+    ),
+    (
+        "rule",
+        "`(rule 'a 'b 100 200)` transition from a to b, with probability and duration",
+    ),
+    (
+        "learn",
+        "`(learn 'name :events ... :sample \"...\")` a generator learned from a sample string",
+    ),
+    (
+        "fully",
+        "`(fully 'name events ...)` fully connected generator",
+    ),
+    (
+        "friendship",
+        "`(friendship 'name center friends ...)` friendship graph generator",
+    ),
+    (
+        "flower",
+        "`(flower 'name :pistil ev :petals (...))` flower-shaped generator",
+    ),
+    ("lin", "`(lin 'name events ...)` linear sequence"),
+    ("loop", "`(loop 'name events ...)` looping sequence"),
+    (
+        "chop",
+        "`(chop 'name 8 sample-event)` chops a sample into slices",
+    ),
+    (
+        "stages",
+        "`(stages 'name events ...)` a generator moving through stages",
+    ),
+    (
+        "ctrl",
+        "`(ctrl ...)` control event, evaluates the commands when it's played",
+    ),
+    ("tmod", "`(tmod 1.0)` global time modifier"),
+    (
+        "bpm",
+        "`(bpm 120)` set the default duration from beats per minute",
+    ),
+    ("latency", "`(latency 0.05)` the latency in seconds"),
+    ("progn", "`(progn ...)` evaluate several expressions"),
+    (
+        "import",
+        "`(import \"drums\" :as 'dr)` load `lib/drums.megra3`, its definitions are called as `dr/name`",
+    ),
+    ("clear", "`(clear)` stop everything"),
+    (
+        "fun",
+        "`(fun name (args ... :optional (arg default) ... :key (arg default) ...) body ...)` define a function",
+    ),
+    (
+        "lambda",
+        "`(lambda (args ...) body ...)` a function without a name, which sees the local variables where it's made",
+    ),
+    (
+        "defmacro",
+        "`(defmacro name (args ... :optional (arg default) ... :rest args) `(... ,arg ,@args))` define a macro, which rewrites the code before it's evaluated",
+    ),
+    (
+        "macroexpand",
+        "`(macroexpand (name ...))` the code a macro call turns into",
+    ),
+    (
+        "if",
+        "`(if condition then else)` `then` if the condition holds, `else` (or `#f`) otherwise",
+    ),
+    (
+        "when",
+        "`(when condition body ...)` evaluate the body if the condition holds",
+    ),
+    (
+        "cond",
+        "`(cond condition result ... default)` the result of the first condition that holds",
+    ),
+    ("and", "`(and a b ...)` true if all of them are, `#f` and 0 are false"),
+    ("or", "`(or a b ...)` the first one that's true"),
+    ("not", "`(not a)` true if `a` is `#f` or 0"),
+    (
+        "lt",
+        "`(lt a b ...)` smaller than, also `<`, `gt`/`>`, `lte`/`<=`, `gte`/`>=`, `eq`/`=`",
+    ),
+    (
+        "map",
+        "`(map f vec ...)` call `f` on each element (of each vector), `(map pair ...)` makes a map",
+    ),
+    ("filter", "`(filter f vec)` the elements `f` returns something true for"),
+    (
+        "reduce",
+        "`(reduce f start vec)` combine the elements with `f`, i.e. `(reduce add (vec 1 2 3))`",
+    ),
+    ("range", "`(range start end step)` a vector of numbers, `(range 4)` is 0 to 3"),
+    ("nth", "`(nth vec 0)` the first element"),
+    ("len", "`(len vec)` the number of elements"),
+    ("zip", "`(zip vec vec ...)` a vector of vectors, with one element from each"),
+    ("shuffle", "`(shuffle vec)` the elements in random order"),
+    ("choose", "`(choose vec)` a random element"),
+    (
+        "let",
+        "`(let 'name value)` define a variable, `(let ((name value) ...) body ...)` local variables",
+    ),
+    (
+        "osc-sender",
+        "`(osc-sender 'name \"127.0.0.1:57120\")` define an OSC sender",
+    ),
+    (
+        "osc-send",
+        "`(osc-send 'name \"/addr\" args ...)` send an OSC message",
+    ),
+    (
+        "osc-route",
+        "`(osc-route 'tag 'sender \"/dirt/play\")` send tagged events to OSC",
+    ),
+    (
+        "osc-receiver",
+        "`(osc-receiver \"127.0.0.1:57123\" :proto 'udp)` receive OSC",
+    ),
+    (
+        "midi-route",
+        "`(midi-route 'tag port :ch 1)` send tagged events to MIDI",
+    ),
+    (
+        "midi-note",
+        "`(midi-note 60 :vel 100 :ch 1)` a MIDI note event",
+    ),
+    (
+        "tempo-sync",
+        "`(tempo-sync \"local\" \"peer\" ... :quantum 4)` share tempo with peers",
+    ),
+];
+
+// the keyword arguments of the functions that aren't events,
+// events take the synth parameters
+const FUNCTION_KEYWORDS: &[(&str, &[&str])] = &[
+    ("sx", &["sync", "resync", "shift", "solo", "block"]),
+    ("nuc", &["dur", "keep"]),
+    ("fully", &["events", "dur", "keep"]),
+    ("infer", &["events", "rules", "dur", "keep"]),
+    (
+        "learn",
+        &[
+            "events",
+            "sample",
+            "dur",
+            "bound",
+            "tie",
+            "epsilon",
+            "size",
+            "autosilence",
+            "keep",
+        ],
+    ),
+    (
+        "cyc",
+        &["dur", "rep", "rnd", "max-rep", "events", "map", "keep"],
+    ),
+    (
+        "loop",
+        &["dur", "rep", "rnd", "max-rep", "events", "map", "keep"],
+    ),
+    ("chop", &["dur", "rep", "rnd", "max-rep", "keep"]),
+    (
+        "flower",
+        &[
+            "dur", "events", "layers", "pistil", "petals", "rep", "rnd", "max-rep", "keep",
+        ],
+    ),
+    (
+        "friendship",
+        &[
+            "dur", "events", "center", "friends", "rep", "rnd", "max-rep", "keep",
+        ],
+    ),
+    ("stages", &["dur", "cyc", "pnext", "pprev", "rnd", "keep"]),
+    ("facts", &["rnd", "keep"]),
+    ("vals", &["rnd", "keep"]),
+    ("osc-receiver", &["proto"]),
+    ("midi-note", &["vel", "ch", "dur"]),
+    ("midi-route", &["ch"]),
+    ("tempo-sync", &["quantum"]),
+];
+
+pub fn function_doc(name: &str) -> Option<&'static str> {
+    FUNCTION_DOCS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, doc)| *doc)
+}
+
+/// the keyword arguments of a function, if it isn't an event
+pub fn function_keywords(name: &str) -> Option<&'static [&'static str]> {
+    FUNCTION_KEYWORDS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, keywords)| *keywords)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Open(usize),
+    Close(usize),
+    // identifiers, symbols, keywords, numbers ...
+    Atom(String, usize),
+    Str(String, usize),
+}
+
+/// Split the text into tokens, with their byte offsets. Comments
+/// are skipped, unterminated strings run until the end.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open(pos)),
+            ')' => tokens.push(Token::Close(pos)),
+            ';' => {
+                while let Some((_, c)) = chars.peek() {
+                    if *c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '"' => {
+                let mut s = String::new();
+                for (_, c) in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                }
+                tokens.push(Token::Str(s, pos));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = String::from(c);
+                while let Some((_, c)) = chars.peek() {
+                    if c.is_whitespace() || *c == '(' || *c == ')' || *c == '"' || *c == ';' {
+                        break;
+                    }
+                    s.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Atom(s, pos));
+            }
+        }
+    }
+
+    tokens
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DefinitionKind {
+    Function,
+    Variable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub offset: usize, // where the name is
+    pub kind: DefinitionKind,
+    pub args: Vec<String>,
+}
+
+/// the function whose argument list is still open at the end of the
+/// tokens, i.e. `nuc` for `(sx 'ga #t (nuc 'a`
+pub fn enclosing_function(tokens: &[Token]) -> Option<&str> {
+    let mut heads: Vec<Option<&str>> = Vec::new();
+    let mut after_open = false;
+    for t in tokens {
+        match t {
+            Token::Open(_) => {
+                heads.push(None);
+                after_open = true;
+                continue;
+            }
+            Token::Close(_) => {
+                heads.pop();
+            }
+            Token::Atom(head, _) if after_open => {
+                if let Some(h) = heads.last_mut() {
+                    *h = Some(head);
+                }
+            }
+            _ => {}
+        }
+        after_open = false;
+    }
+    heads.last().copied().flatten()
+}
+
+/// find everything that's defined with fun/callback and let/defpart
+pub fn find_definitions(tokens: &[Token]) -> Vec<Definition> {
+    let mut defs = Vec::new();
+
+    for (i, window) in tokens.windows(3).enumerate() {
+        let (head, name, offset) = match window {
+            // the offset should point to the name itself, not the quote
+            [Token::Open(_), Token::Atom(head, _), Token::Atom(name, offset)] => {
+                let trimmed = name.trim_start_matches('\'');
+                (head, trimmed, offset + name.len() - trimmed.len())
+            }
+            [Token::Open(_), Token::Atom(head, _), Token::Str(name, offset)] => {
+                (head, name.as_str(), offset + 1)
+            }
+            _ => continue,
+        };
+
+        let kind = if FUNCTION_DEFINITIONS.contains(&head.as_str()) {
+            DefinitionKind::Function
+        } else if VARIABLE_DEFINITIONS.contains(&head.as_str()) {
+            DefinitionKind::Variable
+        } else {
+            continue;
+        };
+
+        let mut args = Vec::new();
+        if kind == DefinitionKind::Function {
+            if let Some(Token::Open(_)) = tokens.get(i + 3) {
+                for t in tokens[i + 4..].iter() {
+                    match t {
+                        Token::Atom(a, _) => args.push(a.clone()),
+                        _ => break,
+                    }
+                }
+            }
+        }
+
+        defs.push(Definition {
+            name: name.to_string(),
+            offset,
+            kind,
+            args,
+        });
+    }
+
+    defs
+}
+
+/// Names that are only bound locally, the arguments of fun/callback/lambda
+/// and the variables of `(let ((name value) ...) ...)`.
+fn find_local_names(tokens: &[Token]) -> BTreeSet<&str> {
+    let mut names = BTreeSet::new();
+
+    for (i, window) in tokens.windows(4).enumerate() {
+        let list_start = match window {
+            [Token::Open(_), Token::Atom(head, _), Token::Open(_), _]
+                if head == "lambda" || VARIABLE_DEFINITIONS.contains(&head.as_str()) =>
+            {
+                i + 2
+            }
+            [Token::Open(_), Token::Atom(head, _), Token::Atom(..), Token::Open(_)]
+                if FUNCTION_DEFINITIONS.contains(&head.as_str()) =>
+            {
+                i + 3
+            }
+            _ => continue,
+        };
+
+        // the names are on the first level, or first in a nested list,
+        // like (name default) or (name value)
+        let mut depth = 0;
+        let mut after_open = false;
+        for t in tokens[list_start..].iter() {
+            match t {
+                Token::Open(_) => {
+                    depth += 1;
+                    after_open = true;
+                    continue;
+                }
+                Token::Close(_) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Token::Atom(name, _) if depth == 1 || (depth == 2 && after_open) => {
+                    names.insert(name.as_str());
+                }
+                _ => {}
+            }
+            after_open = false;
+        }
+    }
+
+    names
+}
+
+/// A problem in the text, as byte offsets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+    pub is_error: bool,
+}
+
+/// unmatched parens, and the ranges of the complete top-level expressions
+fn check_parens(tokens: &[Token]) -> (Vec<Problem>, Vec<(usize, usize)>) {
+    let mut problems = Vec::new();
+    let mut expressions = Vec::new();
+    let mut open = Vec::new();
+
+    for t in tokens.iter() {
+        match t {
+            Token::Open(pos) => open.push(*pos),
+            Token::Close(pos) => {
+                if let Some(start) = open.pop() {
+                    if open.is_empty() {
+                        expressions.push((start, *pos + 1));
+                    }
+                } else {
+                    problems.push(Problem {
+                        start: *pos,
+                        end: *pos + 1,
+                        message: "unmatched closing paren".to_string(),
+                        is_error: true,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for pos in open {
+        problems.push(Problem {
+            start: pos,
+            end: pos + 1,
+            message: "unclosed paren".to_string(),
+            is_error: true,
+        });
+    }
+
+    (problems, expressions)
+}
+
+/// let the actual parser have a look at a complete expression
+fn check_syntax(text: &str, start: usize, end: usize) -> Option<Problem> {
+    // comments would confuse the parser, blank them but keep the offsets
+    let mut expr = String::with_capacity(end - start);
+    let mut in_comment = false;
+    let mut in_string = false;
+    for c in text[start..end].chars() {
+        match c {
+            '"' if !in_comment => {
+                in_string = !in_string;
+                expr.push(c);
+            }
+            ';' if !in_string => {
+                in_comment = true;
+                expr.push(' ');
+            }
+            '\n' => {
+                in_comment = false;
+                expr.push(c);
+            }
+            c if in_comment => expr.push_str(&" ".repeat(c.len_utf8())),
+            c => expr.push(c),
+        }
+    }
+
+    match parse_expr(&expr) {
+        Ok(_) => None,
+        Err(e) => {
+            let error = syntax_error(&expr, e);
+            let span = error.span().unwrap_or(Span::new(0, 1));
+            Some(Problem {
+                start: start + span.start,
+                end: start + span.end.max(span.start + 1),
+                message: error.to_string(),
+                is_error: true,
+            })
+        }
+    }
+}
+
+fn is_function_name(name: &str) -> bool {
+    name.chars().all(valid_identifier_name_char)
+        && !name.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+}
+
+/// Check parens, syntax and function names. Unknown functions are
+/// only warnings, as they might be defined somewhere else.
+pub fn check_document(text: &str, known: &dyn Fn(&str) -> bool) -> Vec<Problem> {
+    let tokens = tokenize(text);
+    let (mut problems, expressions) = check_parens(&tokens);
+
+    for (start, end) in expressions {
+        if let Some(p) = check_syntax(text, start, end) {
+            problems.push(p);
+        }
+    }
+
+    let defs = find_definitions(&tokens);
+    let mut defined: BTreeSet<&str> = defs.iter().map(|d| d.name.as_str()).collect();
+    defined.extend(find_local_names(&tokens));
+
+    for (i, window) in tokens.windows(2).enumerate() {
+        if let [Token::Open(_), Token::Atom(name, offset)] = window {
+            // argument lists of function definitions
+            if i >= 3 {
+                if let (Token::Open(_), Token::Atom(head, _)) = (&tokens[i - 3], &tokens[i - 2]) {
+                    if FUNCTION_DEFINITIONS.contains(&head.as_str()) {
+                        continue;
+                    }
+                }
+            }
+
+            if !is_function_name(name)
+                || SPECIAL_FORMS.contains(&name.as_str())
+                || FUNCTION_DEFINITIONS.contains(&name.as_str())
+                || VARIABLE_DEFINITIONS.contains(&name.as_str())
+                || defined.contains(name.as_str())
+                || known(name)
+            {
+                continue;
+            }
+
+            problems.push(Problem {
+                start: *offset,
+                end: *offset + name.len(),
+                message: format!("unknown function '{name}'"),
+                is_error: false,
+            });
+        }
+    }
+
+    problems
+}
+
+/// LSP positions count UTF-16 code units ...
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let mut line = 0;
+    let mut character = 0;
+    for (pos, c) in text.char_indices() {
+        if pos >= offset {
+            break;
+        }
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16() as u32;
+        }
+    }
+    Position { line, character }
+}
+
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line = 0;
+    let mut character = 0;
+    for (pos, c) in text.char_indices() {
+        if line == position.line && character >= position.character {
+            return pos;
+        }
+        if c == '\n' {
+            if line == position.line {
+                return pos;
+            }
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16() as u32;
+        }
+    }
+    text.len()
+}
+
+/// the word around the offset, and where it starts
+pub fn word_at(text: &str, offset: usize) -> (String, usize) {
+    let is_word_char = |c: char| valid_identifier_name_char(c) || c == '/' || c == '*';
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(offset);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_word_char(*c))
+        .map(|(i, _)| offset + i)
+        .unwrap_or(text.len());
+    (text[start..end].to_string(), start)
+}
+
+pub struct LanguageServer {
+    documents: HashMap<Url, String>,
+    std_lib: BTreeSet<String>,
+    sample_sets: BTreeSet<String>,
+}
+
+impl LanguageServer {
+    pub fn new(samples_path: Option<&Path>) -> Self {
+        let mut std_lib: BTreeSet<String> = define_standard_library().std_lib.into_keys().collect();
+        std_lib.extend(SPECIAL_FORMS.iter().map(|f| f.to_string()));
+
+        // each folder in the sample folder becomes a function
+        let mut sample_sets = BTreeSet::new();
+        if let Some(entries) = samples_path.and_then(|p| std::fs::read_dir(p).ok()) {
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    sample_sets.insert(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+
+        LanguageServer {
+            documents: HashMap::new(),
+            std_lib,
+            sample_sets,
+        }
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        self.std_lib.contains(name) || self.sample_sets.contains(name)
+    }
+
+    pub fn diagnostics(&self, text: &str) -> Vec<Diagnostic> {
+        check_document(text, &|name| self.is_known(name))
+            .into_iter()
+            .map(|p| Diagnostic {
+                range: Range::new(
+                    offset_to_position(text, p.start),
+                    offset_to_position(text, p.end),
+                ),
+                severity: Some(if p.is_error {
+                    DiagnosticSeverity::ERROR
+                } else {
+                    DiagnosticSeverity::WARNING
+                }),
+                source: Some("megra".to_string()),
+                message: p.message,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    pub fn completion(&self, text: &str, position: Position) -> Vec<CompletionItem> {
+        let offset = position_to_offset(text, position);
+        let (prefix, start) = word_at(&text[..offset], offset);
+        let keyword = text[..start].ends_with(':');
+
+        // keyword lists overlap a little, and definitions can shadow builtins
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        if keyword {
+            let keywords: Vec<&&str> =
+                match enclosing_function(&tokenize(&text[..start])).and_then(function_keywords) {
+                    Some(keywords) => keywords.iter().collect(),
+                    None => PARAMETER_NAMES.iter().chain(KEYWORDS.iter()).collect(),
+                };
+            for k in keywords {
+                if k.starts_with(&prefix) && seen.insert(k.to_string()) {
+                    items.push(CompletionItem {
+                        label: k.to_string(),
+                        kind: Some(CompletionItemKind::PROPERTY),
+                        ..Default::default()
+                    });
+                }
+            }
+        } else {
+            for def in find_definitions(&tokenize(text)) {
+                if def.name.starts_with(&prefix) && seen.insert(def.name.clone()) {
+                    items.push(CompletionItem {
+                        label: def.name,
+                        kind: Some(match def.kind {
+                            DefinitionKind::Function => CompletionItemKind::FUNCTION,
+                            DefinitionKind::Variable => CompletionItemKind::VARIABLE,
+                        }),
+                        ..Default::default()
+                    });
+                }
+            }
+            for name in self.std_lib.iter().chain(self.sample_sets.iter()) {
+                if name.starts_with(&prefix) && seen.insert(name.clone()) {
+                    items.push(CompletionItem {
+                        label: name.clone(),
+                        kind: Some(CompletionItemKind::FUNCTION),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+
+        items
+    }
+
+    pub fn hover(&self, text: &str, position: Position) -> Option<String> {
+        let (word, _) = word_at(text, position_to_offset(text, position));
+        if word.is_empty() {
+            return None;
+        }
+
+        if let Some(def) = find_definitions(&tokenize(text))
+            .into_iter()
+            .find(|d| d.name == word)
+        {
+            return Some(match def.kind {
+                DefinitionKind::Function => {
+                    format!("`({} {})` user-defined function", word, def.args.join(" "))
+                }
+                DefinitionKind::Variable => format!("`{word}` variable"),
+            });
+        }
+
+        if let Some(doc) = function_doc(&word) {
+            Some(doc.to_string())
+        } else if self.std_lib.contains(&word) {
+            Some(format!("`{word}` built-in function"))
+        } else if self.sample_sets.contains(&word) {
+            Some(format!("`({word} ...)` sample event"))
+        } else {
+            None
+        }
+    }
+
+    pub fn definition(&self, text: &str, position: Position) -> Option<Range> {
+        let (word, _) = word_at(text, position_to_offset(text, position));
+        find_definitions(&tokenize(text))
+            .into_iter()
+            .find(|d| d.name == word)
+            .map(|d| {
+                Range::new(
+                    offset_to_position(text, d.offset),
+                    offset_to_position(text, d.offset + d.name.len()),
+                )
+            })
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        let result = match req.method.as_str() {
+            Completion::METHOD => serde_json::from_value::<CompletionParams>(req.params)
+                .ok()
+                .and_then(|p| {
+                    let doc = p.text_document_position.text_document.uri;
+                    let text = self.documents.get(&doc)?;
+                    Some(CompletionResponse::Array(
+                        self.completion(text, p.text_document_position.position),
+                    ))
+                })
+                .map(|r| serde_json::to_value(r).unwrap_or_default()),
+            HoverRequest::METHOD => serde_json::from_value::<HoverParams>(req.params)
+                .ok()
+                .and_then(|p| {
+                    let doc = p.text_document_position_params.text_document.uri;
+                    let text = self.documents.get(&doc)?;
+                    self.hover(text, p.text_document_position_params.position)
+                })
+                .map(|doc| {
+                    serde_json::to_value(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: doc,
+                        }),
+                        range: None,
+                    })
+                    .unwrap_or_default()
+                }),
+            GotoDefinition::METHOD => serde_json::from_value::<GotoDefinitionParams>(req.params)
+                .ok()
+                .and_then(|p| {
+                    let uri = p.text_document_position_params.text_document.uri;
+                    let text = self.documents.get(&uri)?;
+                    let range = self.definition(text, p.text_document_position_params.position)?;
+                    Some(GotoDefinitionResponse::Scalar(Location { uri, range }))
+                })
+                .map(|r| serde_json::to_value(r).unwrap_or_default()),
+            _ => {
+                return Response::new_err(
+                    req.id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request {}", req.method),
+                )
+            }
+        };
+
+        Response::new_ok(req.id, result.unwrap_or(serde_json::Value::Null))
+    }
+
+    /// returns the diagnostics to publish, if any
+    fn handle_notification(&mut self, not: Notification) -> Option<PublishDiagnosticsParams> {
+        let (uri, diagnostics) = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let p: DidOpenTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                let diagnostics = self.diagnostics(&p.text_document.text);
+                self.documents
+                    .insert(p.text_document.uri.clone(), p.text_document.text);
+                (p.text_document.uri, diagnostics)
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut p: DidChangeTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                // full sync, the last change is the whole document
+                let text = p.content_changes.pop()?.text;
+                let diagnostics = self.diagnostics(&text);
+                self.documents.insert(p.text_document.uri.clone(), text);
+                (p.text_document.uri, diagnostics)
+            }
+            DidCloseTextDocument::METHOD => {
+                let p: DidCloseTextDocumentParams = serde_json::from_value(not.params).ok()?;
+                self.documents.remove(&p.text_document.uri);
+                (p.text_document.uri, Vec::new())
+            }
+            _ => return None,
+        };
+
+        Some(PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        })
+    }
+}
+
+/// serve on stdin/stdout until the client shuts us down
+pub fn run(samples_path: Option<&Path>) -> Result<(), anyhow::Error> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["(".to_string(), ":".to_string()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = LanguageServer::new(samples_path);
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                connection.sender.send(server.handle_request(req).into())?;
+            }
+            Message::Notification(not) => {
+                if let Some(diagnostics) = server.handle_notification(not) {
+                    connection.sender.send(
+                        Notification::new(PublishDiagnostics::METHOD.to_string(), diagnostics)
+                            .into(),
+                    )?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    // the writer thread only finishes once the connection is gone
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKETCH: &str = ";; a sketch (with parens in comments
+(fun beat (a b)
+  (nuc 'x (saw a)))
+
+(let 'base 100)
+
+(sx 'ga #t
+  (beat 100 200)
+  (frobnicate 'y))
+";
+
+    #[test]
+    fn test_definitions() {
+        let defs = find_definitions(&tokenize(SKETCH));
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs[0].name, "beat");
+        assert_eq!(defs[0].kind, DefinitionKind::Function);
+        assert_eq!(defs[0].args, vec!["a", "b"]);
+        assert_eq!(&SKETCH[defs[0].offset..defs[0].offset + 4], "beat");
+        assert_eq!(defs[1].name, "base");
+        assert_eq!(defs[1].kind, DefinitionKind::Variable);
+        assert_eq!(&SKETCH[defs[1].offset..defs[1].offset + 4], "base");
+    }
+
+    #[test]
+    fn test_check_document() {
+        let known = |name: &str| ["nuc", "saw", "sx"].contains(&name);
+
+        let problems = check_document(SKETCH, &known);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "unknown function 'frobnicate'");
+        assert!(!problems[0].is_error);
+
+        // missing paren
+        let problems = check_document("(sx 'ga #t\n  (nuc 'x (saw 100))", &known);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].start, 0);
+        assert!(problems[0].is_error);
+
+        // local variables and arguments aren't unknown functions
+        let problems = check_document(
+            "(fun f (x :optional (y 2)) (let ((g (lambda (z) (saw z)))) (g (y x))))",
+            &known,
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+
+        let problems = check_document("(sx 'ga #t))", &known);
+        assert_eq!(problems[0].message, "unmatched closing paren");
+        assert_eq!(problems[0].start, 11);
+    }
+
+    #[test]
+    fn test_enclosing_function() {
+        let tokens = tokenize("(sx 'ga #t (nuc 'a (saw 100)) (infer 'b ");
+        assert_eq!(enclosing_function(&tokens), Some("infer"));
+        let tokens = tokenize("(sx 'ga #t (nuc 'a (saw 100)))");
+        assert_eq!(enclosing_function(&tokens), None);
+        let tokens = tokenize("(sx 'ga #t (");
+        assert_eq!(enclosing_function(&tokens), None);
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "(sx 'é\n  (nuc";
+        let pos = offset_to_position(text, text.find("nuc").unwrap());
+        assert_eq!(pos, Position::new(1, 3));
+        assert_eq!(position_to_offset(text, pos), text.find("nuc").unwrap());
+        // é is one utf-16 unit, but two bytes
+        assert_eq!(offset_to_position(text, 7), Position::new(0, 6));
+    }
+
+    #[test]
+    fn test_completion_hover_definition() {
+        let server = LanguageServer::new(None);
+
+        let text = format!("{SKETCH}(sa");
+        let end = offset_to_position(&text, text.len());
+        let items = server.completion(&text, end);
+        assert!(items.iter().any(|i| i.label == "saw"));
+        assert!(items.iter().any(|i| i.label == "sample-number"));
+        assert!(!items.iter().any(|i| i.label == "nuc"));
+
+        let text = "(saw 100 :lp";
+        let items = server.completion(text, Position::new(0, 12));
+        assert!(items.iter().any(|i| i.label == "lpf"));
+        // no label twice, even if the lists overlap
+        let text = "(fun saw () (saw 100)) (sa";
+        let items = server.completion(text, Position::new(0, 26));
+        let labels: HashSet<&String> = items.iter().map(|i| &i.label).collect();
+        assert_eq!(labels.len(), items.len());
+        assert!(labels.contains(&"saw".to_string()));
+        let items = server.completion("(x :", Position::new(0, 4));
+        let labels: HashSet<&String> = items.iter().map(|i| &i.label).collect();
+        assert_eq!(labels.len(), items.len());
+        // generators have their own keywords
+        let text = "(sx 'ga #t (infer 'a (saw 100) :ev";
+        let items = server.completion(text, Position::new(0, 34));
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "events");
+
+        let beat = offset_to_position(SKETCH, SKETCH.rfind("beat").unwrap());
+        assert_eq!(
+            server.hover(SKETCH, beat),
+            Some("`(beat a b)` user-defined function".to_string())
+        );
+        let def = server.definition(SKETCH, beat).unwrap();
+        assert_eq!(def.start, Position::new(1, 5));
+
+        let sx = offset_to_position(SKETCH, SKETCH.find("sx").unwrap());
+        assert!(server.hover(SKETCH, sx).unwrap().contains("sync context"));
+    }
+}
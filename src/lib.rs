@@ -0,0 +1,51 @@
+// take care of these later ...
+#![allow(clippy::new_without_default)]
+#![allow(clippy::needless_lifetimes)]
+#![allow(clippy::large_enum_variant)]
+#![allow(clippy::type_complexity)]
+
+pub mod builtin_types;
+pub mod clock;
+pub mod commands;
+pub mod cyc_parser;
+pub mod editor;
+pub mod eval_server;
+pub mod event;
+pub mod event_helpers;
+pub mod file_interpreter;
+pub mod generator;
+pub mod generator_processor;
+pub mod interpreter;
+pub mod language_server;
+pub mod load_audio_file;
+pub mod markov_sequence_generator;
+pub mod midi_clock;
+pub mod midi_input;
+pub mod midi_output;
+pub mod modules;
+pub mod music_theory;
+pub mod offline_rendering;
+pub mod osc_client;
+pub mod parameter;
+pub mod parser;
+pub mod pfa_growth;
+pub mod pfa_reverse;
+pub mod real_time_streaming;
+pub mod repl;
+pub mod sample_set;
+pub mod scheduler;
+pub mod session;
+pub mod synth_parameter_value_arithmetic;
+pub mod tempo_sync;
+
+#[rustfmt::skip]
+pub mod standard_library;
+
+mod osc_receiver;
+mod osc_sender;
+mod visualizer_client;
+
+// these are used all over the place
+use crate::builtin_types::*;
+use crate::sample_set::SampleAndWavematrixSet;
+use crate::session::{OutputMode, Session};
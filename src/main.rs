@@ -1,57 +1,31 @@
 // take care of these later ...
 #![allow(clippy::new_without_default)]
-#![allow(clippy::needless_lifetimes)]
-#![allow(clippy::large_enum_variant)]
 #![allow(clippy::type_complexity)]
 
-pub mod builtin_types;
-pub mod commands;
-pub mod cyc_parser;
-pub mod editor;
-pub mod event;
-pub mod event_helpers;
-pub mod file_interpreter;
-pub mod generator;
-pub mod generator_processor;
-pub mod interpreter;
-pub mod load_audio_file;
-pub mod markov_sequence_generator;
-pub mod midi_input;
-pub mod music_theory;
-pub mod osc_client;
-pub mod parameter;
-pub mod parser;
-pub mod pfa_growth;
-pub mod pfa_reverse;
-pub mod real_time_streaming;
-pub mod repl;
-pub mod sample_set;
-pub mod scheduler;
-pub mod session;
-pub mod synth_parameter_value_arithmetic;
-
-#[rustfmt::skip]
-mod standard_library;
-
-mod osc_receiver;
-mod osc_sender;
-mod visualizer_client;
-
-use crate::builtin_types::*;
-use crate::osc_client::OscClient;
-use crate::sample_set::SampleAndWavematrixSet;
-use crate::session::{OutputMode, Session};
 use anyhow::anyhow;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Stream, StreamConfig};
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use directories_next::ProjectDirs;
 use getopts::Options;
-use parking_lot::Mutex;
+use megra_rs::builtin_types::*;
+use megra_rs::clock::{AudioClock, ManualClock, SchedulerClock, WallClock};
+use megra_rs::eval_server::ServerAddress;
+use megra_rs::midi_output::MidiOutputs;
+use megra_rs::modules::Modules;
+use megra_rs::osc_client::OscClient;
+use megra_rs::sample_set::SampleAndWavematrixSet;
+use megra_rs::session::{OutputMode, Session};
+use megra_rs::standard_library::define_standard_library;
+use megra_rs::{
+    commands, editor, eval_server, file_interpreter, offline_rendering, parser,
+    real_time_streaming, repl, session,
+};
+use parking_lot::{Mutex, RwLock};
 use real_time_streaming::Throw;
 use ruffbox_synth::ruffbox::{init_ruffbox, ReverbMode, RuffboxPlayhead};
-use standard_library::define_standard_library;
 
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{env, sync, thread};
 
@@ -104,6 +78,15 @@ struct RunOptions {
     downmix_stereo: bool,
     ambisonic_binaural: bool,
     karl_yerkes_mode: bool,
+    audio_clock: bool,
+    eval_server: Option<ServerAddress>,
+}
+
+struct RenderOptions {
+    sketch: String,
+    out_file: String,
+    duration: f64,
+    sample_rate: f32,
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -155,7 +138,7 @@ fn main() -> Result<(), anyhow::Error> {
     opts.optopt(
         "",
         "base",
-        "base folder including samples, a sketchbook, and a recordings folder",
+        "base folder including samples, a sketchbook, a lib and a recordings folder",
         "",
     );
 
@@ -182,6 +165,40 @@ fn main() -> Result<(), anyhow::Error> {
 
     opts.optopt("", "font-size", "editor font size", "15.0");
 
+    opts.optopt(
+        "",
+        "eval-server",
+        "evaluate code sent by other editors (JSON lines) via tcp or unix socket",
+        "127.0.0.1:57400 or unix:/tmp/megra.sock",
+    );
+
+    opts.optopt(
+        "",
+        "clock",
+        "the clock the schedulers follow (wall or audio)",
+        "wall",
+    );
+
+    opts.optopt(
+        "",
+        "render",
+        "render a sketch file to a wave file, faster than realtime (no sound card needed)",
+        "",
+    );
+    opts.optopt(
+        "",
+        "render-out",
+        "output file for --render (default: sketch file with .wav extension)",
+        "",
+    );
+    opts.optopt(
+        "",
+        "duration",
+        "duration of the --render output in seconds",
+        "10.0",
+    );
+    opts.optopt("", "samplerate", "samplerate for --render", "44100");
+
     let matches = match opts.parse(argv) {
         Ok(m) => m,
         Err(e) => {
@@ -291,8 +308,83 @@ fn main() -> Result<(), anyhow::Error> {
         15.0
     };
 
+    let audio_clock = match matches.opt_str("clock").as_deref() {
+        Some("audio") => true,
+        Some("wall") | None => false,
+        _ => {
+            println!("invalid clock, assume wall clock");
+            false
+        }
+    };
+
+    let eval_server = match matches.opt_str("eval-server").map(|s| s.parse()) {
+        Some(Ok(addr)) => Some(addr),
+        Some(Err(e)) => {
+            println!("invalid eval server address: {e}");
+            None
+        }
+        None => None,
+    };
+
     println!("using a live buffer time of: {live_buffer_time}");
 
+    let run_opts = RunOptions {
+        mode: out_mode,
+        num_live_buffers: num_live_buffers as usize,
+        live_buffer_time,
+        max_sample_buffers,
+        editor,
+        create_sketch,
+        load_samples,
+        sample_folder: matches.opt_str("sample-folder"),
+        base_folder: matches.opt_str("base"),
+        reverb_mode,
+        font: matches.opt_str("font"),
+        font_size,
+        downmix_stereo,
+        ambisonic_binaural,
+        karl_yerkes_mode,
+        audio_clock,
+        eval_server,
+    };
+
+    if let Some(sketch) = matches.opt_str("render") {
+        let duration: f64 = matches
+            .opt_str("duration")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10.0);
+
+        let sample_rate: f32 = matches
+            .opt_str("samplerate")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(44100.0);
+
+        let out_file = if let Some(f) = matches.opt_str("render-out") {
+            f
+        } else {
+            PathBuf::from(&sketch)
+                .with_extension("wav")
+                .display()
+                .to_string()
+        };
+
+        let render_opts = RenderOptions {
+            sketch,
+            out_file,
+            duration,
+            sample_rate,
+        };
+
+        match out_mode {
+            OutputMode::Stereo => render::<2>(run_opts, render_opts)?,
+            OutputMode::FourChannel => render::<4>(run_opts, render_opts)?,
+            OutputMode::EightChannel => render::<8>(run_opts, render_opts)?,
+            OutputMode::SixteenChannel => render::<16>(run_opts, render_opts)?,
+        }
+
+        return Ok(());
+    }
+
     #[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
     let host = cpal::host_from_id(cpal::available_hosts()
 				  .into_iter()
@@ -334,24 +426,6 @@ fn main() -> Result<(), anyhow::Error> {
             .find(|x| x.name().map(|y| y == out_device).unwrap_or(false))
     };
 
-    let run_opts = RunOptions {
-        mode: out_mode,
-        num_live_buffers: num_live_buffers as usize,
-        live_buffer_time,
-        max_sample_buffers,
-        editor,
-        create_sketch,
-        load_samples,
-        sample_folder: matches.opt_str("sample-folder"),
-        base_folder: matches.opt_str("base"),
-        reverb_mode,
-        font: matches.opt_str("font"),
-        font_size,
-        downmix_stereo,
-        ambisonic_binaural,
-        karl_yerkes_mode,
-    };
-
     match out_mode {
         OutputMode::Stereo => run::<2>(input_device, output_device, run_opts)?,
         OutputMode::FourChannel => run::<4>(input_device, output_device, run_opts)?,
@@ -490,6 +564,7 @@ fn run_output<const NCHAN: usize>(
     playhead_out: sync::Arc<Mutex<RuffboxPlayhead<BLOCKSIZE, NCHAN>>>,
     is_recording_output: sync::Arc<AtomicBool>,
     throw_out: Throw<BLOCKSIZE, NCHAN>,
+    throw_monitor: Throw<BLOCKSIZE, NCHAN>,
 ) -> Result<Stream, anyhow::Error> {
     let mut out_config: StreamConfig = output_device.default_output_config()?.into();
     out_config.channels = NCHAN as u16;
@@ -517,6 +592,9 @@ fn run_output<const NCHAN: usize>(
                 throw_out.write_samples(&ruff_out, BLOCKSIZE);
             }
 
+            // for the meters, only if someone's watching
+            throw_monitor.write_samples(&ruff_out, BLOCKSIZE);
+
             // there might be a faster way to de-interleave here ...
             for (frame_count, frame) in data.chunks_mut(NCHAN).enumerate() {
                 for ch in 0..NCHAN {
@@ -575,6 +653,8 @@ fn run_output<const NCHAN: usize>(
                         throw_out.write_samples(&ruff_out, BLOCKSIZE);
                     }
 
+                    throw_monitor.write_samples(&ruff_out, BLOCKSIZE);
+
                     //produced += BLOCKSIZE;
                     for ch in 0..NCHAN {
                         let mut tmp_write_idx = write_idx;
@@ -673,6 +753,12 @@ fn run<const NCHAN: usize>(
         0.25,
     );
 
+    // OUTPUT MONITOR (meters etc.)
+    let (throw_monitor, catch_monitor) = real_time_streaming::init_real_time_stream::<
+        BLOCKSIZE,
+        NCHAN,
+    >((BLOCKSIZE_FLOAT / sample_rate) as f64, 0.1);
+
     let is_recording_output = sync::Arc::new(AtomicBool::new(false));
     let is_recording_input = sync::Arc::new(AtomicBool::new(false));
 
@@ -704,16 +790,36 @@ fn run<const NCHAN: usize>(
     }
 
     let out_stream = if let Some(out_dev) = output_device {
-        run_output(&out_dev, playhead_out, is_recording_output, throw_out)
+        run_output(
+            &out_dev,
+            playhead_out,
+            is_recording_output,
+            throw_out,
+            throw_monitor,
+        )
     } else {
         Err(anyhow!("can't start output stream"))
     };
 
-    if out_stream.is_ok() {
+    let monitor = if out_stream.is_ok() {
         println!("[OUTPUT] output started!");
+        Some(real_time_streaming::OutputMonitor {
+            catch: catch_monitor,
+            samplerate: sample_rate as u32,
+        })
     } else {
         eprintln!("[OUTPUT] error starting output!");
-    }
+        None
+    };
+
+    let ruffbox = sync::Arc::new(controls);
+
+    let clock: sync::Arc<dyn SchedulerClock> = if options.audio_clock {
+        println!("schedulers follow the audio clock");
+        sync::Arc::new(AudioClock::new(sync::Arc::clone(&ruffbox)))
+    } else {
+        sync::Arc::new(WallClock::new())
+    };
 
     // global data
     let session = Session {
@@ -721,17 +827,169 @@ fn run<const NCHAN: usize>(
         contexts: sync::Arc::new(DashMap::new()),
         osc_client: OscClient::new(),
         rec_control: sync::Arc::new(Mutex::new(Some(rec_control))),
+        monitor: sync::Arc::new(Mutex::new(monitor)),
+        globals: sync::Arc::new(GlobalVariables::new()),
+        sample_set: SampleAndWavematrixSet::new(),
+        ruffbox,
+        output_mode: options.mode,
+        sync_mode: session::SyncMode::NotOnSilence,
+        clock,
+        tempo_sync: sync::Arc::new(RwLock::new(None)),
+        midi_clock: sync::Arc::new(Mutex::new(None)),
+        midi_clock_follower: sync::Arc::new(Mutex::new(None)),
+        midi_out: MidiOutputs::new(),
+        muted: sync::Arc::new(DashSet::new()),
+        soloed: sync::Arc::new(DashSet::new()),
+        modules: sync::Arc::new(Mutex::new(Modules::new())),
+    };
+
+    // define the "standard library"
+    let stdlib = sync::Arc::new(Mutex::new(define_standard_library()));
+
+    let (base_dir, samples_path) = prepare_base_dir(&options)?;
+
+    load_init_file(&base_dir, &stdlib, &session);
+
+    // load the default sample set ...
+    if options.load_samples {
+        println!("load samples from path: {samples_path:?}");
+        let controls_arc2 = sync::Arc::clone(&session.ruffbox);
+        let stdlib2 = sync::Arc::clone(&stdlib);
+        let sample_set2 = session.sample_set.clone();
+        thread::spawn(move || {
+            commands::load_sample_sets_path(
+                &stdlib2,
+                &controls_arc2,
+                sample_set2,
+                &samples_path,
+                options.downmix_stereo,
+            );
+            println!("a command (load default sample sets)");
+        });
+    }
+
+    if let Some(addr) = options.eval_server.as_ref() {
+        if let Err(e) = eval_server::start_eval_server(
+            addr,
+            &stdlib,
+            session.clone(),
+            base_dir.display().to_string(),
+        ) {
+            println!("can't start eval server: {e}");
+        }
+    }
+
+    let modules = sync::Arc::clone(&session.modules);
+    let res = if options.editor {
+        editor::run_editor(
+            &stdlib,
+            session,
+            base_dir.display().to_string(),
+            options.create_sketch,
+            options.font.as_deref(),
+            options.font_size,
+            options.karl_yerkes_mode,
+        )
+        .unwrap();
+        Ok(())
+    } else {
+        // start the megra repl
+        repl::start_repl(&stdlib, session, base_dir.display().to_string())
+    };
+    modules.lock().stop_watching();
+    res
+}
+
+/// render a sketch file offline, using a virtual clock instead
+/// of the sound card ...
+fn render<const NCHAN: usize>(
+    options: RunOptions,
+    render_options: RenderOptions,
+) -> Result<(), anyhow::Error> {
+    let (controls, mut playhead) = init_ruffbox::<BLOCKSIZE, NCHAN>(
+        options.num_live_buffers,
+        options.live_buffer_time.into(),
+        &options.reverb_mode,
+        render_options.sample_rate.into(),
+        options.max_sample_buffers,
+        10,
+        options.ambisonic_binaural,
+    );
+
+    let clock = sync::Arc::new(ManualClock::new());
+
+    // global data
+    let session = Session {
+        schedulers: sync::Arc::new(DashMap::new()),
+        contexts: sync::Arc::new(DashMap::new()),
+        osc_client: OscClient::new(),
+        rec_control: sync::Arc::new(Mutex::new(None)),
+        monitor: sync::Arc::new(Mutex::new(None)),
         globals: sync::Arc::new(GlobalVariables::new()),
         sample_set: SampleAndWavematrixSet::new(),
         ruffbox: sync::Arc::new(controls),
         output_mode: options.mode,
         sync_mode: session::SyncMode::NotOnSilence,
+        clock: sync::Arc::clone(&clock) as sync::Arc<dyn SchedulerClock>,
+        tempo_sync: sync::Arc::new(RwLock::new(None)),
+        midi_clock: sync::Arc::new(Mutex::new(None)),
+        midi_clock_follower: sync::Arc::new(Mutex::new(None)),
+        midi_out: MidiOutputs::new(),
+        muted: sync::Arc::new(DashSet::new()),
+        soloed: sync::Arc::new(DashSet::new()),
+        modules: sync::Arc::new(Mutex::new(Modules::new())),
     };
 
     // define the "standard library"
     let stdlib = sync::Arc::new(Mutex::new(define_standard_library()));
 
-    let base_dir = if let Some(p) = options.base_folder {
+    let (base_dir, samples_path) = prepare_base_dir(&options)?;
+
+    load_init_file(&base_dir, &stdlib, &session);
+
+    // samples need to be there before the rendering starts,
+    // so they're not loaded in the background here
+    if options.load_samples {
+        println!("load samples from path: {samples_path:?}");
+        commands::load_sample_sets_path(
+            &stdlib,
+            &session.ruffbox,
+            session.sample_set.clone(),
+            &samples_path,
+            options.downmix_stereo,
+        );
+    }
+
+    println!(
+        "render {} to {} ({} seconds)",
+        render_options.sketch, render_options.out_file, render_options.duration
+    );
+
+    file_interpreter::parse_file(
+        render_options.sketch,
+        &stdlib,
+        &session,
+        base_dir.display().to_string(),
+    );
+
+    offline_rendering::render_to_file(
+        &mut playhead,
+        &clock,
+        render_options.duration,
+        render_options.sample_rate as u32,
+        &render_options.out_file,
+    )?;
+
+    session.modules.lock().stop_watching();
+    println!("rendering finished");
+
+    Ok(())
+}
+
+/// create the base folder and its sub-folders if necessary,
+/// returns the base folder and the samples folder
+fn prepare_base_dir(options: &RunOptions) -> Result<(PathBuf, PathBuf), anyhow::Error> {
+    let base_dir = if let Some(p) = options.base_folder.as_ref() {
         let bd = std::path::PathBuf::from(p);
         if !bd.exists() {
             println!("create custom megra resource directory {bd:?}");
@@ -759,7 +1017,7 @@ fn run<const NCHAN: usize>(
 
     println!("base dir is: {base_dir:?}");
 
-    let samples_path = if let Some(folder) = options.sample_folder {
+    let samples_path = if let Some(folder) = options.sample_folder.as_ref() {
         std::path::PathBuf::from(folder)
     } else {
         base_dir.join("samples")
@@ -776,13 +1034,28 @@ fn run<const NCHAN: usize>(
         std::fs::create_dir_all(sketchbook_path.to_str().unwrap())?;
     }
 
+    let lib_path = base_dir.join("lib");
+    if !lib_path.exists() {
+        println!("create megra lib directory {lib_path:?}");
+        std::fs::create_dir_all(lib_path.to_str().unwrap())?;
+    }
+
     let recordings_path = base_dir.join("recordings");
     if !recordings_path.exists() {
         println!("create megra recordings directory {recordings_path:?}");
         std::fs::create_dir_all(recordings_path.to_str().unwrap())?;
     }
 
-    let init_file_path = sketchbook_path.join("init.megra3");
+    Ok((base_dir, samples_path))
+}
+
+/// load the startup file from the sketchbook, if there is one
+fn load_init_file<const NCHAN: usize>(
+    base_dir: &std::path::Path,
+    stdlib: &sync::Arc<Mutex<parser::FunctionMap>>,
+    session: &Session<BLOCKSIZE, NCHAN>,
+) {
+    let init_file_path = base_dir.join("sketchbook").join("init.megra3");
     if !init_file_path.exists() {
         println!("no startup file found");
     } else {
@@ -790,44 +1063,9 @@ fn run<const NCHAN: usize>(
         println!("loading init file {init_path_string}");
         file_interpreter::parse_file(
             init_path_string,
-            &stdlib,
-            &session,
+            stdlib,
+            session,
             base_dir.to_str().unwrap().to_string(),
         );
-    };
-
-    // load the default sample set ...
-    if options.load_samples {
-        println!("load samples from path: {samples_path:?}");
-        let controls_arc2 = sync::Arc::clone(&session.ruffbox);
-        let stdlib2 = sync::Arc::clone(&stdlib);
-        let sample_set2 = session.sample_set.clone();
-        thread::spawn(move || {
-            commands::load_sample_sets_path(
-                &stdlib2,
-                &controls_arc2,
-                sample_set2,
-                &samples_path,
-                options.downmix_stereo,
-            );
-            println!("a command (load default sample sets)");
-        });
-    }
-
-    if options.editor {
-        editor::run_editor(
-            &stdlib,
-            session,
-            base_dir.display().to_string(),
-            options.create_sketch,
-            options.font.as_deref(),
-            options.font_size,
-            options.karl_yerkes_mode,
-        )
-        .unwrap();
-        Ok(())
-    } else {
-        // start the megra repl
-        repl::start_repl(&stdlib, session, base_dir.display().to_string())
     }
 }
@@ -0,0 +1,272 @@
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput};
+
+use std::collections::VecDeque;
+use std::sync;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::builtin_types::{ConfigParameter, GlobalVariables, TypedEntity, VariableId};
+use crate::clock::SchedulerClock;
+use crate::commands;
+use crate::parameter::DynVal;
+
+// midi realtime messages
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+
+// midi clock runs at 24 pulses per quarter note
+const PPQN: f64 = 24.0;
+
+// number of clock intervals to average over when following
+// (one beat)
+const FOLLOW_WINDOW: usize = 24;
+
+/// the default duration in seconds
+fn default_duration(globals: &sync::Arc<GlobalVariables>) -> f64 {
+    if let Some(thing) = globals.get(&VariableId::DefaultDuration) {
+        if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(d)) = thing.value() {
+            return *d as f64 * 0.001;
+        }
+    }
+    0.2
+}
+
+fn global_tmod(globals: &sync::Arc<GlobalVariables>) -> f64 {
+    if let Some(thing) = globals.get(&VariableId::GlobalTimeModifier) {
+        if let TypedEntity::ConfigParameter(ConfigParameter::Dynamic(d)) = thing.value() {
+            return d.static_val as f64;
+        }
+    }
+    1.0
+}
+
+/// The duration of one beat in seconds, as it's currently played.
+/// That's the default duration, modified by the global time modifier.
+pub fn beat_duration(globals: &sync::Arc<GlobalVariables>) -> f64 {
+    default_duration(globals) * global_tmod(globals)
+}
+
+pub fn list_midi_output_ports() {
+    if let Ok(midi_out) = MidiOutput::new("midir output") {
+        println!("\nAvailable output ports:");
+        for (i, p) in midi_out.ports().iter().enumerate() {
+            println!("{}: {}", i, midi_out.port_name(p).unwrap_or_default());
+        }
+    }
+}
+
+/// Sends midi clock to an output port, following the current tempo.
+pub struct MidiClockOutput {
+    running: sync::Arc<AtomicBool>,
+}
+
+impl MidiClockOutput {
+    /// Send start, then clock pulses until stopped. The pulses follow the
+    /// session clock and are delayed by the global latency, like the sound.
+    /// Start is sent after `phase` seconds (plus latency), so it can be
+    /// put on the next bar the generators start on.
+    pub fn start(
+        out_port_num: usize,
+        globals: &sync::Arc<GlobalVariables>,
+        clock: sync::Arc<dyn SchedulerClock>,
+        phase: f64,
+    ) -> Result<MidiClockOutput, anyhow::Error> {
+        let midi_out = MidiOutput::new("megra midi clock")?;
+        let out_ports = midi_out.ports();
+        let out_port = out_ports
+            .get(out_port_num)
+            .ok_or_else(|| anyhow::anyhow!("invalid output port selected"))?;
+
+        println!(
+            "send midi clock to '{}'",
+            midi_out.port_name(out_port).unwrap_or_default()
+        );
+
+        let mut conn_out = midi_out
+            .connect(out_port, "megra-midi-clock")
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        let running = sync::Arc::new(AtomicBool::new(true));
+        let running2 = sync::Arc::clone(&running);
+        let globals2 = sync::Arc::clone(globals);
+
+        // like a scheduler, a virtual clock must not move on
+        // without this thread
+        clock.enter();
+        let clock2 = sync::Arc::clone(&clock);
+
+        let spawned = std::thread::Builder::new()
+            .name("midi clock".to_string())
+            .spawn(move || {
+                // the logical time of the next pulse, which is heard
+                // a latency later ...
+                let mut next = clock2.now() + phase;
+                clock2.wait_until(next + commands::global_latency(&globals2), &running2);
+                let _ = conn_out.send(&[START]);
+
+                // the next pulse is always calculated from the last one,
+                // not from the time we woke up, so there's no drift
+                while running2.load(Ordering::SeqCst) {
+                    let _ = conn_out.send(&[CLOCK]);
+                    next += beat_duration(&globals2) / PPQN;
+                    clock2.wait_until(next + commands::global_latency(&globals2), &running2);
+                }
+
+                let _ = conn_out.send(&[STOP]);
+                conn_out.close();
+                clock2.leave();
+            });
+
+        if let Err(e) = spawned {
+            clock.leave();
+            return Err(e.into());
+        }
+
+        Ok(MidiClockOutput { running })
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Estimates the tempo from incoming midi clock pulses.
+pub struct MidiClockFollower {
+    last_pulse: Option<f64>,
+    intervals: VecDeque<f64>,
+}
+
+impl Default for MidiClockFollower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiClockFollower {
+    pub fn new() -> Self {
+        MidiClockFollower {
+            last_pulse: None,
+            intervals: VecDeque::new(),
+        }
+    }
+
+    /// forget the past pulses, i.e. on start/stop
+    pub fn reset(&mut self) {
+        self.last_pulse = None;
+        self.intervals.clear();
+    }
+
+    /// Register a clock pulse at the given time (in seconds), returns
+    /// the estimated beat duration once there's enough pulses.
+    pub fn pulse(&mut self, time: f64) -> Option<f64> {
+        if let Some(last) = self.last_pulse {
+            self.intervals.push_back(time - last);
+            if self.intervals.len() > FOLLOW_WINDOW {
+                self.intervals.pop_front();
+            }
+        }
+        self.last_pulse = Some(time);
+
+        if self.intervals.len() < FOLLOW_WINDOW {
+            return None;
+        }
+
+        let avg = self.intervals.iter().sum::<f64>() / self.intervals.len() as f64;
+        Some(avg * PPQN)
+    }
+}
+
+/// Follow the midi clock coming in on the given port, by adjusting
+/// the global time modifier, so that the default duration matches
+/// one beat of the incoming clock. The clock is followed as long as
+/// the connection is kept.
+pub fn follow_midi_clock(
+    in_port_num: usize,
+    globals: sync::Arc<GlobalVariables>,
+) -> Result<MidiInputConnection<()>, anyhow::Error> {
+    let mut midi_in = MidiInput::new("megra midi clock input")?;
+    midi_in.ignore(Ignore::None);
+
+    let in_ports = midi_in.ports();
+    let in_port = in_ports
+        .get(in_port_num)
+        .ok_or_else(|| anyhow::anyhow!("invalid input port selected"))?
+        .clone();
+
+    let in_port_name = midi_in.port_name(&in_port).unwrap_or_default();
+
+    let mut follower = MidiClockFollower::new();
+
+    let conn_in = midi_in
+        .connect(
+            &in_port,
+            "megra-midi-clock-input",
+            move |stamp, message, _| match message.first() {
+                Some(&CLOCK) => {
+                    // timestamps are in microseconds
+                    if let Some(beat_dur) = follower.pulse(stamp as f64 * 0.000001) {
+                        commands::set_global_tmod(
+                            &globals,
+                            DynVal::with_value((beat_dur / default_duration(&globals)) as f32),
+                        );
+                    }
+                }
+                Some(&START) | Some(&CONTINUE) | Some(&STOP) => {
+                    follower.reset();
+                }
+                _ => {}
+            },
+            (),
+        )
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    println!("following midi clock from '{in_port_name}' ...");
+
+    Ok(conn_in)
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beat_duration() {
+        let globals = sync::Arc::new(GlobalVariables::new());
+        assert!((beat_duration(&globals) - 0.2).abs() < 0.00001);
+
+        // 120 bpm, half tempo
+        commands::set_default_duration(&globals, 500.0);
+        commands::set_global_tmod(&globals, DynVal::with_value(2.0));
+        assert!((beat_duration(&globals) - 1.0).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_follow_clock() {
+        let mut follower = MidiClockFollower::new();
+
+        // 120 bpm, a beat every 500ms
+        let pulse_dur = 0.5 / 24.0;
+        let mut beat_dur = None;
+        for i in 0..25 {
+            beat_dur = follower.pulse(i as f64 * pulse_dur);
+            if i < 24 {
+                assert!(beat_dur.is_none());
+            }
+        }
+        assert!((beat_dur.unwrap() - 0.5).abs() < 0.00001);
+
+        // speed up to 150 bpm
+        let mut time = 24.0 * pulse_dur;
+        let pulse_dur = 0.4 / 24.0;
+        for _ in 0..24 {
+            time += pulse_dur;
+            beat_dur = follower.pulse(time);
+        }
+        assert!((beat_dur.unwrap() - 0.4).abs() < 0.00001);
+
+        follower.reset();
+        assert!(follower.pulse(time).is_none());
+    }
+}
@@ -2,11 +2,11 @@ use midir::{Ignore, MidiInput};
 
 use parking_lot::Mutex;
 
-use std::collections::HashMap;
 use std::sync;
 
 use crate::builtin_types::Comparable;
-use crate::parser::{eval_expression, EvaluatedExpr, FunctionMap};
+use crate::parser::eval::function;
+use crate::parser::{EvaluatedExpr, FunctionMap};
 use crate::{interpreter, Session};
 
 pub fn list_midi_input_ports() {
@@ -45,52 +45,37 @@ pub fn open_midi_input_port<const BUFSIZE: usize, const NCHAN: usize>(
             move |_, message, _| {
                 let functions = function_map.lock();
 
-                if functions.usr_lib.contains_key("midi") {
-                    let (fun_arg_names, fun_expr) = functions.usr_lib.get("midi").unwrap().clone();
-
-                    // FIRST, eval local args,
-                    // manual zip
-                    let mut local_args = HashMap::new();
-                    for (i, val) in fun_arg_names.iter().enumerate() {
-                        if i < message.len() {
-                            local_args.insert(
-                                val.clone(),
-                                EvaluatedExpr::Typed(
-                                    crate::builtin_types::TypedEntity::Comparable(
-                                        Comparable::Float(message[i] as f32),
-                                    ),
-                                ),
-                            );
-                        }
-                        //else {
-                        //  println!("no midi arg available for pos arg {val}");
-                        // }
-                    }
-
-                    // THIRD
-                    if let Some(fun_tail) = fun_expr
+                if let Some(fun) = functions.usr_lib.get("midi") {
+                    let args = message
                         .iter()
-                        .map(|expr| {
-                            eval_expression(
-                                expr,
-                                &functions,
-                                &session.globals,
-                                Some(&local_args),
-                                session.sample_set.clone(),
-                                session.output_mode,
-                            )
+                        .map(|byte| {
+                            EvaluatedExpr::Typed(crate::builtin_types::TypedEntity::Comparable(
+                                Comparable::Float(*byte as f32),
+                            ))
                         })
-                        .collect::<Option<Vec<EvaluatedExpr>>>()
-                    {
-                        // return last form result, cl-style
-                        for eval_expr in fun_tail {
-                            interpreter::interpret(
-                                eval_expr,
-                                &function_map,
-                                session.clone(),
-                                base_dir.clone(),
-                            );
+                        .collect();
+
+                    match function::call(
+                        fun,
+                        "midi",
+                        args,
+                        &functions,
+                        &session.globals,
+                        session.sample_set.clone(),
+                        session.output_mode,
+                    ) {
+                        Ok(fun_tail) => {
+                            // return last form result, cl-style
+                            for eval_expr in fun_tail {
+                                interpreter::interpret(
+                                    eval_expr,
+                                    &function_map,
+                                    session.clone(),
+                                    base_dir.clone(),
+                                );
+                            }
                         }
+                        Err(e) => println!("midi: can't evaluate midi function: {e}"),
                     }
                 }
             },
@@ -0,0 +1,322 @@
+use crossbeam::atomic::AtomicCell;
+use crossbeam::channel::{unbounded, RecvTimeoutError, Sender};
+use dashmap::DashMap;
+use midir::MidiOutput;
+
+use std::collections::BTreeSet;
+use std::sync;
+use std::time::Duration;
+
+use ruffbox_synth::building_blocks::{SynthParameterLabel, SynthParameterValue};
+
+use crate::clock::SchedulerClock;
+use crate::event::StaticEvent;
+
+const NOTE_ON: u8 = 0x90;
+const NOTE_OFF: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MidiNote {
+    pub channel: u8, // 1 to 16
+    pub note: u8,
+    pub velocity: u8,
+    pub duration: f64, // seconds
+}
+
+fn get_param(ev: &StaticEvent, label: SynthParameterLabel) -> Option<f32> {
+    if let Some(SynthParameterValue::ScalarF32(v)) = ev.params.get(&label.into()) {
+        Some(*v)
+    } else {
+        None
+    }
+}
+
+/// Translate a sound event into a midi note. The note is either given
+/// directly or derived from the frequency, the velocity is derived from
+/// the level (midi-note events keep the velocity as level). The length
+/// of the note is the event duration, or the length of the envelope if
+/// there's no duration.
+pub fn event_to_midi_note(ev: &StaticEvent, channel: u8) -> MidiNote {
+    let note = if let Some(n) = get_param(ev, SynthParameterLabel::PitchNote) {
+        n
+    } else if let Some(f) = get_param(ev, SynthParameterLabel::PitchFrequency) {
+        69.0 + 12.0 * (f / 440.0).log2()
+    } else {
+        60.0
+    };
+
+    let velocity = if let Some(lvl) = get_param(ev, SynthParameterLabel::EnvelopeLevel) {
+        if ev.name == "midi-note" {
+            lvl
+        } else {
+            lvl * 127.0
+        }
+    } else {
+        100.0
+    };
+
+    let duration = if let Some(d) = get_param(ev, SynthParameterLabel::Duration) {
+        d
+    } else {
+        get_param(ev, SynthParameterLabel::Attack).unwrap_or(0.0)
+            + get_param(ev, SynthParameterLabel::Sustain).unwrap_or(200.0)
+            + get_param(ev, SynthParameterLabel::Release).unwrap_or(0.0)
+    };
+
+    MidiNote {
+        channel: channel.clamp(1, 16),
+        note: note.round().clamp(0.0, 127.0) as u8,
+        // velocity zero would be a note off
+        velocity: velocity.round().clamp(1.0, 127.0) as u8,
+        duration: duration as f64 * 0.001,
+    }
+}
+
+/// Remove the messages that are due from the list, in the order
+/// they should be sent (note offs first if they're simultaneous).
+fn take_due_messages(pending: &mut Vec<(f64, [u8; 3])>, now: f64) -> Vec<[u8; 3]> {
+    let mut due: Vec<(f64, [u8; 3])> = Vec::new();
+    pending.retain(|m| {
+        if m.0 <= now {
+            due.push(*m);
+            false
+        } else {
+            true
+        }
+    });
+    due.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1[0].cmp(&b.1[0])));
+    due.into_iter().map(|(_, msg)| msg).collect()
+}
+
+/// An open midi output port. Messages are sent from a separate thread
+/// once they're due. As the time follows the audio stream, the midi
+/// timing is as precise as one audio block.
+pub struct MidiOutputSink {
+    queue: Sender<(f64, [u8; 3])>,
+}
+
+impl MidiOutputSink {
+    pub fn open(
+        out_port_num: usize,
+        clock: sync::Arc<dyn SchedulerClock>,
+    ) -> Result<MidiOutputSink, anyhow::Error> {
+        let midi_out = MidiOutput::new("megra midi output")?;
+        let out_ports = midi_out.ports();
+        let out_port = out_ports
+            .get(out_port_num)
+            .ok_or_else(|| anyhow::anyhow!("invalid output port selected"))?;
+
+        println!(
+            "send midi notes to '{}'",
+            midi_out.port_name(out_port).unwrap_or_default()
+        );
+
+        let mut conn_out = midi_out
+            .connect(out_port, "megra-midi-out")
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        let (tx, rx) = unbounded();
+
+        std::thread::Builder::new()
+            .name("midi output".to_string())
+            .spawn(move || {
+                let mut pending: Vec<(f64, [u8; 3])> = Vec::new();
+                loop {
+                    let now = clock.now();
+                    for msg in take_due_messages(&mut pending, now) {
+                        let _ = conn_out.send(&msg);
+                    }
+
+                    let wait = pending.iter().map(|(t, _)| t - now).fold(0.1, f64::min);
+
+                    match rx.recv_timeout(Duration::from_secs_f64(wait)) {
+                        Ok(msg) => pending.push(msg),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+
+                // no hanging notes
+                for msg in take_due_messages(&mut pending, f64::MAX) {
+                    if msg[0] & 0xF0 == NOTE_OFF {
+                        let _ = conn_out.send(&msg);
+                    }
+                }
+                conn_out.close();
+            })?;
+
+        Ok(MidiOutputSink { queue: tx })
+    }
+
+    /// schedule note on and off for the given (stream) time
+    pub fn send_note(&self, note: MidiNote, time: f64) {
+        let channel = note.channel - 1;
+        let _ = self
+            .queue
+            .send((time, [NOTE_ON | channel, note.note, note.velocity]));
+        let _ = self
+            .queue
+            .send((time + note.duration, [NOTE_OFF | channel, note.note, 0]));
+    }
+}
+
+/// All open midi outputs, and which events go where.
+#[derive(Clone)]
+pub struct MidiOutputs {
+    ports: sync::Arc<DashMap<usize, MidiOutputSink>>,
+    // tag -> port, channel
+    routes: sync::Arc<DashMap<String, (usize, u8)>>,
+    // midi-note events without explicit port go here
+    default_port: sync::Arc<AtomicCell<Option<usize>>>,
+}
+
+impl Default for MidiOutputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiOutputs {
+    pub fn new() -> Self {
+        MidiOutputs {
+            ports: sync::Arc::new(DashMap::new()),
+            routes: sync::Arc::new(DashMap::new()),
+            default_port: sync::Arc::new(AtomicCell::new(None)),
+        }
+    }
+
+    /// open an output port, which becomes the default port
+    pub fn open(
+        &self,
+        out_port_num: usize,
+        clock: sync::Arc<dyn SchedulerClock>,
+    ) -> Result<(), anyhow::Error> {
+        if !self.ports.contains_key(&out_port_num) {
+            let sink = MidiOutputSink::open(out_port_num, clock)?;
+            self.ports.insert(out_port_num, sink);
+        }
+        self.default_port.store(Some(out_port_num));
+        Ok(())
+    }
+
+    /// send events with this tag to midi instead of the synth
+    pub fn add_route(&self, tag: String, port: usize, channel: u8) {
+        self.routes.insert(tag, (port, channel));
+    }
+
+    pub fn remove_route(&self, tag: &str) {
+        self.routes.remove(tag);
+    }
+
+    fn find_route(&self, tags: &BTreeSet<String>) -> Option<(usize, u8)> {
+        for tag in tags.iter() {
+            if let Some(route) = self.routes.get(tag) {
+                return Some(*route.value());
+            }
+        }
+        None
+    }
+
+    /// Send the event to midi if it's a midi event or if it's routed
+    /// to midi. Returns true if the event has been taken care of.
+    pub fn handle_event(&self, ev: &StaticEvent, time: f64) -> bool {
+        let (port, note) = if ev.name == "midi-note" {
+            (
+                self.default_port.load(),
+                event_to_midi_note(ev, ev.midi_channel.unwrap_or(1)),
+            )
+        } else if self.routes.is_empty() {
+            return false;
+        } else if let Some((port, channel)) = self.find_route(&ev.tags) {
+            (Some(port), event_to_midi_note(ev, channel))
+        } else {
+            return false;
+        };
+
+        if let Some(sink) = port.and_then(|p| self.ports.get(&p)) {
+            sink.send_note(note, time);
+        } else {
+            println!("no midi output port open");
+        }
+
+        true
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventOperation;
+    use std::collections::HashMap;
+
+    fn static_event(name: &str, params: &[(SynthParameterLabel, f32)]) -> StaticEvent {
+        let mut map = HashMap::new();
+        for (label, val) in params.iter() {
+            map.insert((*label).into(), SynthParameterValue::ScalarF32(*val));
+        }
+        StaticEvent {
+            name: name.to_string(),
+            params: map,
+            tags: BTreeSet::new(),
+            op: EventOperation::Replace,
+            sample_lookup: None,
+            midi_channel: None,
+        }
+    }
+
+    #[test]
+    fn test_event_to_midi_note() {
+        let ev = static_event(
+            "midi-note",
+            &[
+                (SynthParameterLabel::PitchNote, 60.0),
+                (SynthParameterLabel::EnvelopeLevel, 100.0),
+                (SynthParameterLabel::Duration, 200.0),
+            ],
+        );
+        assert_eq!(
+            event_to_midi_note(&ev, 2),
+            MidiNote {
+                channel: 2,
+                note: 60,
+                velocity: 100,
+                duration: 0.2
+            }
+        );
+
+        // synth events, note from frequency, length from envelope
+        let ev = static_event(
+            "saw",
+            &[
+                (SynthParameterLabel::PitchFrequency, 220.0),
+                (SynthParameterLabel::EnvelopeLevel, 0.5),
+                (SynthParameterLabel::Attack, 1.0),
+                (SynthParameterLabel::Sustain, 48.0),
+                (SynthParameterLabel::Release, 100.0),
+            ],
+        );
+        let note = event_to_midi_note(&ev, 1);
+        assert_eq!(note.note, 57);
+        assert_eq!(note.velocity, 64);
+        assert!((note.duration - 0.149).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_due_messages() {
+        let mut pending = vec![
+            (0.5, [NOTE_ON, 62, 100]),
+            (0.2, [NOTE_OFF, 60, 0]),
+            (0.2, [NOTE_ON, 60, 100]),
+            (0.1, [NOTE_ON, 60, 100]),
+        ];
+
+        let due = take_due_messages(&mut pending, 0.3);
+        // note off before note on at the same time
+        assert_eq!(
+            due,
+            vec![[NOTE_ON, 60, 100], [NOTE_OFF, 60, 0], [NOTE_ON, 60, 100]]
+        );
+        assert_eq!(pending.len(), 1);
+    }
+}
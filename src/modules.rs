@@ -0,0 +1,457 @@
+//! `(import "drums")` loads a file as a module. The functions, macros and
+//! variables it defines are put in a namespace, so different libraries
+//! don't overwrite each other's definitions:
+//!
+//! ```lisp
+//! ;; lib/drums.megra3
+//! (fun beat (name pattern) (sx name #t (cyc name pattern)))
+//!
+//! ;; the sketch
+//! (import "drums")
+//! (import "team/drums" :as 'td)
+//! (drums/beat 'a "bd ~ sn ~")
+//! ```
+//!
+//! Inside the file, its own definitions are used without the namespace.
+//! Only names are namespaced, symbols (i.e. `(defpart 'verse ...)`) and
+//! OSC addresses are shared.
+//!
+//! The file is looked for next to the file that imports it, then in the
+//! `lib` folder in the base folder, then relative to where Mégra runs.
+//! The `.megra3` extension can be left out.
+//!
+//! A file is only evaluated again if it has changed since it was imported,
+//! and changed files are re-imported in the background as well (into every
+//! namespace they've been imported into), so editing a library in another
+//! editor updates the running session (until it's cleared). A file that
+//! imports itself, directly or through other files, is reported instead.
+
+use parking_lot::Mutex;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use std::{fs, sync, thread};
+
+use crate::builtin_types::{Command, VariableId};
+use crate::file_interpreter::segment_expressions;
+use crate::interpreter;
+use crate::parser::{self, Atom, EvaluatedExpr, Expr, FunctionMap};
+use crate::session::Session;
+
+// how often the imported files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// a file can be imported into several namespaces
+type ModuleKey = (PathBuf, String);
+
+/// The files that have been imported (and when they have been modified),
+/// and the ones that are being imported right now, so the watcher and an
+/// import don't load the same file into the same namespace twice at once.
+pub struct Modules {
+    loaded: HashMap<ModuleKey, Option<SystemTime>>,
+    loading: HashSet<ModuleKey>,
+    // set to stop the thread that re-imports changed files
+    watcher: Option<sync::Arc<AtomicBool>>,
+}
+
+impl Modules {
+    pub fn new() -> Self {
+        Modules {
+            loaded: HashMap::new(),
+            loading: HashSet::new(),
+            watcher: None,
+        }
+    }
+
+    /// The modules whose files have changed since they've been loaded,
+    /// marked as loading.
+    fn take_changed(&mut self) -> Vec<ModuleKey> {
+        let changed: Vec<ModuleKey> = self
+            .loaded
+            .iter()
+            .filter(|((path, _), loaded)| **loaded != modified(path))
+            .map(|(key, _)| key.clone())
+            .collect();
+        // the ones that are being imported right now are up to date anyway
+        changed
+            .into_iter()
+            .filter(|key| self.loading.insert(key.clone()))
+            .collect()
+    }
+
+    /// Stop re-importing changed files, until the next import.
+    pub fn stop_watching(&mut self) {
+        if let Some(stop) = self.watcher.take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Where a module is, given the folder of the file that imports it (if
+/// any) and the base folder.
+pub fn find_module(name: &str, importing_dir: Option<&Path>, base_dir: &Path) -> Option<PathBuf> {
+    let lib_dir = base_dir.join("lib");
+    let dirs = importing_dir
+        .into_iter()
+        .chain(std::iter::once(lib_dir.as_path()))
+        .chain(std::iter::once(Path::new(".")));
+
+    for dir in dirs {
+        let path = dir.join(name);
+        for candidate in [path.with_extension("megra3"), path] {
+            if candidate.is_file() {
+                return candidate.canonicalize().ok();
+            }
+        }
+    }
+    None
+}
+
+/// The names of the functions, macros and variables a module defines.
+pub fn defined_names(exprs: &[Expr]) -> HashSet<String> {
+    exprs
+        .iter()
+        .filter_map(|expr| match expr {
+            Expr::Definition(_, tail, _) => match tail.first() {
+                Some(Expr::Constant(Atom::Identifier(name), _)) => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The expression with the given names in the namespace.
+pub fn namespaced(expr: &Expr, names: &HashSet<String>, namespace: &str) -> Expr {
+    match expr {
+        Expr::Constant(Atom::Identifier(name), span) if names.contains(name) => {
+            Expr::Constant(Atom::Identifier(format!("{namespace}/{name}")), *span)
+        }
+        Expr::Application(head, tail, span) => Expr::Application(
+            Box::new(namespaced(head, names, namespace)),
+            tail.iter()
+                .map(|e| namespaced(e, names, namespace))
+                .collect(),
+            *span,
+        ),
+        Expr::Definition(head, tail, span) => Expr::Definition(
+            head.clone(),
+            tail.iter()
+                .map(|e| namespaced(e, names, namespace))
+                .collect(),
+            *span,
+        ),
+        _ => expr.clone(),
+    }
+}
+
+/// Remove what has been defined in a namespace, so that definitions that
+/// have been removed from a file are gone when it's loaded again.
+fn forget_namespace(
+    namespace: &str,
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    globals: &sync::Arc<crate::GlobalVariables>,
+) {
+    let prefix = format!("{namespace}/");
+    let mut functions = function_map.lock();
+    functions
+        .usr_lib
+        .retain(|name, _| !name.starts_with(&prefix));
+    functions
+        .macros
+        .retain(|name, _| !name.starts_with(&prefix));
+    globals.retain(|id, _| !matches!(id, VariableId::Custom(name) if name.starts_with(&prefix)));
+}
+
+pub fn import<const BUFSIZE: usize, const NCHAN: usize>(
+    name: &str,
+    namespace: Option<String>,
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: &Session<BUFSIZE, NCHAN>,
+    base_dir: String,
+) {
+    import_from(name, namespace, &[], function_map, session, &base_dir);
+
+    let mut modules = session.modules.lock();
+    if modules.watcher.is_none() && !modules.loaded.is_empty() {
+        modules.watcher = Some(watch(function_map, session, base_dir));
+    }
+}
+
+/// Import a module from within the modules in `chain`, the last one being
+/// the file that contains the import.
+fn import_from<const BUFSIZE: usize, const NCHAN: usize>(
+    name: &str,
+    namespace: Option<String>,
+    chain: &[PathBuf],
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: &Session<BUFSIZE, NCHAN>,
+    base_dir: &str,
+) {
+    let importing_dir = chain.last().and_then(|p| p.parent());
+
+    let Some(path) = find_module(name, importing_dir, Path::new(base_dir)) else {
+        println!("couldn't find module \"{name}\"");
+        return;
+    };
+
+    if chain.contains(&path) {
+        let cycle: Vec<String> = chain
+            .iter()
+            .chain(std::iter::once(&path))
+            .map(|p| p.display().to_string())
+            .collect();
+        println!("import cycle: {}", cycle.join(" -> "));
+        return;
+    }
+
+    let namespace = namespace.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    {
+        let mut modules = session.modules.lock();
+        let key = (path.clone(), namespace.clone());
+        if modules.loaded.get(&key) == Some(&modified(&path)) {
+            return;
+        }
+        // marked while the lock is held, so nobody else starts loading it
+        if !modules.loading.insert(key) {
+            println!("module {namespace} is being imported already");
+            return;
+        }
+    }
+
+    load(&path, &namespace, chain, function_map, session, base_dir);
+}
+
+/// Evaluate a module into its namespace. The module has to be marked as
+/// loading, the mark is removed when it's done.
+fn load<const BUFSIZE: usize, const NCHAN: usize>(
+    path: &Path,
+    namespace: &str,
+    chain: &[PathBuf],
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: &Session<BUFSIZE, NCHAN>,
+    base_dir: &str,
+) {
+    let chain: Vec<PathBuf> = chain
+        .iter()
+        .cloned()
+        .chain(std::iter::once(path.to_path_buf()))
+        .collect();
+
+    let modified = modified(path);
+    match fs::read_to_string(path) {
+        Ok(s) => {
+            // parse everything first, to know which names the module defines
+            let (mut srcs, mut exprs) = (Vec::new(), Vec::new());
+            for src in segment_expressions(s) {
+                match parser::parse_from_str(&src) {
+                    Ok(expr) => {
+                        srcs.push(src);
+                        exprs.push(expr);
+                    }
+                    Err(e) => println!("{}: {}", path.display(), e.report(&src)),
+                }
+            }
+            let names = defined_names(&exprs);
+
+            println!("import module {namespace} from {}", path.display());
+            forget_namespace(namespace, function_map, &session.globals);
+
+            for (src, expr) in srcs.iter().zip(exprs.iter()) {
+                let res = {
+                    let functions = function_map.lock();
+                    parser::eval_expression(
+                        &namespaced(expr, &names, namespace),
+                        &functions,
+                        &session.globals,
+                        None,
+                        session.sample_set.clone(),
+                        session.output_mode,
+                    )
+                };
+
+                match res {
+                    Ok(res) => interpret(res, &chain, function_map, session, base_dir),
+                    Err(e) => println!("{}: {}", path.display(), e.report(src)),
+                }
+            }
+        }
+        Err(e) => println!("couldn't load module {}: {e}", path.display()),
+    }
+
+    let key = (path.to_path_buf(), namespace.to_string());
+    let mut modules = session.modules.lock();
+    modules.loading.remove(&key);
+    modules.loaded.insert(key, modified);
+}
+
+/// Like `interpreter::interpret`, but imports are relative to the module.
+fn interpret<const BUFSIZE: usize, const NCHAN: usize>(
+    res: EvaluatedExpr,
+    chain: &[PathBuf],
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: &Session<BUFSIZE, NCHAN>,
+    base_dir: &str,
+) {
+    match res {
+        EvaluatedExpr::Command(Command::Import(name, namespace)) => {
+            import_from(&name, namespace, chain, function_map, session, base_dir)
+        }
+        EvaluatedExpr::Progn(exprs) => {
+            for expr in exprs {
+                interpret(expr, chain, function_map, session, base_dir);
+            }
+        }
+        res => interpreter::interpret(res, function_map, session.clone(), base_dir.to_string()),
+    }
+}
+
+/// Import the files again when they change, until the returned flag is set.
+fn watch<const BUFSIZE: usize, const NCHAN: usize>(
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    session: &Session<BUFSIZE, NCHAN>,
+    base_dir: String,
+) -> sync::Arc<AtomicBool> {
+    let stop = sync::Arc::new(AtomicBool::new(false));
+    let stop2 = sync::Arc::clone(&stop);
+    let function_map = sync::Arc::clone(function_map);
+    let session = session.clone();
+    thread::spawn(move || loop {
+        thread::sleep(WATCH_INTERVAL);
+        if stop2.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let changed = session.modules.lock().take_changed();
+        for (path, namespace) in changed {
+            println!("module {namespace} has changed");
+            load(&path, &namespace, &[], &function_map, &session, &base_dir);
+        }
+    });
+    stop
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespaced() {
+        let exprs: Vec<Expr> = [
+            "(fun beat (name) (sx name #t (pattern name)))",
+            "(fun pattern (name) (cyc name \"bd ~\"))",
+            "(let tempo 120)",
+            "(let 'verse (nuc 'a (saw 100)))",
+        ]
+        .iter()
+        .map(|src| parser::parse_from_str(src).unwrap())
+        .collect();
+
+        let names = defined_names(&exprs);
+        assert_eq!(
+            names,
+            HashSet::from([
+                "beat".to_string(),
+                "pattern".to_string(),
+                "tempo".to_string()
+            ])
+        );
+
+        let source = |expr: &Expr| parser::eval::macros::source(&namespaced(expr, &names, "dr"));
+        assert_eq!(
+            source(&exprs[0]),
+            "(fun dr/beat (name) (sx name #t (dr/pattern name)))"
+        );
+        assert_eq!(source(&exprs[2]), "(let dr/tempo 120)");
+        // symbols are shared
+        assert_eq!(source(&exprs[3]), "(let 'verse (nuc 'a (saw 100)))");
+
+        // and the namespaced names can be parsed
+        assert!(matches!(
+            parser::parse_from_str("(dr/beat 'a)"),
+            Ok(Expr::Application(head, _, _))
+                if matches!(head.as_ref(), Expr::Constant(Atom::Identifier(f), _) if f == "dr/beat")
+        ));
+    }
+
+    #[test]
+    fn test_find_module() {
+        let base = std::env::temp_dir().join("test_find_module");
+        let team = base.join("lib").join("team");
+        fs::create_dir_all(&team).unwrap();
+        fs::write(base.join("lib").join("drums.megra3"), "").unwrap();
+        fs::write(team.join("bass.megra3"), "").unwrap();
+        fs::write(team.join("drums.megra3"), "").unwrap();
+
+        let found = |name: &str, dir: Option<&Path>| {
+            find_module(name, dir, &base).map(|p| {
+                p.strip_prefix(base.canonicalize().unwrap())
+                    .unwrap()
+                    .to_path_buf()
+            })
+        };
+
+        assert_eq!(
+            found("drums", None),
+            Some(Path::new("lib/drums.megra3").to_path_buf())
+        );
+        assert_eq!(
+            found("team/bass.megra3", None),
+            Some(Path::new("lib/team/bass.megra3").to_path_buf())
+        );
+        // next to the importing file first
+        assert_eq!(
+            found("drums", Some(&team)),
+            Some(Path::new("lib/team/drums.megra3").to_path_buf())
+        );
+        assert_eq!(
+            found("bass", Some(&team)),
+            Some(Path::new("lib/team/bass.megra3").to_path_buf())
+        );
+        assert_eq!(found("nothing", None), None);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_changed_in_every_namespace() {
+        let path = std::env::temp_dir().join("test_changed_modules.megra3");
+        fs::write(&path, "").unwrap();
+
+        let mut modules = Modules::new();
+        for namespace in ["drums", "dr"] {
+            modules
+                .loaded
+                .insert((path.clone(), namespace.to_string()), modified(&path));
+        }
+        assert!(modules.take_changed().is_empty());
+
+        // both namespaces are loaded again, once
+        modules
+            .loaded
+            .values_mut()
+            .for_each(|m| *m = Some(SystemTime::UNIX_EPOCH));
+        let mut changed: Vec<String> = modules
+            .take_changed()
+            .into_iter()
+            .map(|(_, ns)| ns)
+            .collect();
+        changed.sort();
+        assert_eq!(changed, vec!["dr", "drums"]);
+        assert!(modules.take_changed().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use ruffbox_synth::ruffbox::RuffboxPlayhead;
+
+use crate::clock::ManualClock;
+
+/// Render the given amount of time to a wave file, faster than realtime.
+///
+/// Before each block is rendered, the virtual clock is moved to the end of
+/// the block, so that all the schedulers that are due have had the chance
+/// to send their events to the playhead.
+pub fn render_to_file<const BUFSIZE: usize, const NCHAN: usize>(
+    playhead: &mut RuffboxPlayhead<BUFSIZE, NCHAN>,
+    clock: &ManualClock,
+    duration: f64,
+    samplerate: u32,
+    path: &str,
+) -> Result<(), anyhow::Error> {
+    let spec = hound::WavSpec {
+        channels: NCHAN as u16, // record with global number of channels
+        sample_rate: samplerate,
+        bits_per_sample: 32, // 32bit float is fixed
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+
+    let block_duration = BUFSIZE as f64 / samplerate as f64;
+    let mut samples_left = (duration * samplerate as f64).round() as usize;
+    let mut block_end = 0.0;
+
+    while samples_left > 0 {
+        block_end += block_duration;
+        clock.advance_to(block_end);
+
+        let block = playhead.process(0.0, true);
+
+        let block_size = samples_left.min(BUFSIZE);
+        for s in 0..block_size {
+            for ch_block in block.iter() {
+                writer.write_sample(ch_block[s])?;
+            }
+        }
+        samples_left -= block_size;
+    }
+
+    writer.finalize()?;
+
+    Ok(())
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SchedulerClock;
+    use ruffbox_synth::ruffbox::{init_ruffbox, ReverbMode};
+
+    #[test]
+    fn test_render_to_file() {
+        let (_, mut playhead) =
+            init_ruffbox::<512, 2>(1, 3.0, &ReverbMode::FreeVerb, 44100.0, 100, 10, false);
+        let clock = ManualClock::new();
+        let path = std::env::temp_dir().join("megra_offline_render_test.wav");
+
+        render_to_file(&mut playhead, &clock, 1.0, 44100, path.to_str().unwrap()).unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        // duration is exact, even if it isn't a multiple of the blocksize
+        assert_eq!(reader.duration(), 44100);
+        assert!(clock.now() >= 1.0);
+    }
+}
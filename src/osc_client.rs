@@ -1,9 +1,110 @@
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use rosc::{OscMessage, OscTime, OscType};
+use ruffbox_synth::building_blocks::{
+    SynthParameterAddress, SynthParameterLabel, SynthParameterValue,
+};
+use std::collections::BTreeSet;
 use std::sync::{self, atomic::AtomicBool};
 
+use crate::event::StaticEvent;
+use crate::event_helpers::parameter_name;
+use crate::sample_set::SampleLookup;
 use crate::{osc_sender::OscSender, visualizer_client::VisualizerClient};
 
+// where SuperDirt expects the events
+pub const SUPERDIRT_ADDRESS: &str = "/dirt/play";
+
+/// The SuperDirt name of a parameter, and the value the way SuperDirt
+/// understands it (times in seconds, panning from 0 to 1 ...). Only the
+/// first of indexed parameters (i.e. `lpf`, not `lpf2`) has an equivalent.
+fn superdirt_parameter(addr: &SynthParameterAddress, val: f32) -> Option<(&'static str, f32)> {
+    if addr.idx.is_some_and(|idx| idx > 0) {
+        return None;
+    }
+    Some(match addr.label {
+        SynthParameterLabel::PitchFrequency => ("freq", val),
+        SynthParameterLabel::PitchNote => ("midinote", val),
+        SynthParameterLabel::EnvelopeLevel => ("gain", val),
+        SynthParameterLabel::Attack => ("attack", val / 1000.0),
+        SynthParameterLabel::Sustain => ("hold", val / 1000.0),
+        SynthParameterLabel::Release => ("release", val / 1000.0),
+        SynthParameterLabel::Duration => ("sustain", val / 1000.0),
+        SynthParameterLabel::ChannelPosition => ("pan", (val + 1.0) * 0.5),
+        SynthParameterLabel::LowpassCutoffFrequency => ("cutoff", val),
+        SynthParameterLabel::LowpassQFactor => ("resonance", val),
+        SynthParameterLabel::HighpassCutoffFrequency => ("hcutoff", val),
+        SynthParameterLabel::HighpassQFactor => ("hresonance", val),
+        SynthParameterLabel::PlaybackRate => ("speed", val),
+        SynthParameterLabel::PlaybackStart => ("begin", val),
+        SynthParameterLabel::ReverbMix => ("room", val),
+        SynthParameterLabel::DelayMix => ("delay", val),
+        SynthParameterLabel::WaveshaperMix => ("shape", val),
+        _ => return None,
+    })
+}
+
+/// Translate a sound event into an OSC message, in key-value format. The
+/// sound name is sent as `s`, the sample number (if any) as `n`, followed
+/// by the parameters that can be represented as a number. If the message
+/// goes to SuperDirt (`/dirt/play`), the parameters are translated to the
+/// ones SuperDirt knows, everything else gets Mégra's parameter names.
+pub fn event_to_osc_message(ev: &StaticEvent, addr: &str) -> OscMessage {
+    let mut args = Vec::new();
+
+    match &ev.sample_lookup {
+        Some(SampleLookup::N(set, n)) => {
+            args.push(OscType::String("s".to_string()));
+            args.push(OscType::String(set.clone()));
+            args.push(OscType::String("n".to_string()));
+            args.push(OscType::Int(*n as i32));
+        }
+        Some(SampleLookup::Key(set, _))
+        | Some(SampleLookup::Random(set))
+        | Some(SampleLookup::FixedRandom(set, _)) => {
+            args.push(OscType::String("s".to_string()));
+            args.push(OscType::String(set.clone()));
+        }
+        None => {
+            args.push(OscType::String("s".to_string()));
+            args.push(OscType::String(ev.name.clone()));
+        }
+    }
+
+    // sort the parameters so the messages are predictable
+    let mut params: Vec<(String, OscType)> = ev
+        .params
+        .iter()
+        .filter_map(|(param, val)| {
+            if addr == SUPERDIRT_ADDRESS {
+                let SynthParameterValue::ScalarF32(f) = val else {
+                    return None;
+                };
+                return superdirt_parameter(param, *f)
+                    .map(|(name, f)| (name.to_string(), OscType::Float(f)));
+            }
+            let val = match val {
+                SynthParameterValue::ScalarF32(f) => OscType::Float(*f),
+                SynthParameterValue::ScalarU32(i) => OscType::Int(*i as i32),
+                SynthParameterValue::ScalarUsize(i) => OscType::Int(*i as i32),
+                _ => return None,
+            };
+            parameter_name(param).map(|name| (name, val))
+        })
+        .collect();
+    params.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, val) in params.drain(..) {
+        args.push(OscType::String(name));
+        args.push(val);
+    }
+
+    OscMessage {
+        addr: addr.to_string(),
+        args,
+    }
+}
+
 #[derive(Clone)]
 pub struct OscClient {
     // probably this extra flag isn't all that necessary, but maybe
@@ -13,6 +114,8 @@ pub struct OscClient {
     pub vis_connected: sync::Arc<AtomicBool>,
     pub vis: sync::Arc<RwLock<Option<VisualizerClient>>>,
     pub custom: sync::Arc<DashMap<String, OscSender>>,
+    // tag -> sender name, osc address
+    pub routes: sync::Arc<DashMap<String, (String, String)>>,
 }
 impl OscClient {
     pub fn new() -> Self {
@@ -20,6 +123,143 @@ impl OscClient {
             vis_connected: sync::Arc::new(AtomicBool::new(false)),
             vis: sync::Arc::new(RwLock::new(None)),
             custom: sync::Arc::new(DashMap::new()),
+            routes: sync::Arc::new(DashMap::new()),
         }
     }
+
+    /// send events with this tag to an osc sender instead of the synth
+    pub fn add_route(&self, tag: String, sender: String, addr: String) {
+        self.routes.insert(tag, (sender, addr));
+    }
+
+    pub fn remove_route(&self, tag: &str) {
+        self.routes.remove(tag);
+    }
+
+    fn find_route(&self, tags: &BTreeSet<String>) -> Option<(String, String)> {
+        for tag in tags.iter() {
+            if let Some(route) = self.routes.get(tag) {
+                return Some(route.value().clone());
+            }
+        }
+        None
+    }
+
+    /// Send the event via OSC if it's routed somewhere, as a bundle
+    /// to be played at the given time. Returns true if the event has
+    /// been taken care of.
+    pub fn handle_event(&self, ev: &StaticEvent, timetag: OscTime) -> bool {
+        if self.routes.is_empty() {
+            return false;
+        }
+
+        let (sender_name, addr) = if let Some(route) = self.find_route(&ev.tags) {
+            route
+        } else {
+            return false;
+        };
+
+        if let Some(sender) = self.custom.get(&sender_name) {
+            if let Err(e) = sender.send_bundle(timetag, vec![event_to_osc_message(ev, &addr)]) {
+                println!("can't send event to osc sender {sender_name}: {e}");
+            }
+        } else {
+            println!("no osc sender named {sender_name}");
+        }
+
+        true
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventOperation;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_event_to_osc_message() {
+        let mut params = HashMap::new();
+        params.insert(
+            SynthParameterLabel::PlaybackRate.into(),
+            SynthParameterValue::ScalarF32(0.5),
+        );
+        params.insert(
+            SynthParameterLabel::EnvelopeLevel.into(),
+            SynthParameterValue::ScalarF32(0.8),
+        );
+        params.insert(
+            SynthParameterLabel::SampleBufferNumber.into(),
+            SynthParameterValue::ScalarUsize(3),
+        );
+        params.insert(
+            SynthParameterLabel::LowpassCutoffFrequency.with_index(1),
+            SynthParameterValue::ScalarF32(1000.0),
+        );
+
+        let ev = StaticEvent {
+            name: "sampler".to_string(),
+            params,
+            tags: BTreeSet::new(),
+            op: EventOperation::Replace,
+            sample_lookup: Some(SampleLookup::N("bd".to_string(), 2)),
+            midi_channel: None,
+        };
+
+        let msg = event_to_osc_message(&ev, "/megra/play");
+        assert_eq!(msg.addr, "/megra/play");
+        // the buffer number doesn't mean anything outside of megra
+        assert_eq!(
+            msg.args,
+            vec![
+                OscType::String("s".to_string()),
+                OscType::String("bd".to_string()),
+                OscType::String("n".to_string()),
+                OscType::Int(2),
+                OscType::String("lpf2".to_string()),
+                OscType::Float(1000.0),
+                OscType::String("lvl".to_string()),
+                OscType::Float(0.8),
+                OscType::String("rate".to_string()),
+                OscType::Float(0.5),
+            ]
+        );
+
+        // superdirt has its own names, and no second lowpass
+        let msg = event_to_osc_message(&ev, SUPERDIRT_ADDRESS);
+        assert_eq!(
+            msg.args,
+            vec![
+                OscType::String("s".to_string()),
+                OscType::String("bd".to_string()),
+                OscType::String("n".to_string()),
+                OscType::Int(2),
+                OscType::String("gain".to_string()),
+                OscType::Float(0.8),
+                OscType::String("speed".to_string()),
+                OscType::Float(0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_superdirt_parameter() {
+        assert_eq!(
+            superdirt_parameter(&SynthParameterLabel::ChannelPosition.into(), -1.0),
+            Some(("pan", 0.0))
+        );
+        assert_eq!(
+            superdirt_parameter(&SynthParameterLabel::Attack.into(), 10.0),
+            Some(("attack", 0.01))
+        );
+        assert_eq!(
+            superdirt_parameter(&SynthParameterLabel::LowpassCutoffFrequency.into(), 500.0),
+            Some(("cutoff", 500.0))
+        );
+        assert_eq!(
+            superdirt_parameter(&SynthParameterLabel::SampleBufferNumber.into(), 1.0),
+            None
+        );
+    }
 }
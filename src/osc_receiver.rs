@@ -1,131 +1,590 @@
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use parking_lot::Mutex;
-use rosc::{OscPacket, OscType};
+use rosc::address::{Matcher, OscAddress};
+use rosc::{OscMessage, OscPacket, OscType};
 
-use std::collections::HashMap;
-use std::net::{SocketAddrV4, UdpSocket};
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddrV4, TcpListener, TcpStream, UdpSocket};
 use std::str::FromStr;
 use std::sync;
+use std::time::{Duration, SystemTime};
 
 use crate::builtin_types::{Comparable, TypedEntity};
 use crate::interpreter;
-use crate::parser::{eval_expression, EvaluatedExpr, FunctionMap};
+use crate::parser::eval::function;
+use crate::parser::{EvaluatedExpr, FunctionMap};
 
 use crate::session::Session;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OscTransport {
+    Udp,
+    Tcp, // SLIP-framed, as in OSC 1.1
+}
+
+// SLIP special characters
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+// longer packets are dropped, so a peer that never ends one
+// can't make the buffer grow forever
+const SLIP_MAX_PACKET_SIZE: usize = 65536;
+
+/// Splits a SLIP-encoded byte stream into packets. The stream can be
+/// fed in arbitrary chunks, incomplete packets are kept until the rest
+/// arrives. Packets longer than 64 KiB are discarded.
+#[derive(Default)]
+pub struct SlipDecoder {
+    buf: Vec<u8>,
+    escaped: bool,
+    // skipping the rest of a packet that's too long
+    overflow: bool,
+}
+
+impl SlipDecoder {
+    pub fn new() -> Self {
+        SlipDecoder::default()
+    }
+
+    /// returns the packets that have been completed by this chunk
+    pub fn decode(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        for byte in data.iter() {
+            if self.overflow {
+                if *byte == SLIP_END {
+                    self.overflow = false;
+                    self.escaped = false;
+                }
+                continue;
+            }
+            if self.buf.len() >= SLIP_MAX_PACKET_SIZE {
+                println!("osc packet longer than {SLIP_MAX_PACKET_SIZE} bytes, dropped");
+                self.buf = Vec::new();
+                self.overflow = *byte != SLIP_END;
+                self.escaped = false;
+                continue;
+            }
+            if self.escaped {
+                self.escaped = false;
+                match *byte {
+                    SLIP_ESC_END => self.buf.push(SLIP_END),
+                    SLIP_ESC_ESC => self.buf.push(SLIP_ESC),
+                    // protocol violation, keep the byte as it is
+                    b => self.buf.push(b),
+                }
+            } else {
+                match *byte {
+                    SLIP_END => {
+                        // empty packets are just separators
+                        if !self.buf.is_empty() {
+                            packets.push(std::mem::take(&mut self.buf));
+                        }
+                    }
+                    SLIP_ESC => self.escaped = true,
+                    b => self.buf.push(b),
+                }
+            }
+        }
+        packets
+    }
+}
+
+/// Check whether an incoming address matches the address a handler
+/// has been defined for. Either of them can be a pattern, so a handler
+/// for `/foo/*` gets `/foo/bar`, and a message to `/foo/*` reaches
+/// the handler for `/foo/bar`.
+pub fn address_matches(handler: &str, addr: &str) -> bool {
+    if handler == addr {
+        return true;
+    }
+
+    if let (Ok(matcher), Ok(address)) = (Matcher::new(addr), OscAddress::new(handler.to_string())) {
+        if matcher.match_address(&address) {
+            return true;
+        }
+    }
+
+    if let (Ok(matcher), Ok(address)) = (Matcher::new(handler), OscAddress::new(addr.to_string())) {
+        return matcher.match_address(&address);
+    }
+
+    false
+}
+
+/// Convert an OSC argument to something megra can work with.
+/// Numbers all become floats, as that's what most functions expect.
+/// Blobs, colors and midi messages become vectors of numbers. Nil is
+/// `#f`, like a left-out optional argument, and infinitum (an impulse)
+/// is `#t`.
+pub fn osc_to_typed(val: &OscType) -> TypedEntity {
+    let num = |f: f32| TypedEntity::Comparable(Comparable::Float(f));
+    let bytes = |b: &[u8]| TypedEntity::Vec(b.iter().map(|x| Box::new(num(*x as f32))).collect());
+
+    match val {
+        OscType::Float(f) => num(*f),
+        OscType::Double(d) => num(*d as f32),
+        OscType::Int(i) => num(*i as f32),
+        OscType::Long(i) => num(*i as f32),
+        OscType::String(s) => TypedEntity::Comparable(Comparable::String(s.clone())),
+        OscType::Char(c) => TypedEntity::Comparable(Comparable::Character(*c)),
+        OscType::Bool(b) => TypedEntity::Comparable(Comparable::Boolean(*b)),
+        OscType::Blob(b) => bytes(b),
+        OscType::Color(c) => bytes(&[c.red, c.green, c.blue, c.alpha]),
+        OscType::Midi(m) => bytes(&[m.port, m.status, m.data1, m.data2]),
+        OscType::Time(t) => {
+            // seconds since the unix epoch
+            let secs = SystemTime::from(*t)
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0);
+            TypedEntity::Comparable(Comparable::Double(secs))
+        }
+        OscType::Array(a) => TypedEntity::Vec(
+            a.content
+                .iter()
+                .map(|v| Box::new(osc_to_typed(v)))
+                .collect(),
+        ),
+        OscType::Nil => TypedEntity::Comparable(Comparable::Boolean(false)),
+        OscType::Inf => TypedEntity::Comparable(Comparable::Boolean(true)),
+    }
+}
+
+// the longest a udp receiver waits after an error
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// errors after which receiving might work again
+fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            // i.e. an icmp "port unreachable" for something sent earlier
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionRefused
+    )
+}
+
+type DelayedBundle = (SystemTime, Vec<OscPacket>);
+
+/// Remove the bundles that are due from the list, and return their
+/// contents in the order they should be handled.
+fn take_due_bundles(pending: &mut Vec<DelayedBundle>, now: SystemTime) -> Vec<OscPacket> {
+    let mut due: Vec<DelayedBundle> = Vec::new();
+    let mut i = 0;
+    while i < pending.len() {
+        if pending[i].0 <= now {
+            due.push(pending.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+    // stable, so bundles with the same time tag stay in order
+    due.sort_by_key(|(time, _)| *time);
+    due.into_iter().flat_map(|(_, content)| content).collect()
+}
+
+/// Passes incoming messages to the user-defined functions
+/// with matching addresses.
+struct OscDispatcher<const BUFSIZE: usize, const NCHAN: usize> {
+    function_map: sync::Arc<Mutex<FunctionMap>>,
+    session: Session<BUFSIZE, NCHAN>,
+    base_dir: String,
+    // bundles that are meant for later go here
+    delayed: Sender<DelayedBundle>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> Clone for OscDispatcher<BUFSIZE, NCHAN> {
+    fn clone(&self) -> Self {
+        OscDispatcher {
+            function_map: sync::Arc::clone(&self.function_map),
+            session: self.session.clone(),
+            base_dir: self.base_dir.clone(),
+            delayed: self.delayed.clone(),
+        }
+    }
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> OscDispatcher<BUFSIZE, NCHAN> {
+    fn handle_bytes(&self, bytes: &[u8], from: &str) {
+        match rosc::decoder::decode_udp(bytes) {
+            Ok((_, packet)) => self.handle_packet(packet),
+            Err(e) => println!("osc receiver: can't decode packet from {from}: {e:?}"),
+        }
+    }
+
+    fn handle_packet(&self, packet: OscPacket) {
+        match packet {
+            OscPacket::Message(msg) => self.handle_message(msg),
+            OscPacket::Bundle(bundle) => {
+                // bundles that are meant for later are held back until then,
+                // "immediately" (or anything in the past) is handled right away
+                let due = SystemTime::from(bundle.timetag);
+                if due > SystemTime::now() {
+                    let _ = self.delayed.send((due, bundle.content));
+                } else {
+                    for p in bundle.content {
+                        self.handle_packet(p);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle the delayed bundles once they're due, all in one thread,
+    /// no matter how many are waiting.
+    fn run_delayed(&self, bundles: Receiver<DelayedBundle>) {
+        let mut pending: Vec<DelayedBundle> = Vec::new();
+        loop {
+            let now = SystemTime::now();
+            for p in take_due_bundles(&mut pending, now) {
+                self.handle_packet(p);
+            }
+
+            let wait = pending
+                .iter()
+                .map(|(t, _)| t.duration_since(now).unwrap_or(Duration::ZERO))
+                .fold(Duration::from_millis(100), Duration::min);
+
+            match bundles.recv_timeout(wait) {
+                Ok(bundle) => pending.push(bundle),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    fn handle_message(&self, msg: OscMessage) {
+        let mut results = Vec::new();
+
+        {
+            let functions = self.function_map.lock();
+            let mut found = false;
+
+            for (name, fun) in functions.usr_lib.iter() {
+                if !name.starts_with('/') || !address_matches(name, &msg.addr) {
+                    continue;
+                }
+                found = true;
+
+                if fun.params.len() > msg.args.len() {
+                    println!(
+                        "osc receiver: {} expects {} arguments, got {}",
+                        name,
+                        fun.params.len(),
+                        msg.args.len()
+                    );
+                    continue;
+                }
+
+                let args = msg
+                    .args
+                    .iter()
+                    .map(|val| EvaluatedExpr::Typed(osc_to_typed(val)))
+                    .collect();
+
+                match function::call(
+                    fun,
+                    name,
+                    args,
+                    &functions,
+                    &self.session.globals,
+                    self.session.sample_set.clone(),
+                    self.session.output_mode,
+                ) {
+                    Ok(fun_tail) => results.extend(fun_tail),
+                    Err(e) => println!("osc receiver: can't evaluate {name}: {e}"),
+                }
+            }
+
+            if !found {
+                println!("osc receiver: no function for address {}", msg.addr);
+            }
+        } // the interpreter might need the function map, so release it first
+
+        for eval_expr in results {
+            interpreter::interpret(
+                eval_expr,
+                &self.function_map,
+                self.session.clone(),
+                self.base_dir.clone(),
+            );
+        }
+    }
+}
+
 pub struct OscReceiver;
 
 impl OscReceiver {
-    pub fn start_receiver_thread_udp<const BUFSIZE: usize, const NCHAN: usize>(
+    pub fn start_receiver_thread<const BUFSIZE: usize, const NCHAN: usize>(
         target: String,
+        transport: OscTransport,
         function_map: sync::Arc<Mutex<FunctionMap>>,
         session: Session<BUFSIZE, NCHAN>,
         base_dir: String,
     ) {
-        let addr = match SocketAddrV4::from_str(&target) {
-            Ok(addr) => addr,
-            Err(_) => panic!("err"),
+        let (tx, rx) = unbounded();
+        let dispatcher = OscDispatcher {
+            function_map,
+            session,
+            base_dir,
+            delayed: tx,
         };
 
-        let sock = UdpSocket::bind(addr).unwrap();
-
-        println!("Listening to {}", addr);
-
-        let mut buf = [0u8; rosc::decoder::MTU];
-
-        std::thread::spawn(move || loop {
-            match sock.recv_from(&mut buf) {
-                Ok((size, addr)) => {
-                    println!("Received packet with size {} from: {}", size, addr);
-                    let (_, packet) = rosc::decoder::decode_udp(&buf[..size]).unwrap();
-                    match packet {
-                        OscPacket::Message(msg) => {
-                            println!("OSC address: {}", msg.addr);
-                            println!("OSC arguments: {:?}", msg.args);
-
-                            // check whether we have an OSC function stored under this address ...
-                            let functions = function_map.lock();
-
-                            if functions.usr_lib.contains_key(&msg.addr) {
-                                let (fun_arg_names, fun_expr) =
-                                    functions.usr_lib.get(&msg.addr).unwrap().clone();
-
-                                // FIRST, eval local args,
-                                // manual zip
-                                let mut local_args = HashMap::new();
-                                for (i, val) in msg.args[..fun_arg_names.len()].iter().enumerate() {
-                                    // TODO: better type handling ...
-                                    local_args.insert(
-                                        fun_arg_names[i].clone(),
-                                        match val {
-                                            OscType::Float(f) => EvaluatedExpr::Typed(
-                                                TypedEntity::Comparable(Comparable::Float(*f)),
-                                            ),
-                                            OscType::Double(d) => {
-                                                EvaluatedExpr::Typed(TypedEntity::Comparable(
-                                                    Comparable::Float(*d as f32),
-                                                ))
-                                            }
-                                            OscType::Int(i) => {
-                                                EvaluatedExpr::Typed(TypedEntity::Comparable(
-                                                    Comparable::Float(*i as f32),
-                                                ))
-                                            }
-                                            OscType::Long(i) => {
-                                                EvaluatedExpr::Typed(TypedEntity::Comparable(
-                                                    Comparable::Float(*i as f32),
-                                                ))
-                                            }
-                                            OscType::String(s) => {
-                                                EvaluatedExpr::Typed(TypedEntity::Comparable(
-                                                    Comparable::String(s.clone()),
-                                                ))
-                                            }
-                                            _ => {
-                                                continue;
-                                            } // dirty ..
-                                        },
-                                    );
-                                }
-
-                                // THIRD
-                                if let Some(fun_tail) = fun_expr
-                                    .iter()
-                                    .map(|expr| {
-                                        eval_expression(
-                                            expr,
-                                            &functions,
-                                            &session.globals,
-                                            Some(&local_args),
-                                            session.sample_set.clone(),
-                                            session.output_mode,
-                                        )
-                                    })
-                                    .collect::<Option<Vec<EvaluatedExpr>>>()
-                                {
-                                    // return last form result, cl-style
-                                    for eval_expr in fun_tail {
-                                        interpreter::interpret(
-                                            eval_expr,
-                                            &function_map,
-                                            session.clone(),
-                                            base_dir.clone(),
-                                        );
-                                    }
-                                }
-                            } else {
-                                println!("no callback for OSC addr ??");
+        let res = match transport {
+            OscTransport::Udp => OscReceiver::start_udp(&target, dispatcher.clone()),
+            OscTransport::Tcp => OscReceiver::start_tcp(&target, dispatcher.clone()),
+        }
+        .and_then(|_| {
+            std::thread::Builder::new()
+                .name("osc receiver bundles".to_string())
+                .spawn(move || dispatcher.run_delayed(rx))?;
+            Ok(())
+        });
+
+        if let Err(e) = res {
+            println!("osc receiver: can't listen to {target}: {e}");
+        }
+    }
+
+    fn start_udp<const BUFSIZE: usize, const NCHAN: usize>(
+        target: &str,
+        dispatcher: OscDispatcher<BUFSIZE, NCHAN>,
+    ) -> Result<(), anyhow::Error> {
+        let addr = SocketAddrV4::from_str(target)?;
+        let sock = UdpSocket::bind(addr)?;
+
+        println!("Listening to {addr} (udp)");
+
+        std::thread::Builder::new()
+            .name("osc receiver udp".to_string())
+            .spawn(move || {
+                let mut buf = [0u8; rosc::decoder::MTU];
+                let mut backoff = Duration::ZERO;
+                loop {
+                    match sock.recv_from(&mut buf) {
+                        Ok((size, from)) => {
+                            backoff = Duration::ZERO;
+                            dispatcher.handle_bytes(&buf[..size], &from.to_string())
+                        }
+                        Err(e) if is_transient(&e) => {
+                            // only complain once, and wait longer each time
+                            if backoff.is_zero() {
+                                println!("osc receiver: error receiving from socket: {e}");
                             }
+                            backoff = (backoff * 2)
+                                .max(Duration::from_millis(10))
+                                .min(MAX_BACKOFF);
+                            std::thread::sleep(backoff);
+                        }
+                        Err(e) => {
+                            println!("osc receiver: error receiving from socket, stopped: {e}");
+                            break;
+                        }
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+
+    fn start_tcp<const BUFSIZE: usize, const NCHAN: usize>(
+        target: &str,
+        dispatcher: OscDispatcher<BUFSIZE, NCHAN>,
+    ) -> Result<(), anyhow::Error> {
+        let addr = SocketAddrV4::from_str(target)?;
+        let listener = TcpListener::bind(addr)?;
+
+        println!("Listening to {addr} (tcp)");
+
+        std::thread::Builder::new()
+            .name("osc receiver tcp".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            let dispatcher = dispatcher.clone();
+                            std::thread::spawn(move || {
+                                OscReceiver::handle_tcp_stream(stream, dispatcher);
+                            });
                         }
-                        OscPacket::Bundle(bundle) => {
-                            println!("OSC Bundle: {:?}", bundle);
+                        Err(e) => {
+                            println!("osc receiver: can't accept connection: {e}");
                         }
                     }
                 }
+            })?;
+
+        Ok(())
+    }
+
+    fn handle_tcp_stream<const BUFSIZE: usize, const NCHAN: usize>(
+        mut stream: TcpStream,
+        dispatcher: OscDispatcher<BUFSIZE, NCHAN>,
+    ) {
+        let from = stream
+            .peer_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_default();
+        let mut slip = SlipDecoder::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break, // connection closed
+                Ok(size) => {
+                    for packet in slip.decode(&buf[..size]) {
+                        dispatcher.handle_bytes(&packet, &from);
+                    }
+                }
                 Err(e) => {
-                    println!("Error receiving from socket: {}", e);
-                    //break;
+                    println!("osc receiver: error reading from {from}: {e}");
+                    break;
                 }
             }
-        });
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_types::GlobalVariables;
+    use crate::parser::eval_from_str;
+    use crate::{OutputMode, SampleAndWavematrixSet};
+    use rosc::OscArray;
+
+    #[test]
+    fn test_slip_decode() {
+        let mut slip = SlipDecoder::new();
+        // packet split across chunks, with escaped bytes
+        assert!(slip
+            .decode(&[SLIP_END, 1, SLIP_ESC, SLIP_ESC_END, 2])
+            .is_empty());
+        let packets = slip.decode(&[SLIP_ESC, SLIP_ESC_ESC, SLIP_END, 3, SLIP_END]);
+        assert_eq!(packets, vec![vec![1, SLIP_END, 2, SLIP_ESC], vec![3]]);
+
+        // a packet that's too long is dropped, the next one gets through
+        assert!(slip.decode(&vec![1; SLIP_MAX_PACKET_SIZE + 10]).is_empty());
+        assert!(slip.decode(&[1, 2]).is_empty());
+        let packets = slip.decode(&[SLIP_END, 4, SLIP_END]);
+        assert_eq!(packets, vec![vec![4]]);
+    }
+
+    #[test]
+    fn test_due_bundles() {
+        let msg = |addr: &str| {
+            OscPacket::Message(OscMessage {
+                addr: addr.to_string(),
+                args: Vec::new(),
+            })
+        };
+        let now = SystemTime::now();
+        let mut pending = vec![
+            (now + Duration::from_secs(10), vec![msg("/later")]),
+            (now - Duration::from_millis(10), vec![msg("/b"), msg("/c")]),
+            (now - Duration::from_millis(20), vec![msg("/a")]),
+            (now - Duration::from_millis(10), vec![msg("/d")]),
+        ];
+
+        let due: Vec<String> = take_due_bundles(&mut pending, now)
+            .into_iter()
+            .map(|p| match p {
+                OscPacket::Message(m) => m.addr,
+                OscPacket::Bundle(_) => panic!("no bundles here"),
+            })
+            .collect();
+        assert_eq!(due, vec!["/a", "/b", "/c", "/d"]);
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_address_matches() {
+        assert!(address_matches("/foo/bar", "/foo/bar"));
+        assert!(address_matches("/foo/*", "/foo/bar"));
+        assert!(address_matches("/foo/bar", "/foo/{bar,baz}"));
+        assert!(!address_matches("/foo/*", "/bar/foo"));
+        assert!(!address_matches("/foo", "/foo/bar"));
+        // garbage doesn't match, and doesn't panic either
+        assert!(!address_matches("/foo", "foo[[]"));
+    }
+
+    #[test]
+    fn test_osc_to_typed() {
+        assert!(matches!(
+            osc_to_typed(&OscType::Int(3)),
+            TypedEntity::Comparable(Comparable::Float(f)) if f == 3.0
+        ));
+        assert!(matches!(
+            osc_to_typed(&OscType::Bool(true)),
+            TypedEntity::Comparable(Comparable::Boolean(true))
+        ));
+        assert!(matches!(
+            osc_to_typed(&OscType::Nil),
+            TypedEntity::Comparable(Comparable::Boolean(false))
+        ));
+
+        if let TypedEntity::Vec(v) = osc_to_typed(&OscType::Blob(vec![1, 255])) {
+            assert_eq!(v.len(), 2);
+            assert!(matches!(
+                *v[1],
+                TypedEntity::Comparable(Comparable::Float(f)) if f == 255.0
+            ));
+        } else {
+            panic!("blob should be a vec");
+        }
+
+        if let TypedEntity::Vec(v) = osc_to_typed(&OscType::Array(OscArray {
+            content: vec![
+                OscType::Float(1.0),
+                OscType::Nil,
+                OscType::String("a".into()),
+            ],
+        })) {
+            assert_eq!(v.len(), 3);
+        } else {
+            panic!("array should be a vec");
+        }
+    }
+
+    #[test]
+    fn test_nil_argument() {
+        let mut functions = FunctionMap::new();
+        let globals = sync::Arc::new(GlobalVariables::new());
+        let Ok(EvaluatedExpr::FunctionDefinition(name, fun)) = eval_from_str(
+            "(fun \"/pad\" (note vel) vel)",
+            &functions,
+            &globals,
+            SampleAndWavematrixSet::new(),
+            OutputMode::Stereo,
+        ) else {
+            panic!("not a function");
+        };
+        functions.usr_lib.insert(name, fun);
+
+        // the message isn't dropped, the nil is just #f
+        let args = [OscType::Int(60), OscType::Nil]
+            .iter()
+            .map(|val| EvaluatedExpr::Typed(osc_to_typed(val)))
+            .collect();
+        let results = function::call(
+            &functions.usr_lib["/pad"],
+            "/pad",
+            args,
+            &functions,
+            &globals,
+            SampleAndWavematrixSet::new(),
+            OutputMode::Stereo,
+        )
+        .unwrap();
+        assert!(matches!(
+            results.last(),
+            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                Comparable::Boolean(false)
+            )))
+        ));
     }
 }
@@ -1,8 +1,31 @@
 use rosc::encoder;
-use rosc::{OscMessage, OscPacket, OscType};
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
 
 use std::net;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::SchedulerClock;
+
+/// The special time tag meaning "immediately".
+pub const OSC_TIME_IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
+/// Translate a point in time on the given clock to an OSC time tag,
+/// so that the receiver can schedule it precisely.
+pub fn osc_time_at(clock: &dyn SchedulerClock, time: f64) -> OscTime {
+    let now = SystemTime::now();
+    let diff = time - clock.now();
+    let at = if diff >= 0.0 {
+        now.checked_add(Duration::from_secs_f64(diff))
+    } else {
+        now.checked_sub(Duration::from_secs_f64(-diff))
+    };
+    at.and_then(|t| OscTime::try_from(t).ok())
+        .unwrap_or(OSC_TIME_IMMEDIATELY)
+}
 
 pub struct OscSender {
     pub host_addr: net::SocketAddrV4,
@@ -27,4 +50,40 @@ impl OscSender {
         self.socket.send_to(&msg_buf_add, self.to_addr)?;
         Ok(())
     }
+
+    /// send the messages in a bundle, to be executed at the given time
+    pub fn send_bundle(
+        &self,
+        timetag: OscTime,
+        messages: Vec<OscMessage>,
+    ) -> Result<(), anyhow::Error> {
+        let msg_buf = encoder::encode(&OscPacket::Bundle(OscBundle {
+            timetag,
+            content: messages.into_iter().map(OscPacket::Message).collect(),
+        }))?;
+        self.socket.send_to(&msg_buf, self.to_addr)?;
+        Ok(())
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_osc_time_at() {
+        let clock = ManualClock::new();
+        clock.advance_to(10.0);
+
+        let sys_now = SystemTime::now();
+        let later = SystemTime::from(osc_time_at(&clock, 10.5));
+        let earlier = SystemTime::from(osc_time_at(&clock, 9.0));
+
+        let ahead = later.duration_since(sys_now).unwrap().as_secs_f64();
+        assert!((ahead - 0.5).abs() < 0.01);
+        let behind = sys_now.duration_since(earlier).unwrap().as_secs_f64();
+        assert!((behind - 1.0).abs() < 0.01);
+    }
 }
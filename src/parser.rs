@@ -3,11 +3,11 @@ use nom::{
     bytes::complete::{tag, take_while, take_while1},
     character::complete::{char, multispace0, multispace1},
     character::{is_alphanumeric, is_newline, is_space},
-    combinator::{cut, map, map_res, recognize},
+    combinator::{cut, map, map_opt, map_res, opt, recognize},
     error::{context, ErrorKind, VerboseError, VerboseErrorKind},
     multi::many0,
     number::complete::float,
-    sequence::{delimited, preceded, tuple},
+    sequence::{delimited, pair, preceded, tuple},
     Err, IResult, Parser,
 };
 
@@ -21,8 +21,18 @@ use crate::{
 };
 use crate::{Command, GlobalVariables, OutputMode, SampleAndWavematrixSet, TypedEntity};
 
+pub mod error;
 pub mod eval;
 
+pub use error::{EvalError, Span};
+use eval::conditional;
+use eval::function::{self, Function};
+use eval::macros::{self, Macro, MacroMap};
+
+/// Forms that look like function calls, but aren't in the standard library,
+/// as their arguments aren't simply evaluated one after another.
+pub const SPECIAL_FORMS: &[&str] = &["lambda", "if", "when", "cond", "and", "or", "macroexpand"];
+
 /// These are the basic building blocks of our casual lisp language.
 /// You might notice that there's no lists in this lisp ... not sure
 /// what to call it in that case ...
@@ -41,9 +51,37 @@ pub enum Atom {
 pub enum Expr {
     FunctionDefinition,
     VariableDefinition,
-    Constant(Atom),
-    Application(Box<Expr>, Vec<Expr>),
-    Definition(Box<Expr>, Vec<Expr>),
+    MacroDefinition,
+    Constant(Atom, Span),
+    Application(Box<Expr>, Vec<Expr>, Span),
+    Definition(Box<Expr>, Vec<Expr>, Span),
+}
+
+impl Expr {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Constant(_, span)
+            | Expr::Application(_, _, span)
+            | Expr::Definition(_, _, span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// while parsing, spans count the remaining input before and after the
+    /// expression, this turns them into offsets from the start
+    fn locate(&mut self, len: usize) {
+        match self {
+            Expr::Constant(_, span) => *span = Span::new(len - span.start, len - span.end),
+            Expr::Application(head, tail, span) | Expr::Definition(head, tail, span) => {
+                *span = Span::new(len - span.start, len - span.end);
+                head.locate(len);
+                for e in tail.iter_mut() {
+                    e.locate(len);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -58,12 +96,11 @@ pub enum EvaluatedExpr {
     SyncContext(SyncContext),
     Progn(Vec<EvaluatedExpr>),
     Match(Box<EvaluatedExpr>, Vec<(EvaluatedExpr, EvaluatedExpr)>),
-    // I don't really have an idea how to make functions,
-    // so for now I'll just store the non-evaluated Exprs
-    // and reduce them once the user calls the function ...
-    // that might make them macros rather than functions?
-    // Not sure ...
-    FunctionDefinition(String, Vec<String>, Vec<Expr>),
+    // named functions are stored in the function map,
+    // anonymous ones (from lambda) are values
+    FunctionDefinition(String, sync::Arc<Function>),
+    Closure(sync::Arc<Function>),
+    MacroDefinition(String, sync::Arc<Macro>),
     VariableDefinition(VariableId, TypedEntity),
     // everything else is a typed entity
     Typed(TypedEntity),
@@ -79,9 +116,11 @@ impl fmt::Debug for EvaluatedExpr {
             EvaluatedExpr::SyncContext(_) => write!(f, "EvaluatedExpr::SyncContext(_)"),
             EvaluatedExpr::Progn(_) => write!(f, "EvaluatedExpr::Progn"),
             EvaluatedExpr::Match(_, _) => write!(f, "EvaluatedExpr::Match"),
-            EvaluatedExpr::FunctionDefinition(_, _, _) => {
+            EvaluatedExpr::FunctionDefinition(_, _) => {
                 write!(f, "EvaluatedExpr::FunctionDefinition")
             }
+            EvaluatedExpr::Closure(_) => write!(f, "EvaluatedExpr::Closure"),
+            EvaluatedExpr::MacroDefinition(_, _) => write!(f, "EvaluatedExpr::MacroDefinition"),
             EvaluatedExpr::VariableDefinition(_, _) => {
                 write!(f, "EvaluatedExpr::VariableDefinition")
             }
@@ -92,7 +131,8 @@ impl fmt::Debug for EvaluatedExpr {
 // std_lib are hard-coded,
 // usr_lib is for user-defined functions ...
 pub struct FunctionMap {
-    pub usr_lib: HashMap<String, (Vec<String>, Vec<Expr>)>,
+    pub usr_lib: HashMap<String, sync::Arc<Function>>,
+    pub macros: MacroMap,
     pub std_lib: HashMap<
         String,
         fn(
@@ -101,7 +141,7 @@ pub struct FunctionMap {
             &sync::Arc<GlobalVariables>,
             SampleAndWavematrixSet,
             OutputMode,
-        ) -> Option<EvaluatedExpr>,
+        ) -> Result<EvaluatedExpr, EvalError>,
     >,
 }
 
@@ -110,6 +150,7 @@ impl FunctionMap {
         FunctionMap {
             std_lib: HashMap::new(),
             usr_lib: HashMap::new(),
+            macros: HashMap::new(),
         }
     }
 }
@@ -152,13 +193,17 @@ fn parse_boolean(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     ))(i)
 }
 
+/// whole words only, so functions like `letter` can still be called
 fn parse_definition(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
-    alt((
-        map(tag("fun"), |_| Expr::FunctionDefinition),
-        map(tag("callback"), |_| Expr::FunctionDefinition),
-        map(tag("let"), |_| Expr::VariableDefinition),
-        map(tag("defpart"), |_| Expr::VariableDefinition),
-    ))(i)
+    map_opt(
+        take_while1(valid_identifier_name_char),
+        |word: &str| match word {
+            "fun" | "callback" => Some(Expr::FunctionDefinition),
+            "let" | "defpart" => Some(Expr::VariableDefinition),
+            "defmacro" => Some(Expr::MacroDefinition),
+            _ => None,
+        },
+    )(i)
 }
 
 /// keywords are language constructs that start with a ':'
@@ -183,10 +228,22 @@ pub fn parse_symbol(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     )(i)
 }
 
-/// function names are language constructs that contain allowed function name chars
+/// function names are language constructs that contain allowed function name chars,
+/// predicates end with a question mark, i.e. `number?`, and there are the comparisons.
+/// Names from imported files have their namespace in front, i.e. `drums/beat`.
 fn parse_identifier(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     map(
-        context("identifer", take_while1(valid_identifier_name_char)),
+        context(
+            "identifer",
+            alt((
+                recognize(tuple((
+                    take_while1(valid_identifier_name_char),
+                    opt(pair(char('/'), take_while1(valid_identifier_name_char))),
+                    opt(char('?')),
+                ))),
+                alt((tag("<="), tag(">="), tag("<"), tag(">"), tag("="))),
+            )),
+        ),
         |sym_str: &str| Atom::Identifier(sym_str.to_string()),
     )(i)
 }
@@ -209,18 +266,55 @@ pub fn parse_float(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     }
 }
 
+/// Remember where something has been parsed from. As the parsers only see
+/// the rest of the input, this is the remaining length before and after,
+/// `parse_expr` turns it into actual offsets.
+fn spanned<'a, O, F>(
+    mut inner: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (O, Span), VerboseError<&'a str>>
+where
+    F: Parser<&'a str, O, VerboseError<&'a str>>,
+{
+    move |i: &'a str| {
+        let (rest, o) = inner.parse(i)?;
+        Ok((rest, (o, Span::new(i.len(), rest.len()))))
+    }
+}
+
 /// parse all the atoms
 fn parse_constant(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     map(
-        alt((
+        spanned(alt((
             parse_boolean,
             parse_float,
             parse_keyword,
             parse_symbol,
             parse_string,
             parse_identifier,
+        ))),
+        |(atom, span)| Expr::Constant(atom, span),
+    )(i)
+}
+
+/// `` `x ``, `,x` and `,@x` in macros are short for `(quasiquote x)`,
+/// `(unquote x)` and `(unquote-splicing x)`
+fn parse_quoted(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    map(
+        spanned(pair(
+            alt((
+                map(tag("`"), |_| "quasiquote"),
+                map(tag(",@"), |_| "unquote-splicing"),
+                map(tag(","), |_| "unquote"),
+            )),
+            parse_any_expr,
         )),
-        Expr::Constant,
+        |((form, expr), span)| {
+            Expr::Application(
+                Box::new(Expr::Constant(Atom::Identifier(form.to_string()), span)),
+                vec![expr],
+                span,
+            )
+        },
     )(i)
 }
 
@@ -247,35 +341,50 @@ where
 /// `tuple` is used to sequence parsers together, so we can translate this directly
 /// and then map over it to transform the output into an `Expr::Application`
 fn parse_application(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
-    let application_inner = map(
-        tuple((
-            parse_expr,
-            many0(alt((
-                preceded(multispace0, parse_application), // applications can follow one another without whitespace
-                preceded(multispace1, parse_constant), // constants are delimited by at least one whitespace
-            ))),
-        )),
-        |(head, tail)| match head {
-            Expr::FunctionDefinition => Expr::Definition(Box::new(head), tail),
-            Expr::VariableDefinition => Expr::Definition(Box::new(head), tail),
-            _ => Expr::Application(Box::new(head), tail),
-        },
-    );
+    let application_inner = tuple((
+        parse_any_expr,
+        many0(alt((
+            preceded(multispace0, parse_application), // applications can follow one another without whitespace
+            preceded(multispace0, parse_quoted),
+            preceded(multispace1, parse_constant), // constants are delimited by at least one whitespace
+        ))),
+    ));
     // finally, we wrap it in an s-expression
-    s_exp(application_inner)(i)
+    map(
+        spanned(s_exp(application_inner)),
+        |((head, tail), span)| match head {
+            Expr::FunctionDefinition => Expr::Definition(Box::new(head), tail, span),
+            Expr::VariableDefinition => Expr::Definition(Box::new(head), tail, span),
+            Expr::MacroDefinition => Expr::Definition(Box::new(head), tail, span),
+            _ => Expr::Application(Box::new(head), tail, span),
+        },
+    )(i)
+}
+
+fn parse_any_expr(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    alt((
+        parse_definition,
+        parse_application,
+        parse_quoted,
+        parse_constant,
+    ))(i)
 }
 
 /// We tie them all together again, making a top-level expression parser!
-/// This one generates the abstract syntax tree
+/// This one generates the abstract syntax tree, with the spans
+/// relative to the start of the input.
 pub fn parse_expr(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
-    alt((parse_definition, parse_application, parse_constant))(i)
+    parse_any_expr(i).map(|(rest, mut expr)| {
+        expr.locate(i.len());
+        (rest, expr)
+    })
 }
 
 // evaluate as argument identifiers, or better, constans only, no applications
 // or definitions
 pub fn eval_as_arg(e: &Expr) -> Option<EvaluatedExpr> {
     match e {
-        Expr::Constant(c) => Some(match c {
+        Expr::Constant(c, _) => Some(match c {
             Atom::Float(f) => EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(*f))),
             Atom::Symbol(s) => {
                 EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s.to_string())))
@@ -307,9 +416,9 @@ pub fn eval_expression(
     locals: Option<&HashMap<String, EvaluatedExpr>>,
     sample_set: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     match e {
-        Expr::Constant(c) => Some(match c {
+        Expr::Constant(c, _) => Ok(match c {
             Atom::Float(f) => EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(*f))),
             Atom::Symbol(s) => {
                 EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s.to_string())))
@@ -334,212 +443,382 @@ pub fn eval_expression(
                 }
             }
         }),
-        Expr::Application(head, tail) => {
-            // local variables aren't evaluated for the head, which is supposed to be the identifier
-            // in some context it might be nice to have the head procedurally generated as well,
-            // but it'd cause a whole lotta trouble right now and I have no desire currently to use it ...
-            // there's more explicit ways to do the whole thing ...
-            if let Some(EvaluatedExpr::Identifier(f)) =
-                eval_expression(head, functions, globals, None, sample_set.clone(), out_mode)
-            {
-                // check if we have this function ...
-                if functions.std_lib.contains_key(&f) {
-                    let mut reduced_tail = tail
-                        .iter()
-                        .map(|expr| {
-                            eval_expression(
-                                expr,
-                                functions,
-                                globals,
-                                locals,
-                                sample_set.clone(),
-                                out_mode,
-                            )
-                        })
-                        .collect::<Option<Vec<EvaluatedExpr>>>()?;
-                    // push function name
-                    reduced_tail.insert(0, EvaluatedExpr::Identifier(f.clone()));
-                    functions.std_lib[&f](
-                        functions,
-                        &mut reduced_tail,
-                        globals,
-                        sample_set,
-                        out_mode,
-                    )
-                } else if functions.usr_lib.contains_key(&f) {
-                    let (fun_arg_names, fun_expr) = functions.usr_lib.get(&f).unwrap().clone();
-
-                    if fun_arg_names.len() > tail.len() {
-                        // not enough arguments
-                        return None;
-                    }
-
-                    // FIRST, eval local args,
-                    // manual zip
-                    let mut local_args = HashMap::new();
-                    for (i, expr) in tail[..fun_arg_names.len()].iter().enumerate() {
-                        if let Some(res) = eval_expression(
-                            expr,
-                            functions,
-                            globals,
-                            None,
-                            sample_set.clone(),
-                            out_mode,
-                        ) {
-                            local_args.insert(fun_arg_names[i].clone(), res);
-                        }
-                    }
+        Expr::Application(head, tail, span) => eval_application(
+            head, tail, *span, functions, globals, locals, sample_set, out_mode,
+        ),
+        Expr::Definition(head, tail, span) => eval_definition(
+            head, tail, *span, functions, globals, locals, sample_set, out_mode,
+        ),
+        _ => Err(EvalError::InvalidDefinition {
+            message: "a definition needs to be in parens".to_string(),
+            span: e.span(),
+        }),
+    }
+}
 
-                    // THIRD
-                    let mut fun_tail = fun_expr
-                        .iter()
-                        .map(|expr| {
-                            eval_expression(
-                                expr,
-                                functions,
-                                globals,
-                                Some(&local_args),
-                                sample_set.clone(),
-                                out_mode,
-                            )
-                        })
-                        .collect::<Option<Vec<EvaluatedExpr>>>()?;
-
-                    // return last form result, cl-style
-                    fun_tail.pop()
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        }
-        Expr::Definition(head, tail) => match **head {
-            Expr::FunctionDefinition => {
-                let id = match eval_expression(
-                    &tail[0],
+/// Function calls. Split off from `eval_expression`, like definitions, to
+/// keep the stack frames small, as user-defined functions can recurse.
+#[allow(clippy::too_many_arguments)]
+fn eval_application(
+    head: &Expr,
+    tail: &[Expr],
+    span: Span,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    locals: Option<&HashMap<String, EvaluatedExpr>>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let eval_tail = || {
+        tail.iter()
+            .map(|expr| {
+                eval_expression(
+                    expr,
                     functions,
                     globals,
-                    None,
+                    locals,
                     sample_set.clone(),
                     out_mode,
-                ) {
-                    Some(EvaluatedExpr::Identifier(i)) => Some(i),
-                    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) => {
-                        Some(s)
-                    }
-                    _ => None,
-                };
-
-                if let Some(i) = id {
-                    // i hate this clone ...
-                    let mut tail_clone = tail.clone();
-
-                    // remove function name
-                    tail_clone.remove(0);
-
-                    let mut positional_args = Vec::new();
-                    let mut rem_args = false;
-                    // evaluate positional arguments ...
-                    if let Some(Expr::Application(head, fun_tail)) = tail_clone.first() {
-                        if let Some(EvaluatedExpr::Identifier(f)) = eval_as_arg(head) {
-                            positional_args.push(f);
-
-                            let reduced_tail = fun_tail
-                                .iter()
-                                .map(eval_as_arg)
-                                .collect::<Option<Vec<EvaluatedExpr>>>()?;
-
-                            for ee in reduced_tail {
-                                if let EvaluatedExpr::Identifier(ff) = ee {
-                                    positional_args.push(ff);
-                                }
-                            }
-
-                            rem_args = true;
-                            //}
-                        }
-                    }
+                )
+            })
+            .collect::<Result<Vec<EvaluatedExpr>, EvalError>>()
+    };
+
+    let not_a_function = || EvalError::Syntax {
+        message: "expected a function name".to_string(),
+        span: head.span().unwrap_or(span),
+    };
+
+    // the head is a function name, a local variable that holds a
+    // function (or the name of one), or an expression that returns one
+    let callee = match head {
+        Expr::Constant(Atom::Identifier(f), _) => match locals.and_then(|l| l.get(f)) {
+            Some(local @ (EvaluatedExpr::Closure(_) | EvaluatedExpr::Identifier(_))) => {
+                local.clone()
+            }
+            _ => EvaluatedExpr::Identifier(f.clone()),
+        },
+        Expr::Application(..) => eval_expression(
+            head,
+            functions,
+            globals,
+            locals,
+            sample_set.clone(),
+            out_mode,
+        )?,
+        _ => return Err(not_a_function()),
+    };
+
+    let f = match callee {
+        EvaluatedExpr::Identifier(f) => f,
+        EvaluatedExpr::Closure(fun) => {
+            let mut results = function::call(
+                &fun,
+                "lambda",
+                eval_tail()?,
+                functions,
+                globals,
+                sample_set,
+                out_mode,
+            )
+            .map_err(|e| e.moved_to(span))?;
+            return results.pop().ok_or(EvalError::Failed {
+                function: "lambda".to_string(),
+                message: "function body is empty".to_string(),
+                span: Some(span),
+            });
+        }
+        _ => return Err(not_a_function()),
+    };
+
+    // the special forms decide themselves what to evaluate, and when
+    match f.as_str() {
+        "lambda" => {
+            let env = locals.cloned().unwrap_or_default();
+            return Ok(EvaluatedExpr::Closure(sync::Arc::new(
+                function::parse_function(tail, env)?,
+            )));
+        }
+        "if" | "when" | "cond" | "and" | "or" => {
+            return conditional::special_form(
+                &f, tail, span, functions, globals, locals, sample_set, out_mode,
+            );
+        }
+        // the code a macro call turns into, as a string
+        "macroexpand" => {
+            let [expr] = tail else {
+                return Err(EvalError::MissingArgument {
+                    function: f,
+                    position: 1,
+                    expected: "a macro call".to_string(),
+                    span: Some(span),
+                });
+            };
+            let expanded = macros::expand(expr, &functions.macros)?;
+            return Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                Comparable::String(macros::source(&expanded)),
+            )));
+        }
+        "quasiquote" | "unquote" | "unquote-splicing" => {
+            return Err(EvalError::Failed {
+                function: f,
+                message: "only works in the body of a macro".to_string(),
+                span: Some(span),
+            });
+        }
+        _ => {}
+    }
 
-                    if rem_args {
-                        tail_clone.remove(0);
-                    }
+    // macros are expanded right before they're evaluated
+    if let Some(mac) = functions.macros.get(&f) {
+        let expanded = macros::expand_call(mac, &f, tail, span, &functions.macros)?;
+        return eval_expression(&expanded, functions, globals, locals, sample_set, out_mode);
+    }
+
+    // check if we have this function ...
+    if functions.std_lib.contains_key(&f) {
+        let mut reduced_tail = eval_tail()?;
+        // push function name
+        reduced_tail.insert(0, EvaluatedExpr::Identifier(f.clone()));
+        functions.std_lib[&f](functions, &mut reduced_tail, globals, sample_set, out_mode).map_err(
+            |e| {
+                let arg_spans: Vec<Span> = tail.iter().filter_map(|e| e.span()).collect();
+                e.in_function(&f, span, &arg_spans)
+            },
+        )
+    } else if let Some(fun) = functions.usr_lib.get(&f) {
+        // the arguments are evaluated where the function is called,
+        // the body where it has been defined
+        let mut results = function::call(
+            fun,
+            &f,
+            eval_tail()?,
+            functions,
+            globals,
+            sample_set,
+            out_mode,
+        )
+        .map_err(|e| e.moved_to(span))?;
+
+        // return last form result, cl-style
+        results.pop().ok_or(EvalError::Failed {
+            function: f,
+            message: "function body is empty".to_string(),
+            span: Some(span),
+        })
+    } else {
+        Err(EvalError::UnknownFunction {
+            name: f,
+            span: head.span(),
+        })
+    }
+}
 
-                    Some(EvaluatedExpr::FunctionDefinition(
-                        i,
-                        positional_args,
-                        tail_clone,
+/// fun, let and the like
+#[allow(clippy::too_many_arguments)]
+fn eval_definition(
+    head: &Expr,
+    tail: &[Expr],
+    span: Span,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    locals: Option<&HashMap<String, EvaluatedExpr>>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    match head {
+        Expr::FunctionDefinition => {
+            let id = match tail.first().map(|name| {
+                eval_expression(name, functions, globals, None, sample_set.clone(), out_mode)
+            }) {
+                Some(Ok(EvaluatedExpr::Identifier(i))) => i,
+                Some(Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s))))) => s,
+                _ => {
+                    return Err(EvalError::invalid_definition(
+                        "the function needs a name",
+                        tail.first().and_then(|name| name.span()).unwrap_or(span),
                     ))
-                } else {
-                    None
                 }
-            }
-            Expr::VariableDefinition => {
-                let id = match eval_expression(
-                    &tail[0],
+            };
+
+            // functions see the local variables where they're defined
+            let env = locals.cloned().unwrap_or_default();
+            Ok(EvaluatedExpr::FunctionDefinition(
+                id,
+                sync::Arc::new(function::parse_function(&tail[1..], env)?),
+            ))
+        }
+        Expr::VariableDefinition => {
+            // local variables, (let ((name value) ...) body ...)
+            if let Some(bindings @ Expr::Application(..)) = tail.first() {
+                return function::local_let(
+                    bindings,
+                    &tail[1..],
+                    span,
                     functions,
                     globals,
-                    None,
-                    sample_set.clone(),
+                    locals,
+                    sample_set,
                     out_mode,
-                ) {
-                    Some(EvaluatedExpr::Identifier(i)) => VariableId::Custom(i),
-                    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) => {
-                        // check whether it's a reserved symbol
-                        if crate::parser::eval::events::sound::map_symbolic_param_value(&s)
-                            .is_some()
-                            || crate::music_theory::from_string(&s).is_some()
-                        {
-                            return None;
-                        }
-                        VariableId::Symbol(s)
-                    }
-                    _ => {
-                        return None;
+                );
+            }
+
+            let id = match tail.first().map(|name| {
+                eval_expression(name, functions, globals, None, sample_set.clone(), out_mode)
+            }) {
+                Some(Ok(EvaluatedExpr::Identifier(i))) => VariableId::Custom(i),
+                Some(Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s))))) => {
+                    // check whether it's a reserved symbol
+                    if crate::parser::eval::events::sound::map_symbolic_param_value(&s).is_some()
+                        || crate::music_theory::from_string(&s).is_some()
+                    {
+                        return Err(EvalError::invalid_definition(
+                            &format!("'{s} is a reserved symbol"),
+                            tail[0].span().unwrap_or(span),
+                        ));
                     }
-                };
-
-                let mut reduced_tail = tail
-                    .iter()
-                    .map(|expr| {
-                        eval_expression(
-                            expr,
-                            functions,
-                            globals,
-                            locals,
-                            sample_set.clone(),
-                            out_mode,
-                        )
-                    })
-                    .collect::<Option<Vec<EvaluatedExpr>>>()?;
-
-                if let Some(EvaluatedExpr::Typed(te)) = reduced_tail.pop() {
-                    Some(EvaluatedExpr::VariableDefinition(id, te))
-                } else {
-                    None
+                    VariableId::Symbol(s)
                 }
+                _ => {
+                    return Err(EvalError::invalid_definition(
+                        "the variable needs a name",
+                        tail.first().and_then(|name| name.span()).unwrap_or(span),
+                    ));
+                }
+            };
+
+            let mut reduced_tail = tail
+                .iter()
+                .map(|expr| {
+                    eval_expression(
+                        expr,
+                        functions,
+                        globals,
+                        locals,
+                        sample_set.clone(),
+                        out_mode,
+                    )
+                })
+                .collect::<Result<Vec<EvaluatedExpr>, EvalError>>()?;
+
+            if let Some(EvaluatedExpr::Typed(te)) = reduced_tail.pop() {
+                Ok(EvaluatedExpr::VariableDefinition(id, te))
+            } else {
+                Err(EvalError::invalid_definition(
+                    "the variable needs a value",
+                    span,
+                ))
             }
-            _ => None,
-        },
-        _ => None,
+        }
+        Expr::MacroDefinition => {
+            let Some(Expr::Constant(Atom::Identifier(name), _)) = tail.first() else {
+                return Err(EvalError::invalid_definition(
+                    "the macro needs a name",
+                    tail.first().and_then(|name| name.span()).unwrap_or(span),
+                ));
+            };
+            Ok(EvaluatedExpr::MacroDefinition(
+                name.clone(),
+                sync::Arc::new(macros::parse_macro(&tail[1..], span)?),
+            ))
+        }
+        _ => Err(EvalError::invalid_definition("unknown definition", span)),
     }
 }
+
+/// the innermost paren that isn't closed (as byte offset), if any
+pub fn find_unclosed_paren(src: &str) -> Option<usize> {
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut in_comment = false;
+    for (pos, c) in src.char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => in_comment = true,
+            '(' if !in_string => open.push(pos),
+            ')' if !in_string => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.pop()
+}
+
+/// turn a parser error into something readable
+pub fn syntax_error(src: &str, e: nom::Err<VerboseError<&str>>) -> EvalError {
+    let e = match e {
+        Err::Error(e) | Err::Failure(e) => e,
+        Err::Incomplete(_) => {
+            return EvalError::Syntax {
+                message: "incomplete input".to_string(),
+                span: Span::new(src.len(), src.len()),
+            }
+        }
+    };
+
+    // the errors contain the rest of the input where they happened
+    let offset = e
+        .errors
+        .first()
+        .map(|(rest, _)| src.len() - rest.len())
+        .unwrap_or(0);
+
+    let context = e.errors.iter().find_map(|(_, kind)| {
+        if let VerboseErrorKind::Context(ctx) = kind {
+            Some(*ctx)
+        } else {
+            None
+        }
+    });
+
+    if context == Some("closing paren") && src[offset..].trim().is_empty() {
+        // nothing wrong, just not finished yet
+        let pos = find_unclosed_paren(src).unwrap_or(0);
+        EvalError::UnclosedParen {
+            span: Span::new(pos, pos + 1),
+        }
+    } else {
+        let end = src[offset..]
+            .char_indices()
+            .nth(1)
+            .map(|(i, _)| offset + i)
+            .unwrap_or(src.len());
+        EvalError::Syntax {
+            message: format!("expected {}", context.unwrap_or("an expression")),
+            span: Span::new(offset, end),
+        }
+    }
+}
+
+/// parse an expression, with the spans as offsets in `src`
+pub fn parse_from_str(src: &str) -> Result<Expr, EvalError> {
+    // preprocessing - remove all comments, but keep the offsets
+    // for the error messages ...
+    let re = Regex::new(r";[^\n]+\n").unwrap();
+    let src_nocomment = re.replace_all(src, |caps: &regex::Captures| {
+        format!("{}\n", " ".repeat(caps[0].len() - 1))
+    });
+    // the spans count from the end, so leading whitespace can be skipped
+    let (_, mut exp) =
+        parse_any_expr(src_nocomment.trim_start()).map_err(|e| syntax_error(&src_nocomment, e))?;
+    exp.locate(src_nocomment.len());
+    Ok(exp)
+}
+
 pub fn eval_from_str(
     src: &str,
     functions: &FunctionMap,
     globals: &sync::Arc<GlobalVariables>,
     sample_set: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Result<EvaluatedExpr, String> {
-    // preprocessing - remove all comments ...
-    let re = Regex::new(r";[^\n]+\n").unwrap();
-    let src_nocomment = re.replace_all(src, "\n");
-    parse_expr(&src_nocomment)
-        .map_err(|e: nom::Err<VerboseError<&str>>| format!("{e:#?}"))
-        .and_then(|(_, exp)| {
-            eval_expression(&exp, functions, globals, None, sample_set, out_mode)
-                .ok_or_else(|| "eval failed".to_string())
-        })
+) -> Result<EvaluatedExpr, EvalError> {
+    let exp = parse_from_str(src)?;
+    eval_expression(&exp, functions, globals, None, sample_set, out_mode)
 }
 
 #[cfg(test)]
@@ -618,7 +897,7 @@ mod tests {
                     panic!();
                 }
 
-                Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                     Comparable::Boolean(true),
                 )))
             });
@@ -641,7 +920,7 @@ mod tests {
                     panic!();
                 }
 
-                Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                     Comparable::Boolean(true),
                 )))
             });
@@ -705,23 +984,23 @@ mod tests {
     fn test_parse_atom_constant() {
         assert!(matches!(
             parse_constant("#t"),
-            Ok(("", Expr::Constant(Atom::Boolean(true))))
+            Ok(("", Expr::Constant(Atom::Boolean(true), _)))
         ));
         assert!(matches!(
             parse_constant("#f"),
-            Ok(("", Expr::Constant(Atom::Boolean(false))))
+            Ok(("", Expr::Constant(Atom::Boolean(false), _)))
         ));
         assert!(matches!(
             parse_constant("'test"),
-            Ok(("", Expr::Constant(Atom::Symbol(_))))
+            Ok(("", Expr::Constant(Atom::Symbol(_), _)))
         ));
         assert!(matches!(
             parse_constant(":test"),
-            Ok(("", Expr::Constant(Atom::Keyword(_))))
+            Ok(("", Expr::Constant(Atom::Keyword(_), _)))
         ));
         assert!(matches!(
             parse_constant("\"test\""),
-            Ok(("", Expr::Constant(Atom::String(_))))
+            Ok(("", Expr::Constant(Atom::String(_), _)))
         ));
     }
 
@@ -729,117 +1008,117 @@ mod tests {
     fn test_parse_expr() {
         assert!(matches!(
             parse_expr("#t"),
-            Ok(("", Expr::Constant(Atom::Boolean(true))))
+            Ok(("", Expr::Constant(Atom::Boolean(true), _)))
         ));
         assert!(matches!(
             parse_expr("#f"),
-            Ok(("", Expr::Constant(Atom::Boolean(false))))
+            Ok(("", Expr::Constant(Atom::Boolean(false), _)))
         ));
         assert!(matches!(
             parse_expr("'test"),
-            Ok(("", Expr::Constant(Atom::Symbol(_))))
+            Ok(("", Expr::Constant(Atom::Symbol(_), _)))
         ));
         assert!(matches!(
             parse_expr(":test"),
-            Ok(("", Expr::Constant(Atom::Keyword(_))))
+            Ok(("", Expr::Constant(Atom::Keyword(_), _)))
         ));
         assert!(matches!(
             parse_expr("\"test\""),
-            Ok(("", Expr::Constant(Atom::String(_))))
+            Ok(("", Expr::Constant(Atom::String(_), _)))
         ));
         assert!(matches!(
             parse_expr("(#t)"),
-            Ok(("", Expr::Application(_, _)))
+            Ok(("", Expr::Application(_, _, _)))
         ));
         assert!(matches!(
             parse_expr("('test)"),
-            Ok(("", Expr::Application(_, _)))
+            Ok(("", Expr::Application(_, _, _)))
         ));
         assert!(matches!(
             parse_expr("(:test)"),
-            Ok(("", Expr::Application(_, _)))
+            Ok(("", Expr::Application(_, _, _)))
         ));
         assert!(matches!(
             parse_expr("(\"test\")"),
-            Ok(("", Expr::Application(_, _)))
+            Ok(("", Expr::Application(_, _, _)))
         ));
 
-        if let Ok(("", Expr::Application(head, tail))) =
+        if let Ok(("", Expr::Application(head, tail, _))) =
             parse_expr("(text 'tar :lvl 1.0 :global #t :relate #f :boost (bounce 0 400))")
         {
-            if let Expr::Constant(Atom::Identifier(function_name)) = *head {
+            if let Expr::Constant(Atom::Identifier(function_name), _) = *head {
                 assert!(function_name == "text");
             } else {
                 panic!()
             }
 
             // SYMBOLS
-            if let Expr::Constant(Atom::Symbol(s)) = &tail[0] {
+            if let Expr::Constant(Atom::Symbol(s), _) = &tail[0] {
                 assert!(s == "tar");
             } else {
                 panic!();
             }
 
             // KEYWORDS
-            if let Expr::Constant(Atom::Keyword(k)) = &tail[1] {
+            if let Expr::Constant(Atom::Keyword(k), _) = &tail[1] {
                 assert!(k == "lvl");
             } else {
                 panic!();
             }
 
-            if let Expr::Constant(Atom::Keyword(k)) = &tail[3] {
+            if let Expr::Constant(Atom::Keyword(k), _) = &tail[3] {
                 assert!(k == "global");
             } else {
                 panic!();
             }
 
-            if let Expr::Constant(Atom::Keyword(k)) = &tail[5] {
+            if let Expr::Constant(Atom::Keyword(k), _) = &tail[5] {
                 assert!(k == "relate");
             } else {
                 panic!();
             }
 
-            if let Expr::Constant(Atom::Keyword(k)) = &tail[7] {
+            if let Expr::Constant(Atom::Keyword(k), _) = &tail[7] {
                 assert!(k == "boost");
             } else {
                 panic!();
             }
 
             // BOOLEANS
-            if let Expr::Constant(Atom::Boolean(b)) = &tail[4] {
+            if let Expr::Constant(Atom::Boolean(b), _) = &tail[4] {
                 assert!(b);
             } else {
                 panic!();
             }
 
-            if let Expr::Constant(Atom::Boolean(b)) = &tail[6] {
+            if let Expr::Constant(Atom::Boolean(b), _) = &tail[6] {
                 assert!(!b);
             } else {
                 panic!();
             }
 
             // FLOAT
-            if let Expr::Constant(Atom::Float(f)) = &tail[2] {
+            if let Expr::Constant(Atom::Float(f), _) = &tail[2] {
                 assert!(*f == 1.0);
             } else {
                 panic!();
             }
 
             // APPLICATION
-            if let Expr::Application(head2, tail2) = &tail[8] {
-                if let Expr::Constant(Atom::Identifier(function_name2)) = &**head2 {
+            if let Expr::Application(head2, tail2, _) = &tail[8] {
+                if let Expr::Constant(Atom::Identifier(function_name2), _) = &**head2 {
                     assert!(function_name2 == "bounce")
                 } else {
                     panic!()
                 }
                 // FLOAT
-                if let Expr::Constant(Atom::Float(f)) = &tail2[0] {
+                if let Expr::Constant(Atom::Float(f), _) = &tail2[0] {
                     assert!(*f == 0.0);
                 } else {
                     panic!();
                 }
                 // FLOAT
-                if let Expr::Constant(Atom::Float(f)) = &tail2[1] {
+                if let Expr::Constant(Atom::Float(f), _) = &tail2[1] {
                     assert!(*f == 400.0);
                 } else {
                     panic!();
@@ -851,4 +1130,108 @@ mod tests {
             panic!();
         }
     }
+
+    #[test]
+    fn test_eval_errors() {
+        let mut functions = FunctionMap::new();
+        let globals = sync::Arc::new(GlobalVariables::new());
+
+        functions
+            .std_lib
+            .insert("int".to_string(), eval::types::int);
+        functions
+            .std_lib
+            .insert("print".to_string(), eval::print::print);
+        functions
+            .std_lib
+            .insert("nuc".to_string(), eval::constructors::nuc::nuc);
+        functions
+            .std_lib
+            .insert("cyc".to_string(), eval::constructors::cyc::cyc);
+        functions
+            .std_lib
+            .insert("facts".to_string(), eval::constructors::facts::facts);
+        functions
+            .std_lib
+            .insert("sx".to_string(), eval::session::sync_context::sync_context);
+        functions.std_lib.insert(
+            "load-sample-set".to_string(),
+            eval::commands::load_sample_set,
+        );
+
+        let eval = |src: &str| {
+            eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            )
+        };
+
+        // spans are offsets into the original input, comments included
+        let src = ";; comment\n(int (sawx 100))";
+        match eval(src) {
+            Err(EvalError::UnknownFunction { name, span }) => {
+                assert_eq!(name, "sawx");
+                assert_eq!(span, Some(Span::new(17, 21)));
+            }
+            e => panic!("{e:?}"),
+        }
+
+        assert!(matches!(
+            eval("(int (print 1.0\n"),
+            Err(EvalError::UnclosedParen { span }) if span.start == 5
+        ));
+
+        assert!(matches!(eval("(int 1.0 ]"), Err(EvalError::Syntax { .. })));
+
+        // the failing argument is pointed out
+        let src = "(print (int 'x))";
+        match eval(src) {
+            Err(EvalError::InvalidArgument {
+                function,
+                position,
+                span,
+                ..
+            }) => {
+                assert_eq!(function, "int");
+                assert_eq!(position, 1);
+                assert_eq!(&src[span.unwrap().start..span.unwrap().end], "'x");
+            }
+            _ => panic!(),
+        }
+
+        assert!(matches!(
+            eval("(print)"),
+            Err(EvalError::MissingArgument { position: 1, .. })
+        ));
+
+        // generators need a name
+        for src in ["(nuc 100)", "(cyc \"bd ~\")"] {
+            assert!(matches!(
+                eval(src),
+                Err(EvalError::InvalidArgument { position: 1, .. })
+            ));
+        }
+
+        // missing arguments and keyword values are errors, not panics
+        assert!(matches!(
+            eval("(load-sample-set)"),
+            Err(EvalError::MissingArgument { position: 1, .. })
+        ));
+        assert!(matches!(
+            eval("(facts 'f 'lvl 0.5 :rnd)"),
+            Err(EvalError::MissingArgument { position: 5, .. })
+        ));
+        match eval("(sx 'ga #t :shift 'x)") {
+            Err(EvalError::InvalidArgument {
+                function, position, ..
+            }) => {
+                assert_eq!(function, "sx");
+                assert_eq!(position, 4);
+            }
+            e => panic!("{e:?}"),
+        }
+    }
 }
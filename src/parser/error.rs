@@ -0,0 +1,348 @@
+use std::fmt;
+
+/// Byte offsets into the source code, `end` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// line and column (both starting at 1) of the start
+    pub fn line_col(&self, src: &str) -> (usize, usize) {
+        let before = &src[..self.start.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let col = before
+            .rfind('\n')
+            .map(|nl| before[nl + 1..].chars().count())
+            .unwrap_or_else(|| before.chars().count())
+            + 1;
+        (line, col)
+    }
+}
+
+/// Everything that can go wrong between the source code and an evaluated
+/// expression. Argument positions start at 1, the function name itself
+/// doesn't count. The standard library functions don't know where they've
+/// been called from, so they leave span and function name empty, the
+/// evaluator fills them in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    /// the input ended before all parens were closed
+    UnclosedParen {
+        span: Span,
+    },
+    Syntax {
+        message: String,
+        span: Span,
+    },
+    UnknownFunction {
+        name: String,
+        span: Option<Span>,
+    },
+    MissingArgument {
+        function: String,
+        position: usize,
+        expected: String,
+        span: Option<Span>,
+    },
+    InvalidArgument {
+        function: String,
+        position: usize,
+        expected: String,
+        span: Option<Span>,
+    },
+    InvalidDefinition {
+        message: String,
+        span: Option<Span>,
+    },
+    /// the arguments are fine on their own, but don't make sense together
+    Failed {
+        function: String,
+        message: String,
+        span: Option<Span>,
+    },
+}
+
+impl EvalError {
+    /// the argument at `position` is missing or isn't what we expected
+    pub fn invalid_argument(position: usize, expected: &str) -> Self {
+        EvalError::InvalidArgument {
+            function: String::new(),
+            position,
+            expected: expected.to_string(),
+            span: None,
+        }
+    }
+
+    pub fn failed(message: &str) -> Self {
+        EvalError::Failed {
+            function: String::new(),
+            message: message.to_string(),
+            span: None,
+        }
+    }
+
+    pub fn invalid_definition(message: &str, span: Span) -> Self {
+        EvalError::InvalidDefinition {
+            message: message.to_string(),
+            span: Some(span),
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::UnclosedParen { span } | EvalError::Syntax { span, .. } => Some(*span),
+            EvalError::UnknownFunction { span, .. }
+            | EvalError::MissingArgument { span, .. }
+            | EvalError::InvalidArgument { span, .. }
+            | EvalError::InvalidDefinition { span, .. }
+            | EvalError::Failed { span, .. } => *span,
+        }
+    }
+
+    /// Fill in the function name and span, if they aren't known yet.
+    /// `arg_spans` are the spans of the arguments the function has been
+    /// called with, so an invalid argument can be pointed out directly.
+    /// If the position is beyond the arguments, it's missing.
+    pub fn in_function(self, name: &str, call: Span, arg_spans: &[Span]) -> Self {
+        match self {
+            EvalError::InvalidArgument {
+                function,
+                position,
+                expected,
+                span,
+            } if function.is_empty() => {
+                if position >= 1 && position <= arg_spans.len() {
+                    EvalError::InvalidArgument {
+                        function: name.to_string(),
+                        position,
+                        expected,
+                        span: span.or(Some(arg_spans[position - 1])),
+                    }
+                } else {
+                    EvalError::MissingArgument {
+                        function: name.to_string(),
+                        position,
+                        expected,
+                        span: span.or(Some(call)),
+                    }
+                }
+            }
+            EvalError::Failed {
+                function,
+                message,
+                span,
+            } if function.is_empty() => EvalError::Failed {
+                function: name.to_string(),
+                message,
+                span: span.or(Some(call)),
+            },
+            other => other.at(call),
+        }
+    }
+
+    /// Fill in the function name only, for functions that are called by
+    /// other functions (like `map` does), as there's no call to point to.
+    pub fn named(self, name: &str) -> Self {
+        match self {
+            EvalError::InvalidArgument {
+                function,
+                position,
+                expected,
+                span,
+            } if function.is_empty() => EvalError::InvalidArgument {
+                function: name.to_string(),
+                position,
+                expected,
+                span,
+            },
+            EvalError::Failed {
+                function,
+                message,
+                span,
+            } if function.is_empty() => EvalError::Failed {
+                function: name.to_string(),
+                message,
+                span,
+            },
+            other => other,
+        }
+    }
+
+    /// set the span if there isn't one yet
+    pub fn at(self, location: Span) -> Self {
+        self.with_span(|span| span.or(Some(location)))
+    }
+
+    /// Errors inside of user-defined functions point to the function body,
+    /// which isn't part of the code that's evaluated right now, so they're
+    /// moved to the call.
+    pub fn moved_to(self, location: Span) -> Self {
+        self.with_span(|_| Some(location))
+    }
+
+    /// For code that has been cut out of a bigger text (like the editor
+    /// does), move the span so it points into the whole text.
+    pub fn shifted(self, offset: usize) -> Self {
+        self.with_span(|span| span.map(|s| Span::new(s.start + offset, s.end + offset)))
+    }
+
+    fn with_span(self, f: impl FnOnce(Option<Span>) -> Option<Span>) -> Self {
+        match self {
+            EvalError::UnclosedParen { span } => EvalError::UnclosedParen {
+                span: f(Some(span)).unwrap_or(span),
+            },
+            EvalError::Syntax { message, span } => EvalError::Syntax {
+                message,
+                span: f(Some(span)).unwrap_or(span),
+            },
+            EvalError::UnknownFunction { name, span } => EvalError::UnknownFunction {
+                name,
+                span: f(span),
+            },
+            EvalError::MissingArgument {
+                function,
+                position,
+                expected,
+                span,
+            } => EvalError::MissingArgument {
+                function,
+                position,
+                expected,
+                span: f(span),
+            },
+            EvalError::InvalidArgument {
+                function,
+                position,
+                expected,
+                span,
+            } => EvalError::InvalidArgument {
+                function,
+                position,
+                expected,
+                span: f(span),
+            },
+            EvalError::InvalidDefinition { message, span } => EvalError::InvalidDefinition {
+                message,
+                span: f(span),
+            },
+            EvalError::Failed {
+                function,
+                message,
+                span,
+            } => EvalError::Failed {
+                function,
+                message,
+                span: f(span),
+            },
+        }
+    }
+
+    /// The error with its location in the source, and the line in question
+    /// with the failing part marked, for the REPL and the terminal.
+    pub fn report(&self, src: &str) -> String {
+        if let Some(span) = self.span() {
+            let (line, col) = span.line_col(src);
+            let line_str = src.lines().nth(line - 1).unwrap_or("");
+            let len = src
+                .get(span.start..span.end.max(span.start))
+                .map(|s| s.lines().next().unwrap_or("").chars().count())
+                .unwrap_or(1)
+                .max(1);
+            format!(
+                "error at line {line}, column {col}: {self}\n  {line_str}\n  {}{}",
+                " ".repeat(col - 1),
+                "^".repeat(len)
+            )
+        } else {
+            format!("error: {self}")
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UnclosedParen { .. } => write!(f, "missing closing paren"),
+            EvalError::Syntax { message, .. } => write!(f, "syntax error, {message}"),
+            EvalError::UnknownFunction { name, .. } => write!(f, "unknown function '{name}'"),
+            EvalError::MissingArgument {
+                function,
+                position,
+                expected,
+                ..
+            } => write!(
+                f,
+                "({function} ...) argument {position} is missing, expected {expected}"
+            ),
+            EvalError::InvalidArgument {
+                function,
+                position,
+                expected,
+                ..
+            } => write!(
+                f,
+                "({function} ...) argument {position} should be {expected}"
+            ),
+            EvalError::InvalidDefinition { message, .. } => {
+                write!(f, "invalid definition, {message}")
+            }
+            EvalError::Failed {
+                function, message, ..
+            } => write!(f, "({function} ...) {message}"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_function() {
+        let call = Span::new(0, 20);
+        let args = [Span::new(5, 8), Span::new(9, 12)];
+
+        let e = EvalError::invalid_argument(2, "a number").in_function("saw", call, &args);
+        assert_eq!(e.span(), Some(Span::new(9, 12)));
+        assert_eq!(e.to_string(), "(saw ...) argument 2 should be a number");
+
+        let e = EvalError::invalid_argument(3, "a number").in_function("saw", call, &args);
+        assert!(matches!(e, EvalError::MissingArgument { position: 3, .. }));
+        assert_eq!(e.span(), Some(call));
+
+        // nested errors already know where they're from
+        let inner = EvalError::invalid_argument(1, "a symbol").in_function("nuc", args[0], &[]);
+        assert_eq!(inner.clone().in_function("sx", call, &args), inner);
+    }
+
+    #[test]
+    fn test_report() {
+        let src = "(sx 'ga #t\n  (nuc 'da (sawx 100)))";
+        let e = EvalError::UnknownFunction {
+            name: "sawx".to_string(),
+            span: Some(Span::new(23, 27)),
+        };
+        assert_eq!(
+            e.report(src),
+            "error at line 2, column 13: unknown function 'sawx'\n    (nuc 'da (sawx 100)))\n              ^^^^"
+        );
+    }
+
+    #[test]
+    fn test_shifted() {
+        let e = EvalError::UnclosedParen {
+            span: Span::new(0, 1),
+        };
+        assert_eq!(e.shifted(10).span(), Some(Span::new(10, 11)));
+        assert_eq!(EvalError::failed("no").shifted(10).span(), None);
+    }
+}
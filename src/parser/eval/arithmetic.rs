@@ -1,5 +1,5 @@
 use crate::builtin_types::{Comparable, LazyArithmetic, LazyVal, TypedEntity};
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -38,9 +38,9 @@ pub fn add(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Add(collect_lazy_vals(tail)),
         )));
     }
@@ -54,7 +54,7 @@ pub fn add(
             result += f;
         }
     }
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -65,9 +65,9 @@ pub fn sub(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Sub(collect_lazy_vals(tail)),
         )));
     }
@@ -90,7 +90,7 @@ pub fn sub(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -101,9 +101,9 @@ pub fn mul(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Mul(collect_lazy_vals(tail)),
         )));
     }
@@ -126,7 +126,7 @@ pub fn mul(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -137,9 +137,9 @@ pub fn div(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Div(collect_lazy_vals(tail)),
         )));
     }
@@ -162,7 +162,7 @@ pub fn div(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -173,9 +173,9 @@ pub fn modulo(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Modulo(collect_lazy_vals(tail)),
         )));
     }
@@ -189,14 +189,14 @@ pub fn modulo(
         if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(b)))) =
             tail_drain.next()
         {
-            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                 Comparable::Float(a % b),
             )))
         } else {
-            None
+            Err(EvalError::invalid_argument(2, "a number"))
         }
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -206,9 +206,9 @@ pub fn pow(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Pow(collect_lazy_vals(tail)),
         )));
     }
@@ -222,14 +222,14 @@ pub fn pow(
         if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(b)))) =
             tail_drain.next()
         {
-            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                 Comparable::Float(a.powf(b)),
             )))
         } else {
-            None
+            Err(EvalError::invalid_argument(2, "a number"))
         }
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -239,9 +239,9 @@ pub fn max(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Mul(collect_lazy_vals(tail)),
         )));
     }
@@ -264,7 +264,7 @@ pub fn max(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -275,9 +275,9 @@ pub fn min(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     if needs_resolve(&tail[1..]) {
-        return Some(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
             LazyArithmetic::Mul(collect_lazy_vals(tail)),
         )));
     }
@@ -300,7 +300,7 @@ pub fn min(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
         Comparable::Float(result),
     )))
 }
@@ -7,7 +7,7 @@ use std::collections::BTreeSet;
 
 use ruffbox_synth::building_blocks::SynthParameterLabel;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{valid_identifier_name_char, EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -18,7 +18,7 @@ pub fn import_sample_set(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1).peekable();
 
     let mut url: Option<String> = None;
@@ -69,7 +69,7 @@ pub fn import_sample_set(
 
     // url has priority in case someone provided both ..
     if let Some(url_string) = url {
-        Some(EvaluatedExpr::Command(Command::ImportSampleSet(
+        Ok(EvaluatedExpr::Command(Command::ImportSampleSet(
             SampleResource::Url(url_string, checksum),
         )))
     } else {
@@ -79,6 +79,7 @@ pub fn import_sample_set(
                 checksum,
             )))
         })
+        .ok_or(EvalError::failed("needs a :url or a :file"))
     }
 }
 
@@ -89,7 +90,7 @@ pub fn load_sample_as_wavematrix(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
 
     let mut key: Option<String> = None;
@@ -148,7 +149,7 @@ pub fn load_sample_as_wavematrix(
         }
     }
     if key.is_some() && path.is_some() && matrix_size.is_some() {
-        Some(EvaluatedExpr::Command(Command::LoadSampleAsWavematrix(
+        Ok(EvaluatedExpr::Command(Command::LoadSampleAsWavematrix(
             key.unwrap(),
             path.unwrap(),
             method.unwrap(),
@@ -156,7 +157,7 @@ pub fn load_sample_as_wavematrix(
             start.unwrap(),
         )))
     } else {
-        None
+        Err(EvalError::failed("needs :key, :path and :size"))
     }
 }
 
@@ -166,7 +167,7 @@ pub fn freeze_buffer(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
 
     // on the user side,
@@ -199,7 +200,7 @@ pub fn freeze_buffer(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::FreezeBuffer(
+    Ok(EvaluatedExpr::Command(Command::FreezeBuffer(
         freezbuf, inbuf,
     )))
 }
@@ -210,7 +211,7 @@ pub fn load_sample(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
 
     let mut collect_keywords = false;
@@ -265,7 +266,7 @@ pub fn load_sample(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::LoadSample(
+    Ok(EvaluatedExpr::Command(Command::LoadSample(
         set,
         keywords,
         path,
@@ -279,14 +280,14 @@ pub fn load_sample_sets(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    let path = if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(n))) =
-        tail_drain.next().unwrap()
+    let path = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(n)))) =
+        tail_drain.next()
     {
         n
     } else {
-        "".to_string()
+        return Err(EvalError::invalid_argument(1, "a string (the folder)"));
     };
 
     let mut downmix_stereo = false;
@@ -300,7 +301,7 @@ pub fn load_sample_sets(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::LoadSampleSets(
+    Ok(EvaluatedExpr::Command(Command::LoadSampleSets(
         path,
         downmix_stereo,
     )))
@@ -312,14 +313,14 @@ pub fn load_sample_set(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    let path = if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(n))) =
-        tail_drain.next().unwrap()
+    let path = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(n)))) =
+        tail_drain.next()
     {
         n
     } else {
-        "".to_string()
+        return Err(EvalError::invalid_argument(1, "a string (the folder)"));
     };
 
     let mut downmix_stereo = false;
@@ -333,7 +334,7 @@ pub fn load_sample_set(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::LoadSampleSet(
+    Ok(EvaluatedExpr::Command(Command::LoadSampleSet(
         path,
         downmix_stereo,
     )))
@@ -345,9 +346,9 @@ pub fn tmod(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    Some(EvaluatedExpr::Command(Command::Tmod(
+    Ok(EvaluatedExpr::Command(Command::Tmod(
         match tail_drain.next() {
             Some(EvaluatedExpr::Typed(TypedEntity::Parameter(p))) => p,
             Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => {
@@ -364,9 +365,9 @@ pub fn latency(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    Some(EvaluatedExpr::Command(Command::Latency(
+    Ok(EvaluatedExpr::Command(Command::Latency(
         match tail_drain.next() {
             Some(EvaluatedExpr::Typed(TypedEntity::Parameter(p))) => p,
             Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => {
@@ -383,9 +384,9 @@ pub fn bpm(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    Some(EvaluatedExpr::Command(Command::Bpm(
+    Ok(EvaluatedExpr::Command(Command::Bpm(
         match tail_drain.next() {
             Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => {
                 60000.0 / f
@@ -401,9 +402,9 @@ pub fn default_duration(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    Some(EvaluatedExpr::Command(Command::DefaultDuration(
+    Ok(EvaluatedExpr::Command(Command::DefaultDuration(
         match tail_drain.next() {
             Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => f,
             _ => 200.0,
@@ -417,9 +418,9 @@ pub fn globres(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
-    Some(EvaluatedExpr::Command(Command::GlobRes(
+    Ok(EvaluatedExpr::Command(Command::GlobRes(
         match tail_drain.next() {
             Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => f,
             _ => 400000.0,
@@ -433,7 +434,7 @@ pub fn reverb(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
     let mut param_map = HashMap::new();
 
@@ -480,7 +481,7 @@ pub fn reverb(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::GlobalRuffboxParams(
+    Ok(EvaluatedExpr::Command(Command::GlobalRuffboxParams(
         param_map,
     )))
 }
@@ -491,7 +492,7 @@ pub fn delay(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
     let mut param_map = HashMap::new();
 
@@ -585,7 +586,7 @@ pub fn delay(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::GlobalRuffboxParams(
+    Ok(EvaluatedExpr::Command(Command::GlobalRuffboxParams(
         param_map,
     )))
 }
@@ -596,7 +597,7 @@ pub fn export_dot(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
 
     // filename
@@ -606,11 +607,11 @@ pub fn export_dot(
         {
             s
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a string (the file name)"));
         };
 
     match tail_drain.next() {
-        Some(EvaluatedExpr::Typed(TypedEntity::Generator(g))) => Some(EvaluatedExpr::Command(
+        Some(EvaluatedExpr::Typed(TypedEntity::Generator(g))) => Ok(EvaluatedExpr::Command(
             Command::ExportDotStatic(filename, g),
         )),
         Some(EvaluatedExpr::Keyword(k)) => {
@@ -625,14 +626,14 @@ pub fn export_dot(
                         id_tags.insert(si);
                     }
                     // collect next symbols
-                    Some(EvaluatedExpr::Command(Command::ExportDotRunning((
+                    Ok(EvaluatedExpr::Command(Command::ExportDotRunning((
                         filename, id_tags,
                     ))))
                 }
-                _ => None,
+                _ => Err(EvalError::invalid_argument(2, "a generator or :live")),
             }
         }
-        _ => None,
+        _ => Err(EvalError::invalid_argument(2, "a generator or :live")),
     }
 }
 
@@ -642,7 +643,7 @@ pub fn once(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let tail_drain = tail.drain(..).skip(1);
     let mut sound_events = Vec::new();
     let mut control_events = Vec::new();
@@ -657,7 +658,7 @@ pub fn once(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::Once(
+    Ok(EvaluatedExpr::Command(Command::Once(
         sound_events,
         control_events,
     )))
@@ -669,14 +670,14 @@ pub fn step_part(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Command(Command::StepPart(s)))
+        Ok(EvaluatedExpr::Command(Command::StepPart(s)))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a symbol (the part)"))
     }
 }
 
@@ -686,8 +687,8 @@ pub fn clear(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
-    Some(EvaluatedExpr::Command(Command::Clear))
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(Command::Clear))
 }
 
 pub fn connect_visualizer(
@@ -696,8 +697,8 @@ pub fn connect_visualizer(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
-    Some(EvaluatedExpr::Command(Command::ConnectVisualizer))
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(Command::ConnectVisualizer))
 }
 
 pub fn start_recording(
@@ -706,7 +707,7 @@ pub fn start_recording(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
     let prefix = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
         tail_drain.next()
@@ -730,7 +731,7 @@ pub fn start_recording(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::StartRecording(
+    Ok(EvaluatedExpr::Command(Command::StartRecording(
         prefix, rec_input,
     )))
 }
@@ -741,8 +742,8 @@ pub fn stop_recording(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
-    Some(EvaluatedExpr::Command(Command::StopRecording))
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(Command::StopRecording))
 }
 
 pub fn load_file(
@@ -751,13 +752,46 @@ pub fn load_file(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Command(Command::LoadFile(s)))
+        Ok(EvaluatedExpr::Command(Command::LoadFile(s)))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a string (the file name)"))
     }
 }
+
+/// (import "drums" :as 'dr), the namespace is the file name by default
+pub fn import(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(..).skip(1);
+    let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(name)))) =
+        tail_drain.next()
+    else {
+        return Err(EvalError::invalid_argument(1, "a string (the file name)"));
+    };
+
+    let mut namespace = None;
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::Keyword(k) if k == "as" => match tail_drain.next() {
+                Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                    Comparable::Symbol(s) | Comparable::String(s),
+                ))) if !s.is_empty() && s.chars().all(valid_identifier_name_char) => {
+                    namespace = Some(s)
+                }
+                _ => return Err(EvalError::invalid_argument(3, "a name for the namespace")),
+            },
+            _ => return Err(EvalError::invalid_argument(2, ":as")),
+        }
+    }
+
+    Ok(EvaluatedExpr::Command(Command::Import(name, namespace)))
+}
@@ -1,7 +1,7 @@
 use crate::builtin_types::*;
 use crate::generator_processor::GeneratorWrapperProcessor;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -38,10 +38,10 @@ pub fn compose(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     let last = tail.pop();
-    Some(match last {
+    Ok(match last {
         Some(EvaluatedExpr::Typed(TypedEntity::Generator(mut g))) => {
             let mut proc_or_mods = collect_compose(tail);
             let mut procs = Vec::new();
@@ -84,6 +84,6 @@ pub fn compose(
                 collect_compose(tail),
             ))
         }
-        _ => return None,
+        _ => return Err(EvalError::failed("nothing to compose")),
     })
 }
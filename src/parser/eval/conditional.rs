@@ -0,0 +1,356 @@
+//! `if`, `when`, `cond`, `and` and `or`. They're special forms rather than
+//! functions, as they only evaluate what's needed:
+//!
+//! ```lisp
+//! (if (gt a 0.5) (saw 200) (sqr 200))
+//! (when (eq note 60) (once (saw 100)) ...)
+//! (cond (lt a 0.3) 100 (lt a 0.6) 200 400) ;; the last one is the default
+//! (and (gt a 0) (lt a 1))
+//! (or a 'default)
+//! ```
+//!
+//! `#f` and 0 are false, everything else is true. If a condition depends on
+//! a global variable and the results are numbers, i.e.
+//! `(saw (if (gt x 0.5) 200 400))`, the decision is made each time the
+//! event is played, like with the arithmetic. Otherwise the variable is
+//! looked up right away, which is what callbacks need.
+
+use std::sync;
+
+use crate::builtin_types::*;
+use crate::parser::eval::function::Locals;
+use crate::parser::eval::logic::{lazy_val, resolve_truth, truth};
+use crate::parser::{eval_expression, Atom, EvalError, EvaluatedExpr, Expr, FunctionMap, Span};
+use crate::{OutputMode, SampleAndWavematrixSet};
+
+// what can be left until the event is played, without any side effects
+const LAZY_FORMS: &[&str] = &[
+    "add", "sub", "mul", "div", "mod", "pow", "lt", "gt", "lte", "gte", "eq", "<", ">", "<=", ">=",
+    "=", "not", "and", "or", "if", "cond",
+];
+
+fn is_lazy_candidate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Constant(Atom::Float(_) | Atom::Boolean(_) | Atom::Identifier(_), _) => true,
+        Expr::Application(head, args, _) => {
+            matches!(head.as_ref(), Expr::Constant(Atom::Identifier(f), _) if LAZY_FORMS.contains(&f.as_str()))
+                && args.iter().all(is_lazy_candidate)
+        }
+        _ => false,
+    }
+}
+
+fn boolean(b: bool) -> EvaluatedExpr {
+    EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Boolean(b)))
+}
+
+fn lazy(l: LazyArithmetic) -> EvaluatedExpr {
+    EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(l))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn special_form(
+    name: &str,
+    tail: &[Expr],
+    span: Span,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    locals: Option<&Locals>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let eval = |expr: &Expr| {
+        eval_expression(
+            expr,
+            functions,
+            globals,
+            locals,
+            sample_set.clone(),
+            out_mode,
+        )
+    };
+    let missing = |position: usize, expected: &str| EvalError::MissingArgument {
+        function: name.to_string(),
+        position,
+        expected: expected.to_string(),
+        span: Some(span),
+    };
+
+    match name {
+        "if" => {
+            let (Some(condition), Some(then)) = (tail.first(), tail.get(1)) else {
+                return Err(missing(tail.len() + 1, "a condition and a result"));
+            };
+            if tail.len() > 3 {
+                return Err(EvalError::Failed {
+                    function: name.to_string(),
+                    message: "expected (if condition then else)".to_string(),
+                    span: Some(span),
+                });
+            }
+            cond(&[(condition, then)], tail.get(2), globals, &eval)
+        }
+        "cond" => {
+            let clauses = tail.chunks_exact(2);
+            let default = clauses.remainder().first();
+            let clauses: Vec<(&Expr, &Expr)> = clauses.map(|c| (&c[0], &c[1])).collect();
+            cond(&clauses, default, globals, &eval)
+        }
+        "when" => {
+            let Some(condition) = tail.first() else {
+                return Err(missing(1, "a condition"));
+            };
+            let value = eval(condition)?;
+            let holds = truth(&value).unwrap_or_else(|| resolve_truth(value, globals));
+            if !holds {
+                return Ok(boolean(false));
+            }
+            let mut results = tail[1..]
+                .iter()
+                .map(eval)
+                .collect::<Result<Vec<EvaluatedExpr>, EvalError>>()?;
+            Ok(results.pop().unwrap_or(boolean(true)))
+        }
+        // the value that decides, like in other lisps
+        "and" | "or" => {
+            let is_and = name == "and";
+            let mut undecided = Vec::new();
+            let mut last = boolean(is_and);
+            for (i, arg) in tail.iter().enumerate() {
+                let value = eval(arg)?;
+                let holds = match truth(&value) {
+                    Some(holds) => holds,
+                    None if tail[i + 1..].iter().all(is_lazy_candidate) => {
+                        undecided.extend(lazy_val(&value));
+                        continue;
+                    }
+                    None => resolve_truth(value.clone(), globals),
+                };
+                if holds != is_and {
+                    return Ok(if truth(&value).is_some() {
+                        value
+                    } else {
+                        boolean(holds)
+                    });
+                }
+                last = value;
+            }
+
+            if undecided.is_empty() {
+                Ok(last)
+            } else if is_and {
+                Ok(lazy(LazyArithmetic::And(undecided)))
+            } else {
+                Ok(lazy(LazyArithmetic::Or(undecided)))
+            }
+        }
+        _ => Err(EvalError::UnknownFunction {
+            name: name.to_string(),
+            span: Some(span),
+        }),
+    }
+}
+
+/// The result of the first clause whose condition holds. Once there's a
+/// condition that depends on a variable, the rest is left for later if
+/// possible.
+fn cond(
+    clauses: &[(&Expr, &Expr)],
+    default: Option<&Expr>,
+    globals: &sync::Arc<GlobalVariables>,
+    eval: &dyn Fn(&Expr) -> Result<EvaluatedExpr, EvalError>,
+) -> Result<EvaluatedExpr, EvalError> {
+    for (i, (condition, result)) in clauses.iter().enumerate() {
+        let value = eval(condition)?;
+        match truth(&value) {
+            Some(true) => return eval(result),
+            Some(false) => {}
+            None => {
+                if let Some(l) = lazy_cond(&value, &clauses[i..], default, eval)? {
+                    return Ok(lazy(l));
+                }
+                if resolve_truth(value, globals) {
+                    return eval(result);
+                }
+            }
+        }
+    }
+
+    default.map_or(Ok(boolean(false)), eval)
+}
+
+/// The remaining clauses as nested lazy ifs, if all of them are numbers or
+/// arithmetic. The first condition has been evaluated already.
+fn lazy_cond(
+    first: &EvaluatedExpr,
+    clauses: &[(&Expr, &Expr)],
+    default: Option<&Expr>,
+    eval: &dyn Fn(&Expr) -> Result<EvaluatedExpr, EvalError>,
+) -> Result<Option<LazyArithmetic>, EvalError> {
+    let later_conditions = clauses.iter().skip(1).map(|(c, _)| *c);
+    let results = clauses.iter().map(|(_, r)| *r);
+    if !later_conditions
+        .chain(results)
+        .chain(default)
+        .all(is_lazy_candidate)
+    {
+        return Ok(None);
+    }
+
+    let mut branches = Vec::new();
+    for (i, (condition, result)) in clauses.iter().enumerate() {
+        let condition = if i == 0 {
+            lazy_val(first)
+        } else {
+            lazy_val(&eval(condition)?)
+        };
+        match (condition, lazy_val(&eval(result)?)) {
+            (Some(c), Some(r)) => branches.push((c, r)),
+            _ => return Ok(None),
+        }
+    }
+    let mut otherwise = match default {
+        Some(expr) => match lazy_val(&eval(expr)?) {
+            Some(v) => v,
+            None => return Ok(None),
+        },
+        None => LazyVal::Val(0.0),
+    };
+
+    for (condition, result) in branches.into_iter().rev() {
+        otherwise = LazyVal::Arith(LazyArithmetic::If(vec![condition, result, otherwise]));
+    }
+    match otherwise {
+        LazyVal::Arith(l) => Ok(Some(l)),
+        _ => Ok(None),
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::eval::resolver::resolve_lazy;
+    use crate::parser::eval_from_str;
+    use crate::standard_library::define_standard_library;
+
+    #[test]
+    fn test_conditionals() {
+        let mut functions = define_standard_library();
+        let globals = sync::Arc::new(GlobalVariables::new());
+
+        for src in [
+            "(fun fact (n) (if (lt n 2) 1 (mul n (fact (sub n 1)))))",
+            // names can start like definitions
+            "(fun letter (x) x)",
+        ] {
+            match eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            ) {
+                Ok(EvaluatedExpr::FunctionDefinition(name, fun)) => {
+                    functions.usr_lib.insert(name, fun);
+                }
+                e => panic!("{e:?}"),
+            }
+        }
+
+        let eval = |src: &str| {
+            eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            )
+        };
+        let value = |src: &str| match eval(src) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(c))) => c,
+            e => panic!("{src}: {e:?}"),
+        };
+        let lazy = |src: &str| match eval(src) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(l))) => resolve_lazy(l, &globals),
+            e => panic!("{src}: {e:?}"),
+        };
+
+        assert_eq!(value("(if (lt 1 2) 10 20)"), Comparable::Float(10.0));
+        assert_eq!(value("(if 0 10 20)"), Comparable::Float(20.0));
+        assert_eq!(value("(if #f 10)"), Comparable::Boolean(false));
+        assert_eq!(
+            value("(cond (gt 1 2) 1 (eq 'a 'a) 2 3)"),
+            Comparable::Float(2.0)
+        );
+        assert_eq!(value("(cond #f 1 3)"), Comparable::Float(3.0));
+        assert_eq!(value("(when (>= 3 2) 1 2)"), Comparable::Float(2.0));
+        assert_eq!(value("(and 1 2)"), Comparable::Float(2.0));
+        assert_eq!(value("(and 1 #f 2)"), Comparable::Boolean(false));
+        assert_eq!(value("(or #f 0 'x)"), Comparable::Symbol("x".to_string()));
+        assert_eq!(value("(< 1 2 3)"), Comparable::Boolean(true));
+        assert_eq!(value("(< 1 3 2)"), Comparable::Boolean(false));
+        assert_eq!(value("(= \"a\" \"a\")"), Comparable::Boolean(true));
+        assert_eq!(value("(not 0)"), Comparable::Boolean(true));
+        assert_eq!(value("(fact 5)"), Comparable::Float(120.0));
+        assert_eq!(value("(letter 3)"), Comparable::Float(3.0));
+
+        // only what's needed is evaluated
+        assert_eq!(value("(and #f (nope))"), Comparable::Boolean(false));
+        assert_eq!(value("(if #t 1 (nope))"), Comparable::Float(1.0));
+
+        assert_eq!(value("(number? 1)"), Comparable::Boolean(true));
+        assert_eq!(value("(symbol? \"a\")"), Comparable::Boolean(false));
+        assert_eq!(value("(function? fact)"), Comparable::Boolean(true));
+        assert_eq!(
+            value("(function? (lambda (x) x))"),
+            Comparable::Boolean(true)
+        );
+        assert_eq!(value("(keyword? :dur)"), Comparable::Boolean(true));
+
+        assert!(matches!(
+            eval("(lt 1 'a)"),
+            Err(EvalError::InvalidArgument { position: 2, .. })
+        ));
+
+        // with a global variable, numbers are decided when they're played ...
+        globals.insert(
+            VariableId::Custom("x".to_string()),
+            TypedEntity::Comparable(Comparable::Float(0.7)),
+        );
+        assert_eq!(lazy("(if (gt x 0.5) 200 400)"), 200.0);
+        assert_eq!(lazy("(add (if (gt x 0.5) 1 2) 1)"), 2.0);
+        assert_eq!(lazy("(and (gt x 0) (lt x 1))"), 1.0);
+        globals.insert(
+            VariableId::Custom("x".to_string()),
+            TypedEntity::Comparable(Comparable::Float(0.4)),
+        );
+        assert_eq!(lazy("(if (gt x 0.5) 200 400)"), 400.0);
+        assert_eq!(lazy("(cond (lt x 0.3) 100 (lt x 0.6) 200 400)"), 200.0);
+        assert_eq!(lazy("(not (gt x 0.5))"), 1.0);
+
+        // ... anything else right away
+        assert_eq!(
+            value("(if (gt x 0.5) 'a 'b)"),
+            Comparable::Symbol("b".to_string())
+        );
+        assert_eq!(
+            value("(when (lt x 0.5) 'yes)"),
+            Comparable::Symbol("yes".to_string())
+        );
+
+        // and symbols can only be compared right away
+        globals.insert(
+            VariableId::Custom("mode".to_string()),
+            TypedEntity::Comparable(Comparable::Symbol("fast".to_string())),
+        );
+        assert_eq!(value("(eq mode 'fast)"), Comparable::Boolean(true));
+        assert_eq!(value("(= 'slow mode)"), Comparable::Boolean(false));
+        assert_eq!(
+            value("(if (eq mode 'fast) 'a 'b)"),
+            Comparable::Symbol("a".to_string())
+        );
+        // numbers still wait
+        assert_eq!(lazy("(eq x 0.4)"), 1.0);
+    }
+}
@@ -12,7 +12,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync;
 use vom_rs::pfa::{Pfa, Rule};
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 
 pub fn cyc(
     functions: &FunctionMap,
@@ -20,7 +20,7 @@ pub fn cyc(
     globals: &sync::Arc<GlobalVariables>,
     sample_set: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     let mut tail_drain = tail.drain(1..).peekable();
 
@@ -30,7 +30,7 @@ pub fn cyc(
     {
         n.clone()
     } else {
-        "".to_string()
+        return Err(EvalError::invalid_argument(1, "a symbol (the name)"));
     };
 
     tail_drain.next();
@@ -385,7 +385,7 @@ pub fn cyc(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
@@ -11,7 +11,7 @@ use crate::generator::Generator;
 use crate::markov_sequence_generator::MarkovSequenceGenerator;
 use crate::parameter::*;
 use crate::parser::eval::resolver::resolve_globals;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn facts(
@@ -20,10 +20,11 @@ pub fn facts(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
+    let args = tail.len() - 1;
     let mut tail_drain = tail.drain(1..);
 
     // name is the first symbol
@@ -32,7 +33,7 @@ pub fn facts(
     {
         n
     } else {
-        return None;
+        return Err(EvalError::invalid_argument(1, "a symbol (the name)"));
     };
 
     // the param to be factorized
@@ -41,7 +42,7 @@ pub fn facts(
     {
         n
     } else {
-        return None;
+        return Err(EvalError::invalid_argument(2, "a symbol (the parameter)"));
     };
 
     let dur: DynVal = if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(d)) = globals
@@ -65,10 +66,14 @@ pub fn facts(
         match c {
             EvaluatedExpr::Keyword(k) => match k.as_str() {
                 "rnd" => {
-                    if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n))) =
-                        tail_drain.next().unwrap()
+                    let position = args - tail_drain.len() + 1;
+                    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(
+                        n,
+                    )))) = tail_drain.next()
                     {
                         randomize_chance = n;
+                    } else {
+                        return Err(EvalError::invalid_argument(position, "a number after :rnd"));
                     }
                 }
                 "keep" => {
@@ -108,7 +113,7 @@ pub fn facts(
     }
 
     if ev_vecs.is_empty() {
-        return None;
+        return Err(EvalError::failed("needs at least one event or value"));
     }
 
     /////////////////////////////////
@@ -183,7 +188,7 @@ pub fn facts(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
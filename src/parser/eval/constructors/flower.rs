@@ -10,7 +10,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync;
 use vom_rs::pfa::{Pfa, Rule};
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn flower(
@@ -19,10 +19,11 @@ pub fn flower(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
+    let args = tail.len() - 1;
     let mut tail_drain = tail.drain(1..);
 
     // name is the first symbol
@@ -177,24 +178,39 @@ pub fn flower(
                     continue;
                 }
                 "rep" => {
-                    if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n))) =
-                        tail_drain.next().unwrap()
+                    let position = args - tail_drain.len() + 1;
+                    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(
+                        n,
+                    )))) = tail_drain.next()
                     {
                         repetition_chance = n;
+                    } else {
+                        return Err(EvalError::invalid_argument(position, "a number after :rep"));
                     }
                 }
                 "rnd" => {
-                    if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n))) =
-                        tail_drain.next().unwrap()
+                    let position = args - tail_drain.len() + 1;
+                    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(
+                        n,
+                    )))) = tail_drain.next()
                     {
                         randomize_chance = n;
+                    } else {
+                        return Err(EvalError::invalid_argument(position, "a number after :rnd"));
                     }
                 }
                 "max-rep" => {
-                    if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n))) =
-                        tail_drain.next().unwrap()
+                    let position = args - tail_drain.len() + 1;
+                    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(
+                        n,
+                    )))) = tail_drain.next()
                     {
                         max_repetitions = n;
+                    } else {
+                        return Err(EvalError::invalid_argument(
+                            position,
+                            "a number after :max-rep",
+                        ));
                     }
                 }
                 "keep" => {
@@ -365,7 +381,7 @@ pub fn flower(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
@@ -10,7 +10,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync;
 use vom_rs::pfa;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn rule(
@@ -19,7 +19,7 @@ pub fn rule(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
@@ -31,7 +31,7 @@ pub fn rule(
         {
             s.chars().collect()
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a symbol (the source)"));
         };
 
     let sym_vec: Vec<char> =
@@ -40,7 +40,7 @@ pub fn rule(
         {
             s.chars().collect()
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(2, "a symbol (the destination)"));
         };
 
     let def_dur: f32 = if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(d)) = globals
@@ -73,7 +73,7 @@ pub fn rule(
             def_dur as u64
         };
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Rule(Rule {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Rule(Rule {
         source: source_vec,
         symbol: sym_vec[0],
         probability,
@@ -87,7 +87,7 @@ pub fn infer(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
@@ -213,7 +213,7 @@ pub fn infer(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
@@ -10,7 +10,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync;
 use vom_rs::pfa::{Pfa, Rule};
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn nuc(
@@ -19,7 +19,7 @@ pub fn nuc(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     let mut tail_drain = tail.drain(1..);
 
@@ -29,7 +29,7 @@ pub fn nuc(
     {
         n
     } else {
-        "".to_string()
+        return Err(EvalError::invalid_argument(1, "a symbol (the name)"));
     };
 
     let mut event_mapping = BTreeMap::<char, Vec<SourceEvent>>::new();
@@ -57,6 +57,15 @@ pub fn nuc(
             EvaluatedExpr::Typed(TypedEntity::ControlEvent(c)) => {
                 ev_vec.push(SourceEvent::Control(c))
             }
+            EvaluatedExpr::Typed(TypedEntity::Vec(v)) => {
+                for x in v {
+                    match *x {
+                        TypedEntity::SoundEvent(e) => ev_vec.push(SourceEvent::Sound(e)),
+                        TypedEntity::ControlEvent(c) => ev_vec.push(SourceEvent::Control(c)),
+                        _ => {}
+                    }
+                }
+            }
             EvaluatedExpr::Keyword(k) => match k.as_str() {
                 "dur" => match tail_drain.next() {
                     Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n)))) => {
@@ -106,7 +115,7 @@ pub fn nuc(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
@@ -142,7 +151,7 @@ mod tests {
             .std_lib
             .insert("nuc".to_string(), eval::constructors::nuc::nuc);
         functions.std_lib.insert("bd".to_string(), |_, _, _, _, _| {
-            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                 Comparable::String("bd".to_string()),
             )))
         });
@@ -10,7 +10,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync;
 use vom_rs::pfa::{Pfa, Rule};
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn stages(
@@ -19,10 +19,11 @@ pub fn stages(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
+    let args = tail.len() - 1;
     let mut tail_drain = tail.drain(1..);
 
     // name is the first symbol
@@ -91,10 +92,14 @@ pub fn stages(
                     }
                 }
                 "rnd" => {
-                    if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n))) =
-                        tail_drain.next().unwrap()
+                    let position = args - tail_drain.len() + 1;
+                    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(
+                        n,
+                    )))) = tail_drain.next()
                     {
                         randomize_chance = n;
+                    } else {
+                        return Err(EvalError::invalid_argument(position, "a number after :rnd"));
                     }
                 }
                 "keep" => {
@@ -276,7 +281,7 @@ pub fn stages(
     let mut id_tags = BTreeSet::new();
     id_tags.insert(name.clone());
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
+    Ok(EvaluatedExpr::Typed(TypedEntity::Generator(Generator {
         id_tags,
         root_generator: MarkovSequenceGenerator {
             name,
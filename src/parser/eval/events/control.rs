@@ -1,6 +1,6 @@
 use crate::builtin_types::TypedEntity;
 use crate::event::*;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
 
 use std::collections::BTreeSet;
@@ -12,7 +12,7 @@ pub fn control(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut sync_contexts = Vec::new();
     let mut commands = Vec::new();
 
@@ -28,7 +28,7 @@ pub fn control(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::ControlEvent(
+    Ok(EvaluatedExpr::Typed(TypedEntity::ControlEvent(
         ControlEvent {
             tags: BTreeSet::new(),
             ctx: if sync_contexts.is_empty() {
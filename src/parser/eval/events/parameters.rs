@@ -2,7 +2,7 @@ use crate::builtin_types::{Comparable, TypedEntity};
 use crate::event::{Event, EventOperation};
 use crate::music_theory;
 use crate::parameter::{DynVal, ParameterValue};
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::sample_set::SampleLookup;
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
 
@@ -16,7 +16,7 @@ pub fn sample_keys(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
 
     // get function name, check which parameter we're dealing with
@@ -37,7 +37,7 @@ pub fn sample_keys(
             EventOperation::Replace
         }
     } else {
-        return None;
+        return Err(EvalError::failed("called without a name"));
     };
 
     let mut keyword_set = HashSet::new();
@@ -53,7 +53,7 @@ pub fn sample_keys(
     // an "empty" lookup to be merged later down the line ...
     ev.sample_lookup = Some(SampleLookup::Key("".to_string(), keyword_set));
 
-    Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
 }
 
 pub fn sample_number(
@@ -62,7 +62,7 @@ pub fn sample_number(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
 
     // get function name, check which parameter we're dealing with
@@ -85,7 +85,7 @@ pub fn sample_number(
             EventOperation::Replace
         }
     } else {
-        return None;
+        return Err(EvalError::failed("called without a name"));
     };
 
     let snum = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
@@ -93,7 +93,7 @@ pub fn sample_number(
     {
         f as usize
     } else {
-        return None;
+        return Err(EvalError::invalid_argument(1, "a number"));
     };
 
     let mut ev = Event::with_name_and_operation("snum".to_string(), op);
@@ -101,7 +101,7 @@ pub fn sample_number(
     // an "empty" lookup to be merged later down the line ...
     ev.sample_lookup = Some(SampleLookup::N("".to_string(), snum));
 
-    Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
 }
 
 pub fn random_sample(
@@ -110,13 +110,13 @@ pub fn random_sample(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut ev = Event::with_name_and_operation("randsam".to_string(), EventOperation::Replace);
 
     // an "empty" lookup to be merged later down the line ...
     ev.sample_lookup = Some(SampleLookup::Random("".to_string()));
 
-    Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
 }
 
 #[allow(clippy::excessive_precision)]
@@ -126,7 +126,7 @@ pub fn transpose(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).skip(1);
 
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n)))) =
@@ -152,9 +152,9 @@ pub fn transpose(
             SynthParameterLabel::PitchFrequency.into(),
             ParameterValue::Scalar(DynVal::with_value(factor)),
         );
-        Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+        Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number (semitones)"))
     }
 }
 
@@ -164,7 +164,7 @@ pub fn parameter(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
 
     // get function name, check which parameter we're dealing with
@@ -241,17 +241,22 @@ pub fn parameter(
                     } else {
                         ev.params.insert(param_key, p);
                     }
+                } else {
+                    return Err(EvalError::invalid_argument(
+                        1,
+                        "a number, a parameter or a known symbol",
+                    ));
                 }
 
                 //println!("{:?}", ev);
-                Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+                Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
             } else {
-                None
+                Err(EvalError::invalid_argument(1, "a value"))
             }
         } else {
-            None
+            Err(EvalError::failed("unknown parameter"))
         }
     } else {
-        None
+        Err(EvalError::failed("called without a name"))
     }
 }
@@ -3,7 +3,7 @@ use crate::event::{Event, EventOperation};
 use crate::event_helpers::map_parameter;
 use crate::music_theory;
 use crate::parameter::{DynVal, ParameterValue};
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::sample_set::SampleLookup;
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet, VariableId};
 
@@ -374,7 +374,7 @@ pub fn sound(
     _: &sync::Arc<GlobalVariables>,
     sample_set: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..).peekable();
 
     // get the function name ...
@@ -382,7 +382,7 @@ pub fn sound(
         f
     } else {
         // nothing to do ...
-        return None;
+        return Err(EvalError::failed("called without a name"));
     };
 
     // here's where the sound events are taken apart ...
@@ -487,6 +487,24 @@ pub fn sound(
             multi_synth_defaults(&mut ev);
             ev
         }
+        "midi-note" => {
+            let mut ev =
+                Event::with_name_and_operation("midi-note".to_string(), EventOperation::Replace);
+            // numbers are midi notes here, everything else is handled like a pitch
+            if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(n)))) =
+                tail_drain.peek()
+            {
+                let nn = *n;
+                tail_drain.next();
+                ev.params.insert(
+                    SynthParameterLabel::PitchNote.into(),
+                    ParameterValue::Scalar(DynVal::with_value(nn)),
+                );
+            } else {
+                get_pitch_param(&mut ev, &mut tail_drain);
+            }
+            ev
+        }
         "silence" => Event::with_name_and_operation("silence".to_string(), EventOperation::Replace),
         "~" => Event::with_name_and_operation("silence".to_string(), EventOperation::Replace),
         "feedr" => {
@@ -552,7 +570,7 @@ pub fn sound(
 
                 ev // return event
             } else {
-                return None;
+                return Err(EvalError::failed("no samples in this sample set"));
             }
         }
     };
@@ -566,6 +584,20 @@ pub fn sound(
                 ev.tags.insert(s.clone());
                 tail_drain.next();
             }
+        } else if ev.name == "midi-note" && k == "vel" {
+            // midi velocity is stored as level, but not scaled
+            ev.params.insert(
+                SynthParameterLabel::EnvelopeLevel.into(),
+                collect_param_value(&mut tail_drain),
+            );
+        } else if ev.name == "midi-note" && k == "ch" {
+            if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(ch)))) =
+                tail_drain.next()
+            {
+                ev.midi_channel = Some(ch.clamp(1.0, 16.0) as u8);
+            } else {
+                return Err(EvalError::failed(":ch needs a number (1 to 16)"));
+            }
         } else if k.starts_with("wm") {
             // use start_with to account for possible indices
             // wavematrix lookup
@@ -590,7 +622,7 @@ pub fn sound(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev)))
 }
 
 #[cfg(test)]
@@ -630,4 +662,46 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_eval_midi_note() {
+        let snippet = "(midi-note 60 :vel 100 :ch 2 :dur 200)";
+        let mut functions = FunctionMap::new();
+        let sample_set = SampleAndWavematrixSet::new();
+
+        functions
+            .std_lib
+            .insert("midi-note".to_string(), eval::events::sound::sound);
+
+        let globals = sync::Arc::new(GlobalVariables::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::SoundEvent(ev))) => {
+                assert_eq!(ev.name, "midi-note");
+                // the channel isn't a synth parameter
+                assert_eq!(ev.midi_channel, Some(2));
+                assert!(!ev
+                    .params
+                    .contains_key(&SynthParameterLabel::ChannelPosition.into()));
+                for (label, val) in [
+                    (SynthParameterLabel::PitchNote, 60.0),
+                    (SynthParameterLabel::EnvelopeLevel, 100.0),
+                    (SynthParameterLabel::Duration, 200.0),
+                ] {
+                    if let Some(ParameterValue::Scalar(p)) = ev.params.get(&label.into()) {
+                        assert_eq!(p.static_val, val);
+                    } else {
+                        panic!()
+                    }
+                }
+            }
+            _ => panic!(),
+        }
+    }
 }
@@ -0,0 +1,444 @@
+//! User-defined functions, named ones from `fun` (or `callback`) and
+//! anonymous ones from `lambda`, which are values like any other. Both
+//! are closures, the body sees the local variables of the place where the
+//! function has been defined, not the ones of the place it's called from.
+//!
+//! Parameters can be required, optional or given by keyword:
+//!
+//! ```lisp
+//! (fun beat (a b :optional (c 2) d :key (lvl 0.5) dur) ...)
+//! (beat 100 200 :dur 400)
+//! ```
+//!
+//! `c` is 2 if it's left out, `d` is `#f`, and so is `dur` if there's no
+//! `:dur` in the call.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync;
+
+use crate::builtin_types::*;
+use crate::parser::{eval_expression, Atom, EvalError, EvaluatedExpr, Expr, FunctionMap, Span};
+use crate::{OutputMode, SampleAndWavematrixSet};
+
+// deeper than that, it's most likely a recursion that doesn't end
+const MAX_DEPTH: usize = 64;
+
+// the OSC, MIDI etc. threads have the default 2 MiB of stack, which
+// lasts for only a few nested calls in a debug build, so the outermost
+// call runs on a thread of its own that has enough for MAX_DEPTH
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub type Locals = HashMap<String, EvaluatedExpr>;
+
+#[derive(Clone)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub optional: Vec<(String, Option<Expr>)>,
+    pub keyword: Vec<(String, Option<Expr>)>,
+    pub body: Vec<Expr>,
+    // the local variables where the function has been defined
+    pub env: Locals,
+}
+
+impl Function {
+    /// the parameters the way they're shown to the user, i.e. `a [c] :lvl`
+    pub fn arg_names(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .cloned()
+            .chain(self.optional.iter().map(|(name, _)| format!("[{name}]")))
+            .chain(self.keyword.iter().map(|(name, _)| format!(":{name}")))
+            .collect()
+    }
+}
+
+fn not_a_param(span: Span) -> EvalError {
+    EvalError::invalid_definition("the arguments need to be names", span)
+}
+
+/// Makes a function from a definition (without the name). If the first
+/// expression doesn't look like a parameter list, the function has no
+/// parameters and that's part of the body.
+pub fn parse_function(tail: &[Expr], env: Locals) -> Result<Function, EvalError> {
+    let mut function = Function {
+        params: Vec::new(),
+        optional: Vec::new(),
+        keyword: Vec::new(),
+        body: tail.to_vec(),
+        env,
+    };
+
+    let Some(Expr::Application(head, params, span)) = tail.first() else {
+        return Ok(function);
+    };
+    match head.as_ref() {
+        Expr::Constant(Atom::Identifier(_), _) => {}
+        Expr::Constant(Atom::Keyword(k), _) if k == "optional" || k == "key" => {}
+        _ => return Ok(function),
+    }
+    function.body.remove(0);
+
+    let mut section = "";
+    for param in std::iter::once(head.as_ref()).chain(params.iter()) {
+        let (name, default) = match param {
+            Expr::Constant(Atom::Keyword(k), _) if k == "optional" || k == "key" => {
+                section = if k == "optional" { "optional" } else { "key" };
+                continue;
+            }
+            Expr::Constant(Atom::Identifier(name), _) => (name.clone(), None),
+            // parameters with default values
+            Expr::Application(name, default, param_span) if !section.is_empty() => {
+                match (name.as_ref(), default.as_slice()) {
+                    (Expr::Constant(Atom::Identifier(name), _), [default]) => {
+                        (name.clone(), Some(default.clone()))
+                    }
+                    _ => return Err(not_a_param(*param_span)),
+                }
+            }
+            _ => return Err(not_a_param(param.span().unwrap_or(*span))),
+        };
+
+        match section {
+            "optional" => function.optional.push((name, default)),
+            "key" => function.keyword.push((name, default)),
+            _ => function.params.push(name),
+        }
+    }
+
+    Ok(function)
+}
+
+/// Calls a function with arguments that have been evaluated already.
+/// The results of all the expressions in the body are returned, the
+/// last one is the result of the call.
+pub fn call(
+    function: &Function,
+    name: &str,
+    args: Vec<EvaluatedExpr>,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<Vec<EvaluatedExpr>, EvalError> {
+    if function.params.len() > args.len() {
+        return Err(EvalError::MissingArgument {
+            function: name.to_string(),
+            position: args.len() + 1,
+            expected: function.params[args.len()].clone(),
+            span: None,
+        });
+    }
+
+    let eval_default = |default: &Option<Expr>, locals: &Locals| match default {
+        Some(expr) => eval_expression(
+            expr,
+            functions,
+            globals,
+            Some(locals),
+            sample_set.clone(),
+            out_mode,
+        ),
+        None => Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Comparable::Boolean(false),
+        ))),
+    };
+    let is_keyword = |arg: &EvaluatedExpr| matches!(arg, EvaluatedExpr::Keyword(k) if function.keyword.iter().any(|(name, _)| name == k));
+
+    let mut locals = function.env.clone();
+    let mut args = args.into_iter().peekable();
+
+    for param in function.params.iter() {
+        locals.insert(param.clone(), args.next().unwrap());
+    }
+
+    // the optional ones come before the keywords
+    for (param, default) in function.optional.iter() {
+        let value = match args.next_if(|arg| !is_keyword(arg)) {
+            Some(arg) => arg,
+            None => eval_default(default, &locals)?,
+        };
+        locals.insert(param.clone(), value);
+    }
+
+    // surplus arguments are ignored
+    let mut given = HashMap::new();
+    while let Some(arg) = args.next() {
+        if !is_keyword(&arg) {
+            continue;
+        }
+        if let EvaluatedExpr::Keyword(k) = arg {
+            let value = args.next().ok_or_else(|| EvalError::Failed {
+                function: name.to_string(),
+                message: format!("no value for :{k}"),
+                span: None,
+            })?;
+            given.insert(k, value);
+        }
+    }
+    for (param, default) in function.keyword.iter() {
+        let value = match given.remove(param) {
+            Some(value) => value,
+            None => eval_default(default, &locals)?,
+        };
+        locals.insert(param.clone(), value);
+    }
+
+    let depth = DEPTH.with(|d| d.get());
+    if depth >= MAX_DEPTH {
+        return Err(EvalError::Failed {
+            function: name.to_string(),
+            message: format!("more than {MAX_DEPTH} nested calls, does the recursion end?"),
+            span: None,
+        });
+    }
+
+    let eval_body = || {
+        DEPTH.with(|d| d.set(depth + 1));
+        let results = function
+            .body
+            .iter()
+            .map(|expr| {
+                eval_expression(
+                    expr,
+                    functions,
+                    globals,
+                    Some(&locals),
+                    sample_set.clone(),
+                    out_mode,
+                )
+            })
+            .collect();
+        DEPTH.with(|d| d.set(depth));
+        results
+    };
+
+    if depth > 0 {
+        return eval_body();
+    }
+
+    std::thread::scope(|scope| {
+        let body = std::thread::Builder::new()
+            .name(name.to_string())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, eval_body)
+            .map_err(|e| EvalError::Failed {
+                function: name.to_string(),
+                message: format!("can't start the call: {e}"),
+                span: None,
+            })?;
+        body.join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Calls a function that has been passed around as a value, i.e. to `map`,
+/// which is either a closure or the name of a function.
+pub fn apply(
+    callee: &EvaluatedExpr,
+    args: Vec<EvaluatedExpr>,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let (function, name) = match callee {
+        EvaluatedExpr::Closure(function) => (function, "lambda"),
+        EvaluatedExpr::Identifier(name) => match functions.usr_lib.get(name) {
+            Some(function) => (function, name.as_str()),
+            None => {
+                let Some(std_fun) = functions.std_lib.get(name) else {
+                    return Err(EvalError::UnknownFunction {
+                        name: name.clone(),
+                        span: None,
+                    });
+                };
+                let mut tail = args;
+                tail.insert(0, callee.clone());
+                return std_fun(functions, &mut tail, globals, sample_set, out_mode)
+                    .map_err(|e| e.named(name));
+            }
+        },
+        _ => return Err(EvalError::failed("expected a function")),
+    };
+
+    call(
+        function, name, args, functions, globals, sample_set, out_mode,
+    )?
+    .pop()
+    .ok_or_else(|| EvalError::Failed {
+        function: name.to_string(),
+        message: "function body is empty".to_string(),
+        span: None,
+    })
+}
+
+/// `(let ((a 1) (b (add a 1))) body ...)`, or `(let (a 1) body ...)` for a
+/// single variable. The variables are only visible in the body, each one
+/// in the ones that follow as well. The result is the last one of the body.
+#[allow(clippy::too_many_arguments)]
+pub fn local_let(
+    bindings: &Expr,
+    body: &[Expr],
+    span: Span,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalVariables>,
+    locals: Option<&Locals>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let Expr::Application(head, rest, bindings_span) = bindings else {
+        return Err(EvalError::invalid_definition(
+            "expected variables like ((name value) ...)",
+            span,
+        ));
+    };
+
+    let pairs: Vec<&Expr> = if let Expr::Application(..) = head.as_ref() {
+        std::iter::once(head.as_ref()).chain(rest.iter()).collect()
+    } else {
+        vec![bindings]
+    };
+
+    let not_a_variable = |pair: &Expr| {
+        EvalError::invalid_definition(
+            "a variable looks like (name value)",
+            pair.span().unwrap_or(*bindings_span),
+        )
+    };
+
+    let mut scope = locals.cloned().unwrap_or_default();
+    for pair in pairs {
+        let (name, value) = match pair {
+            Expr::Application(name, value, _) => match (name.as_ref(), value.as_slice()) {
+                (Expr::Constant(Atom::Identifier(name), _), [value]) => (name, value),
+                _ => return Err(not_a_variable(pair)),
+            },
+            _ => return Err(not_a_variable(pair)),
+        };
+        let value = eval_expression(
+            value,
+            functions,
+            globals,
+            Some(&scope),
+            sample_set.clone(),
+            out_mode,
+        )?;
+        scope.insert(name.clone(), value);
+    }
+
+    let mut results = body
+        .iter()
+        .map(|expr| {
+            eval_expression(
+                expr,
+                functions,
+                globals,
+                Some(&scope),
+                sample_set.clone(),
+                out_mode,
+            )
+        })
+        .collect::<Result<Vec<EvaluatedExpr>, EvalError>>()?;
+
+    results
+        .pop()
+        .ok_or_else(|| EvalError::invalid_definition("the let needs a body", span))
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::eval::arithmetic;
+    use crate::parser::eval_from_str;
+
+    #[test]
+    fn test_functions() {
+        let mut functions = FunctionMap::new();
+        let globals = sync::Arc::new(GlobalVariables::new());
+        functions.std_lib.insert("add".to_string(), arithmetic::add);
+        functions.std_lib.insert("mul".to_string(), arithmetic::mul);
+
+        // store the definitions, like the interpreter does
+        for src in [
+            "(fun double (x) (mul x 2))",
+            "(fun quad (x) (double (double x)))",
+            "(fun adder (n) (lambda (x) (add x n)))",
+            "(fun twice (f x) (f (f x)))",
+            "(fun scaled (x :optional (factor 2) :key (offset 0)) (add (mul x factor) offset))",
+            "(fun sees-x x)",
+            "(fun forever (x) (forever x))",
+            "(fun nested (x) (add 1 (mul 2 (add 3 (nested x)))))",
+        ] {
+            match eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            ) {
+                Ok(EvaluatedExpr::FunctionDefinition(name, fun)) => {
+                    functions.usr_lib.insert(name, fun);
+                }
+                e => panic!("{e:?}"),
+            }
+        }
+
+        let eval = |src: &str| {
+            eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            )
+        };
+        let float = |src: &str| match eval(src) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => f,
+            e => panic!("{src}: {e:?}"),
+        };
+
+        // arguments are passed on to other functions
+        assert_eq!(float("(quad 3)"), 12.0);
+
+        // closures keep the variables where they've been made
+        assert_eq!(float("((adder 5) 1)"), 6.0);
+        assert_eq!(float("(let ((add5 (adder 5)) (y 10)) (add5 y))"), 15.0);
+        assert_eq!(float("(twice double 3)"), 12.0);
+        assert_eq!(float("(twice (adder 1) 3)"), 5.0);
+        assert_eq!(float("(twice (lambda (x) (mul x x)) 3)"), 81.0);
+
+        // but don't see the ones where they're called
+        assert!(matches!(
+            eval("(let ((x 1)) (sees-x))"),
+            Ok(EvaluatedExpr::Identifier(x)) if x == "x"
+        ));
+
+        assert_eq!(float("(scaled 3)"), 6.0);
+        assert_eq!(float("(scaled 3 3)"), 9.0);
+        assert_eq!(float("(scaled 3 :offset 1)"), 7.0);
+        assert_eq!(float("(scaled 3 4 :offset 1)"), 13.0);
+
+        // local variables see the ones before
+        assert_eq!(float("(let ((a 1) (b (add a 1))) (mul a b))"), 2.0);
+        assert_eq!(float("(let (a 4) (double a))"), 8.0);
+
+        assert!(matches!(
+            eval("(double)"),
+            Err(EvalError::MissingArgument { position: 1, .. })
+        ));
+
+        // on a thread with the default stack size
+        for src in ["(forever 1)", "(nested 1)"] {
+            assert!(matches!(
+                eval(src),
+                Err(EvalError::Failed { message, .. }) if message.contains("nested calls")
+            ));
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::builtin_types::*;
 use std::sync;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use super::multiplyer::spread_gens;
@@ -12,7 +12,7 @@ pub fn generator_list(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut gen_list = Vec::new();
 
     let mut tail_drain = tail.drain(..);
@@ -32,7 +32,7 @@ pub fn generator_list(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::GeneratorList(gen_list)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::GeneratorList(gen_list)))
 }
 
 pub fn spread_list(
@@ -41,7 +41,7 @@ pub fn spread_list(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut gen_list = Vec::new();
 
     let mut tail_drain = tail.drain(..);
@@ -63,5 +63,5 @@ pub fn spread_list(
 
     spread_gens(&mut gen_list, &out_mode);
 
-    Some(EvaluatedExpr::Typed(TypedEntity::GeneratorList(gen_list)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::GeneratorList(gen_list)))
 }
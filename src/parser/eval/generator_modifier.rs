@@ -3,7 +3,7 @@ use crate::generator::*;
 use std::collections::HashMap;
 use std::sync;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 /// Helper function to collect arguments
@@ -44,7 +44,7 @@ pub fn eval_haste(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(haste, tail, globals)
 }
 
@@ -54,7 +54,7 @@ pub fn eval_keep(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(keep, tail, globals)
 }
 
@@ -64,7 +64,7 @@ pub fn eval_relax(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(relax, tail, globals)
 }
 
@@ -74,7 +74,7 @@ pub fn eval_blur(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(blur, tail, globals)
 }
 
@@ -84,7 +84,7 @@ pub fn eval_sharpen(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(sharpen, tail, globals)
 }
 
@@ -94,7 +94,7 @@ pub fn eval_solidify(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(solidify, tail, globals)
 }
 
@@ -104,7 +104,7 @@ pub fn eval_rep(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(rep, tail, globals)
 }
 
@@ -114,7 +114,7 @@ pub fn eval_shake(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(shake, tail, globals)
 }
 
@@ -124,7 +124,7 @@ pub fn eval_skip(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(skip, tail, globals)
 }
 
@@ -134,7 +134,7 @@ pub fn eval_rewind(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(rewind, tail, globals)
 }
 
@@ -144,7 +144,7 @@ pub fn eval_rnd(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(rnd, tail, globals)
 }
 
@@ -154,7 +154,7 @@ pub fn eval_grow(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(grow, tail, globals)
 }
 
@@ -164,7 +164,7 @@ pub fn eval_grown(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(grown, tail, globals)
 }
 
@@ -174,7 +174,7 @@ pub fn eval_shrink(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(shrink, tail, globals)
 }
 
@@ -184,7 +184,7 @@ pub fn eval_reverse(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     eval_generator_modifier(reverse, tail, globals)
 }
 
@@ -192,9 +192,9 @@ fn eval_generator_modifier(
     fun: GenModFun,
     tail: &mut Vec<EvaluatedExpr>,
     globals: &std::sync::Arc<GlobalVariables>,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let last = tail.pop();
-    Some(match last {
+    Ok(match last {
         Some(EvaluatedExpr::Typed(TypedEntity::Generator(mut g))) => {
             let mut tail_drain = tail.drain(..);
             tail_drain.next();
@@ -9,7 +9,7 @@ use crate::builtin_types::*;
 use crate::generator_processor::GeneratorProcessor;
 use std::sync;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use super::resolver::resolve_globals;
@@ -22,7 +22,7 @@ pub fn eval_pear(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(pear::collect_pear, tail)
 }
@@ -33,7 +33,7 @@ pub fn eval_inhibit(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(inhibit::collect_inhibit, tail)
 }
@@ -44,7 +44,7 @@ pub fn eval_exhibit(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(exhibit::collect_exhibit, tail)
 }
@@ -55,7 +55,7 @@ pub fn eval_apple(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(apple::collect_apple, tail)
 }
@@ -66,7 +66,7 @@ pub fn eval_every(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(every::collect_every, tail)
 }
@@ -77,7 +77,7 @@ pub fn eval_lifemodel(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_generator_processor(lifemodel::collect_lifemodel, tail)
 }
@@ -86,9 +86,9 @@ pub fn eval_lifemodel(
 fn eval_generator_processor(
     collector: Collector,
     tail: &mut Vec<EvaluatedExpr>,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let last = tail.pop();
-    Some(match last {
+    Ok(match last {
         Some(EvaluatedExpr::Typed(TypedEntity::Generator(mut g))) => {
             let gp = collector(tail);
             g.processors.push((gp.get_id(), gp));
@@ -144,6 +144,6 @@ fn eval_generator_processor(
                 GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
             ))
         }
-        None => return None,
+        None => return Err(EvalError::failed("needs at least one argument")),
     })
 }
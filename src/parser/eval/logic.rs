@@ -0,0 +1,263 @@
+//! Comparisons, `not` and the type predicates. `#f` and 0 are false,
+//! everything else is true.
+//!
+//! Like the arithmetic, comparisons with a global variable in them, i.e.
+//! `(gt x 0.5)`, are resolved when the event is played, as 1.0 or 0.0.
+//! Equality with something that isn't a number, i.e. `(eq mode 'fast)`,
+//! looks the variables up right away.
+
+use crate::builtin_types::{Comparable, LazyArithmetic, LazyVal, TypedEntity, VariableId};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
+use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
+
+use std::sync;
+
+use super::resolver::{needs_resolve, resolve_lazy};
+
+fn entity_truth(entity: &TypedEntity) -> bool {
+    match entity {
+        TypedEntity::Comparable(Comparable::Boolean(b)) => *b,
+        TypedEntity::Comparable(Comparable::Float(f)) => *f != 0.0,
+        TypedEntity::Comparable(Comparable::Double(f)) => *f != 0.0,
+        TypedEntity::Comparable(Comparable::Int32(i)) => *i != 0,
+        TypedEntity::Comparable(Comparable::Int64(i)) => *i != 0,
+        _ => true,
+    }
+}
+
+/// Whether something counts as true, or `None` if that depends on a
+/// global variable.
+pub fn truth(value: &EvaluatedExpr) -> Option<bool> {
+    match value {
+        EvaluatedExpr::Identifier(_) | EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(_)) => None,
+        EvaluatedExpr::Typed(entity) => Some(entity_truth(entity)),
+        _ => Some(true),
+    }
+}
+
+/// Whether something counts as true, with the global variables as they are
+/// right now. Unknown variables are false.
+pub fn resolve_truth(value: EvaluatedExpr, globals: &sync::Arc<GlobalVariables>) -> bool {
+    match value {
+        EvaluatedExpr::Identifier(i) => {
+            let entity = globals
+                .get(&VariableId::Custom(i))
+                .map(|thing| thing.value().clone());
+            match entity {
+                Some(TypedEntity::LazyArithmetic(l)) => resolve_lazy(l, globals) != 0.0,
+                Some(entity) => entity_truth(&entity),
+                None => false,
+            }
+        }
+        EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(l)) => resolve_lazy(l, globals) != 0.0,
+        value => truth(&value).unwrap_or(false),
+    }
+}
+
+/// Something that can be resolved when the event is played, if it's a
+/// number or depends on a variable.
+pub fn lazy_val(value: &EvaluatedExpr) -> Option<LazyVal> {
+    match value {
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f))) => {
+            Some(LazyVal::Val(*f))
+        }
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Boolean(b))) => {
+            Some(LazyVal::Val(if *b { 1.0 } else { 0.0 }))
+        }
+        EvaluatedExpr::Identifier(i) => Some(LazyVal::Id(VariableId::Custom(i.clone()))),
+        EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(a)) => Some(LazyVal::Arith(a.clone())),
+        _ => None,
+    }
+}
+
+fn as_number(value: &EvaluatedExpr) -> Option<f64> {
+    match value {
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f))) => Some(*f as f64),
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Double(f))) => Some(*f),
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Int32(i))) => Some(*i as f64),
+        EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Int64(i))) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+// whether something is a number, or will be one when it's resolved
+fn lazy_number(value: &EvaluatedExpr, globals: &sync::Arc<GlobalVariables>) -> bool {
+    match value {
+        // unknown variables might become numbers
+        EvaluatedExpr::Identifier(i) => match globals.get(&VariableId::Custom(i.clone())) {
+            Some(thing) => lazy_val(&EvaluatedExpr::Typed(thing.value().clone())).is_some(),
+            None => true,
+        },
+        value => lazy_val(value).is_some(),
+    }
+}
+
+/// (lt a b c ...) is true if each one is smaller than the next, and so on.
+/// The equality works for strings and symbols as well.
+pub fn compare(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(..);
+    let name = match tail_drain.next() {
+        Some(EvaluatedExpr::Identifier(name)) => name,
+        _ => String::new(),
+    };
+    let mut args: Vec<EvaluatedExpr> = tail_drain.collect();
+    if args.len() < 2 {
+        return Err(EvalError::invalid_argument(
+            args.len() + 1,
+            "something to compare",
+        ));
+    }
+
+    if matches!(name.as_str(), "eq" | "=") && !args.iter().all(|a| lazy_number(a, globals)) {
+        // a symbol or string can't be compared later
+        for arg in args.iter_mut() {
+            let resolved = match arg {
+                EvaluatedExpr::Identifier(i) => globals
+                    .get(&VariableId::Custom(i.clone()))
+                    .map(|thing| thing.value().clone()),
+                EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(l)) => {
+                    Some(TypedEntity::LazyArithmetic(l.clone()))
+                }
+                _ => None,
+            };
+            *arg = match resolved {
+                Some(TypedEntity::LazyArithmetic(l)) => EvaluatedExpr::Typed(
+                    TypedEntity::Comparable(Comparable::Float(resolve_lazy(l, globals))),
+                ),
+                Some(entity) => EvaluatedExpr::Typed(entity),
+                None => continue,
+            };
+        }
+    } else if needs_resolve(&args) {
+        let mut vals = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            vals.push(lazy_val(arg).ok_or_else(|| EvalError::invalid_argument(i + 1, "a number"))?);
+        }
+        return Ok(EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(
+            match name.as_str() {
+                "lt" | "<" => LazyArithmetic::Lt(vals),
+                "gt" | ">" => LazyArithmetic::Gt(vals),
+                "lte" | "<=" => LazyArithmetic::Lte(vals),
+                "gte" | ">=" => LazyArithmetic::Gte(vals),
+                _ => LazyArithmetic::Eq(vals),
+            },
+        )));
+    }
+
+    let mut result = true;
+    for (i, pair) in args.windows(2).enumerate() {
+        let (a, b) = (&pair[0], &pair[1]);
+        let holds = match (name.as_str(), as_number(a), as_number(b)) {
+            ("eq" | "=", Some(a), Some(b)) => a == b,
+            ("eq" | "=", _, _) => match (a, b) {
+                (
+                    EvaluatedExpr::Typed(TypedEntity::Comparable(a)),
+                    EvaluatedExpr::Typed(TypedEntity::Comparable(b)),
+                ) => a == b,
+                (EvaluatedExpr::Keyword(a), EvaluatedExpr::Keyword(b)) => a == b,
+                _ => false,
+            },
+            ("lt" | "<", Some(a), Some(b)) => a < b,
+            ("gt" | ">", Some(a), Some(b)) => a > b,
+            ("lte" | "<=", Some(a), Some(b)) => a <= b,
+            ("gte" | ">=", Some(a), Some(b)) => a >= b,
+            (_, None, _) => return Err(EvalError::invalid_argument(i + 1, "a number")),
+            (_, _, None) => return Err(EvalError::invalid_argument(i + 2, "a number")),
+            _ => false,
+        };
+        result = result && holds;
+    }
+
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Comparable::Boolean(result),
+    )))
+}
+
+pub fn not(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next(); // don't need the function name
+
+    let Some(value) = tail_drain.next() else {
+        return Err(EvalError::invalid_argument(1, "something to negate"));
+    };
+
+    Ok(EvaluatedExpr::Typed(match truth(&value) {
+        Some(t) => TypedEntity::Comparable(Comparable::Boolean(!t)),
+        // identifiers and lazy values can always be made lazy
+        None => {
+            TypedEntity::LazyArithmetic(LazyArithmetic::Not(lazy_val(&value).into_iter().collect()))
+        }
+    }))
+}
+
+/// `number?`, `string?` and so on, the name of the function says which type
+/// to check for. Global variables are looked up right away.
+pub fn type_predicate(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(..);
+    let name = match tail_drain.next() {
+        Some(EvaluatedExpr::Identifier(name)) => name,
+        _ => String::new(),
+    };
+    let Some(mut value) = tail_drain.next() else {
+        return Err(EvalError::invalid_argument(1, "something to check"));
+    };
+
+    if let EvaluatedExpr::Identifier(i) = &value {
+        if let Some(thing) = globals.get(&VariableId::Custom(i.clone())) {
+            value = EvaluatedExpr::Typed(thing.value().clone());
+        }
+    }
+
+    let is = match (name.as_str(), &value) {
+        (
+            "number?",
+            EvaluatedExpr::Typed(
+                TypedEntity::Comparable(
+                    Comparable::Float(_)
+                    | Comparable::Double(_)
+                    | Comparable::Int32(_)
+                    | Comparable::Int64(_),
+                )
+                | TypedEntity::LazyArithmetic(_),
+            ),
+        ) => true,
+        ("string?", EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(_)))) => true,
+        ("symbol?", EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(_)))) => true,
+        ("boolean?", EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Boolean(_)))) => true,
+        ("keyword?", EvaluatedExpr::Keyword(_)) => true,
+        (
+            "event?",
+            EvaluatedExpr::Typed(TypedEntity::SoundEvent(_) | TypedEntity::ControlEvent(_)),
+        ) => true,
+        ("generator?", EvaluatedExpr::Typed(TypedEntity::Generator(_))) => true,
+        ("vec?", EvaluatedExpr::Typed(TypedEntity::Vec(_))) => true,
+        ("map?", EvaluatedExpr::Typed(TypedEntity::Map(_))) => true,
+        ("function?", EvaluatedExpr::Closure(_) | EvaluatedExpr::FunctionDefinition(..)) => true,
+        ("function?", EvaluatedExpr::Identifier(f)) => {
+            functions.usr_lib.contains_key(f) || functions.std_lib.contains_key(f)
+        }
+        _ => false,
+    };
+
+    Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Comparable::Boolean(is),
+    )))
+}
@@ -0,0 +1,438 @@
+//! Macros rewrite the code before it's evaluated, so users can make their
+//! own constructors:
+//!
+//! ```lisp
+//! (defmacro beat (name pattern :optional (dur 200) :rest mods)
+//!   `(sx ,name #t (cyc ,name ,pattern :dur ,dur) ,@mods))
+//!
+//! (beat 'drums "bd ~ sn ~")
+//! (beat 'hats "hh hh" 100 (shake 0.5))
+//! ```
+//!
+//! The parameters stand for the code that's passed to the macro, which
+//! isn't evaluated. In the quasiquoted (`` ` ``) part of the body, only the
+//! unquoted ones (`,name`) are replaced, and `,@mods` puts the rest of
+//! the arguments in place. Unlike in other lisps, the body isn't evaluated
+//! when the macro is expanded, the result is always the body with the
+//! parameters replaced. `(macroexpand (beat 'drums "bd"))` shows it.
+
+use std::collections::HashMap;
+use std::sync;
+
+use crate::parser::{Atom, EvalError, Expr, Span};
+
+// deeper than that, a macro most likely expands into itself
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct Macro {
+    pub params: Vec<String>,
+    pub optional: Vec<(String, Option<Expr>)>,
+    pub rest: Option<String>,
+    pub body: Expr,
+}
+
+pub type MacroMap = HashMap<String, sync::Arc<Macro>>;
+
+impl Macro {
+    /// the parameters the way they're shown to the user, i.e. `a [b] c...`
+    pub fn arg_names(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .cloned()
+            .chain(self.optional.iter().map(|(name, _)| format!("[{name}]")))
+            .chain(self.rest.iter().map(|name| format!("{name}...")))
+            .collect()
+    }
+}
+
+// what a parameter stands for when the macro is expanded
+enum Binding<'a> {
+    One(&'a Expr),
+    Many(&'a [Expr]),
+}
+
+fn not_a_param(span: Span) -> EvalError {
+    EvalError::invalid_definition("the arguments need to be names", span)
+}
+
+/// Makes a macro from a definition (without the name), which is the
+/// parameter list and the body.
+pub fn parse_macro(tail: &[Expr], span: Span) -> Result<Macro, EvalError> {
+    let (params, body) = match tail {
+        [params, body] => (params, body),
+        [_] | [] => {
+            return Err(EvalError::invalid_definition(
+                "expected (defmacro name (args ...) body)",
+                span,
+            ))
+        }
+        [_, _, extra, ..] => {
+            return Err(EvalError::invalid_definition(
+                "a macro has only one body, use progn for more",
+                extra.span().unwrap_or(span),
+            ))
+        }
+    };
+
+    let mut mac = Macro {
+        params: Vec::new(),
+        optional: Vec::new(),
+        rest: None,
+        body: body.clone(),
+    };
+
+    let Expr::Application(head, rest, params_span) = params else {
+        return Err(not_a_param(params.span().unwrap_or(span)));
+    };
+
+    let mut section = "";
+    for param in std::iter::once(head.as_ref()).chain(rest.iter()) {
+        let (name, default) = match param {
+            Expr::Constant(Atom::Keyword(k), _) if k == "optional" || k == "rest" => {
+                section = if k == "optional" { "optional" } else { "rest" };
+                continue;
+            }
+            Expr::Constant(Atom::Identifier(name), _) => (name.clone(), None),
+            Expr::Application(name, default, param_span) if section == "optional" => {
+                match (name.as_ref(), default.as_slice()) {
+                    (Expr::Constant(Atom::Identifier(name), _), [default]) => {
+                        (name.clone(), Some(default.clone()))
+                    }
+                    _ => return Err(not_a_param(*param_span)),
+                }
+            }
+            _ => return Err(not_a_param(param.span().unwrap_or(*params_span))),
+        };
+
+        match section {
+            "optional" => mac.optional.push((name, default)),
+            "rest" if mac.rest.is_none() => mac.rest = Some(name),
+            "rest" => {
+                return Err(EvalError::invalid_definition(
+                    "there can only be one :rest argument",
+                    param.span().unwrap_or(*params_span),
+                ))
+            }
+            _ => mac.params.push(name),
+        }
+    }
+
+    Ok(mac)
+}
+
+/// Expands a call of a macro, and all the macro calls in the result.
+pub fn expand_call(
+    mac: &Macro,
+    name: &str,
+    args: &[Expr],
+    span: Span,
+    macros: &MacroMap,
+) -> Result<Expr, EvalError> {
+    expand_call_at(mac, name, args, span, macros, 0)
+}
+
+fn expand_call_at(
+    mac: &Macro,
+    name: &str,
+    args: &[Expr],
+    span: Span,
+    macros: &MacroMap,
+    depth: usize,
+) -> Result<Expr, EvalError> {
+    if depth >= MAX_DEPTH {
+        return Err(EvalError::Failed {
+            function: name.to_string(),
+            message: format!(
+                "more than {MAX_DEPTH} nested expansions, does the macro expand into itself?"
+            ),
+            span: Some(span),
+        });
+    }
+    if mac.params.len() > args.len() {
+        return Err(EvalError::MissingArgument {
+            function: name.to_string(),
+            position: args.len() + 1,
+            expected: mac.params[args.len()].clone(),
+            span: Some(span),
+        });
+    }
+
+    let mut bindings = HashMap::new();
+    let mut args = args.iter();
+    for param in mac.params.iter() {
+        bindings.insert(param.as_str(), Binding::One(args.next().unwrap()));
+    }
+    let no = Expr::Constant(Atom::Boolean(false), span);
+    for (param, default) in mac.optional.iter() {
+        let arg = args.next().or(default.as_ref()).unwrap_or(&no);
+        bindings.insert(param.as_str(), Binding::One(arg));
+    }
+    let rest = args.as_slice();
+    match &mac.rest {
+        Some(param) => {
+            bindings.insert(param.as_str(), Binding::Many(rest));
+        }
+        None if !rest.is_empty() => {
+            return Err(EvalError::Failed {
+                function: name.to_string(),
+                message: format!(
+                    "expects {} arguments at most",
+                    mac.params.len() + mac.optional.len()
+                ),
+                span: Some(span),
+            });
+        }
+        None => {}
+    }
+
+    let expanded = match substitute(&mac.body, &bindings, false, span)?.as_slice() {
+        [expr] => expr.clone(),
+        _ => {
+            return Err(EvalError::Failed {
+                function: name.to_string(),
+                message: "the macro needs to expand into one expression".to_string(),
+                span: Some(span),
+            })
+        }
+    };
+
+    expand_at(&expanded, macros, depth + 1)
+}
+
+/// Expands all the macro calls in an expression.
+pub fn expand(expr: &Expr, macros: &MacroMap) -> Result<Expr, EvalError> {
+    expand_at(expr, macros, 0)
+}
+
+fn expand_at(expr: &Expr, macros: &MacroMap, depth: usize) -> Result<Expr, EvalError> {
+    match expr {
+        Expr::Application(head, tail, span) => {
+            if let Expr::Constant(Atom::Identifier(f), _) = head.as_ref() {
+                if let Some(mac) = macros.get(f) {
+                    return expand_call_at(mac, f, tail, *span, macros, depth);
+                }
+            }
+            Ok(Expr::Application(
+                Box::new(expand_at(head, macros, depth)?),
+                tail.iter()
+                    .map(|e| expand_at(e, macros, depth))
+                    .collect::<Result<Vec<Expr>, EvalError>>()?,
+                *span,
+            ))
+        }
+        // the bodies of other macros are expanded when they're used
+        Expr::Definition(head, _, _) if matches!(head.as_ref(), Expr::MacroDefinition) => {
+            Ok(expr.clone())
+        }
+        Expr::Definition(head, tail, span) => Ok(Expr::Definition(
+            head.clone(),
+            tail.iter()
+                .map(|e| expand_at(e, macros, depth))
+                .collect::<Result<Vec<Expr>, EvalError>>()?,
+            *span,
+        )),
+        _ => Ok(expr.clone()),
+    }
+}
+
+// `(quasiquote x)` and the like, which is what `` `x `` is parsed into
+fn quoted<'a>(expr: &'a Expr, form: &str) -> Option<&'a Expr> {
+    match expr {
+        Expr::Application(head, tail, _) => match (head.as_ref(), tail.as_slice()) {
+            (Expr::Constant(Atom::Identifier(f), _), [inner]) if f == form => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The expression with the parameters replaced. In quasiquoted parts only
+/// the unquoted ones are. It's a list, as `,@rest` can be several ones.
+/// The body has been parsed from somewhere else, so its spans are moved to
+/// the call, the arguments keep theirs.
+fn substitute(
+    expr: &Expr,
+    bindings: &HashMap<&str, Binding>,
+    in_quasiquote: bool,
+    call: Span,
+) -> Result<Vec<Expr>, EvalError> {
+    if let Some(inner) = quoted(expr, "quasiquote") {
+        return substitute(inner, bindings, true, call);
+    }
+    if let Some(inner) = quoted(expr, "unquote") {
+        return substitute(inner, bindings, false, call);
+    }
+    if let Some(inner) = quoted(expr, "unquote-splicing") {
+        if let Expr::Constant(Atom::Identifier(name), _) = inner {
+            match bindings.get(name.as_str()) {
+                Some(Binding::Many(exprs)) => return Ok(exprs.to_vec()),
+                Some(Binding::One(expr)) => return Ok(vec![(*expr).clone()]),
+                None => {}
+            }
+        }
+        return substitute(inner, bindings, false, call);
+    }
+
+    match expr {
+        Expr::Constant(Atom::Identifier(name), _) if !in_quasiquote => {
+            match bindings.get(name.as_str()) {
+                Some(Binding::One(expr)) => Ok(vec![(*expr).clone()]),
+                Some(Binding::Many(_)) => Err(EvalError::invalid_definition(
+                    &format!("{name} is a list of arguments, use ,@{name}"),
+                    call,
+                )),
+                None => Ok(vec![Expr::Constant(Atom::Identifier(name.clone()), call)]),
+            }
+        }
+        Expr::Constant(atom, _) => Ok(vec![Expr::Constant(atom.clone(), call)]),
+        Expr::Application(head, tail, _) => {
+            let head = match substitute(head, bindings, in_quasiquote, call)?.as_slice() {
+                [head] => head.clone(),
+                _ => {
+                    return Err(EvalError::invalid_definition(
+                        "a function name needs to be one expression",
+                        call,
+                    ))
+                }
+            };
+            let mut new_tail = Vec::new();
+            for e in tail.iter() {
+                new_tail.append(&mut substitute(e, bindings, in_quasiquote, call)?);
+            }
+            Ok(vec![Expr::Application(Box::new(head), new_tail, call)])
+        }
+        Expr::Definition(head, tail, _) => {
+            let mut new_tail = Vec::new();
+            for e in tail.iter() {
+                new_tail.append(&mut substitute(e, bindings, in_quasiquote, call)?);
+            }
+            Ok(vec![Expr::Definition(head.clone(), new_tail, call)])
+        }
+        _ => Ok(vec![expr.clone()]),
+    }
+}
+
+/// The code an expression has been parsed from, more or less (numbers
+/// might look different, and `callback` is `fun` and so on).
+pub fn source(expr: &Expr) -> String {
+    match expr {
+        Expr::Constant(atom, _) => match atom {
+            Atom::Float(f) => format!("{f}"),
+            Atom::String(s) => format!("\"{s}\""),
+            Atom::Keyword(k) => format!(":{k}"),
+            Atom::Symbol(s) => format!("'{s}"),
+            Atom::Boolean(b) => (if *b { "#t" } else { "#f" }).to_string(),
+            Atom::Identifier(i) => i.clone(),
+        },
+        Expr::Application(head, tail, _) | Expr::Definition(head, tail, _) => {
+            if let Some(inner) = quoted(expr, "quasiquote") {
+                return format!("`{}", source(inner));
+            }
+            if let Some(inner) = quoted(expr, "unquote") {
+                return format!(",{}", source(inner));
+            }
+            if let Some(inner) = quoted(expr, "unquote-splicing") {
+                return format!(",@{}", source(inner));
+            }
+            let mut parts = vec![source(head)];
+            parts.extend(tail.iter().map(source));
+            format!("({})", parts.join(" "))
+        }
+        Expr::FunctionDefinition => "fun".to_string(),
+        Expr::VariableDefinition => "let".to_string(),
+        Expr::MacroDefinition => "defmacro".to_string(),
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_types::{Comparable, GlobalVariables, TypedEntity};
+    use crate::parser::{eval_from_str, EvaluatedExpr};
+    use crate::standard_library::define_standard_library;
+    use crate::{OutputMode, SampleAndWavematrixSet};
+
+    #[test]
+    fn test_macros() {
+        let mut functions = define_standard_library();
+        let globals = sync::Arc::new(GlobalVariables::new());
+
+        for src in [
+            "(defmacro beat (name pattern :optional (dur 200) :rest mods) `(sx ,name #t (cyc ,name ,pattern :dur ,dur) ,@mods))",
+            "(defmacro twice (x) `(add ,x ,x))",
+            "(defmacro quadruple (x) `(twice (twice ,x)))",
+            "(defmacro forever (x) `(forever ,x))",
+        ] {
+            match eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            ) {
+                Ok(EvaluatedExpr::MacroDefinition(name, mac)) => {
+                    functions.macros.insert(name, mac);
+                }
+                e => panic!("{e:?}"),
+            }
+        }
+
+        let eval = |src: &str| {
+            eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            )
+        };
+        let string = |src: &str| match eval(src) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) => s,
+            e => panic!("{src}: {e:?}"),
+        };
+
+        // the arguments aren't evaluated, they're put in place
+        assert!(matches!(
+            eval("(twice (mul 2 3))"),
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) if f == 12.0
+        ));
+        assert_eq!(
+            string("(macroexpand (twice (mul 2 3)))"),
+            "(add (mul 2 3) (mul 2 3))"
+        );
+        assert_eq!(
+            string("(macroexpand (quadruple 1))"),
+            "(add (add 1 1) (add 1 1))"
+        );
+
+        // optional arguments have defaults, the rest is spliced in
+        assert_eq!(
+            string("(macroexpand (beat 'drums \"bd ~ sn ~\"))"),
+            "(sx 'drums #t (cyc 'drums \"bd ~ sn ~\" :dur 200))"
+        );
+        assert_eq!(
+            string("(macroexpand (beat 'hats \"hh hh\" 100 (skip 2) (rew 3)))"),
+            "(sx 'hats #t (cyc 'hats \"hh hh\" :dur 100) (skip 2) (rew 3))"
+        );
+        assert!(matches!(
+            eval("(beat 'drums \"bd ~ sn ~\")"),
+            Ok(EvaluatedExpr::SyncContext(s)) if s.name == "drums"
+        ));
+
+        assert!(matches!(
+            eval("(beat 'drums)"),
+            Err(EvalError::MissingArgument { position: 2, .. })
+        ));
+        assert!(matches!(eval("(twice 1 2)"), Err(EvalError::Failed { .. })));
+        assert!(matches!(
+            eval("(forever 1)"),
+            Err(EvalError::Failed { message, .. }) if message.contains("nested expansions")
+        ));
+        assert!(matches!(
+            eval("`(add 1 2)"),
+            Err(EvalError::Failed { message, .. }) if message.contains("macro")
+        ));
+    }
+}
@@ -3,16 +3,21 @@ use std::sync;
 
 use crate::builtin_types::*;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn map(
-    _: &FunctionMap,
+    functions: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
-    _: &sync::Arc<GlobalVariables>,
-    _: SampleAndWavematrixSet,
-    _: OutputMode,
-) -> Option<EvaluatedExpr> {
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    // (map f (vec ...)) maps over vectors, the map itself is made from pairs
+    if let Some(EvaluatedExpr::Closure(_) | EvaluatedExpr::Identifier(_)) = tail.get(1) {
+        return super::vector::map(functions, tail, globals, sample_set, out_mode);
+    }
+
     let tail_drain = tail.drain(..).skip(1);
 
     let mut pmap = HashMap::new();
@@ -31,7 +36,7 @@ pub fn map(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Map(pmap)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::Map(pmap)))
 }
 
 pub fn insert(
@@ -40,7 +45,7 @@ pub fn insert(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(1..);
 
     let place = match tail_drain.next() {
@@ -49,7 +54,7 @@ pub fn insert(
             VariableId::Symbol(s)
         }
         _ => {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a map variable"));
         }
     };
 
@@ -62,13 +67,16 @@ pub fn insert(
             VariableId::Symbol(s)
         }
         _ => {
-            return None;
+            return Err(EvalError::invalid_argument(
+                2,
+                "a string or symbol (the key)",
+            ));
         }
     };
 
     if let Some(EvaluatedExpr::Typed(t)) = tail_drain.next() {
-        Some(EvaluatedExpr::Command(Command::Insert(place, key, t)))
+        Ok(EvaluatedExpr::Command(Command::Insert(place, key, t)))
     } else {
-        None
+        Err(EvalError::invalid_argument(3, "a value"))
     }
 }
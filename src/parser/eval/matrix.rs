@@ -2,7 +2,7 @@ use std::sync;
 
 use crate::builtin_types::*;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 pub fn mat(
@@ -11,7 +11,7 @@ pub fn mat(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let tail_drain = tail.drain(..).skip(1);
 
     let mut pmat = Vec::new();
@@ -39,5 +39,5 @@ pub fn mat(
         pmat.push(row.clone());
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Matrix(pmat)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::Matrix(pmat)))
 }
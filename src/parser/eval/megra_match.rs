@@ -1,5 +1,5 @@
 use crate::builtin_types::*;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -10,7 +10,7 @@ pub fn megra_match(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     // ignore function name
     tail_drain.next();
@@ -23,8 +23,8 @@ pub fn megra_match(
                 matchees.push((n, x));
             }
         }
-        Some(EvaluatedExpr::Match(Box::new(to_be_matched), matchees))
+        Ok(EvaluatedExpr::Match(Box::new(to_be_matched), matchees))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a value to match"))
     }
 }
@@ -2,7 +2,7 @@ use std::sync;
 
 use crate::{
     builtin_types::{Comparable, GlobalVariables, TypedEntity},
-    parser::{EvaluatedExpr, FunctionMap},
+    parser::{EvalError, EvaluatedExpr, FunctionMap},
     sample_set::SampleAndWavematrixSet,
     session::OutputMode,
 };
@@ -13,8 +13,8 @@ pub fn eval_list_midi_ports(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
-    Some(EvaluatedExpr::Command(
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(
         crate::builtin_types::Command::MidiListPorts,
     ))
 }
@@ -25,16 +25,163 @@ pub fn open_midi_port(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(1..);
 
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(port)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Command(
+        Ok(EvaluatedExpr::Command(
             crate::builtin_types::Command::MidiStartReceiver(port as usize),
         ))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number (the port)"))
+    }
+}
+
+pub fn midi_clock_start(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(port)))) =
+        tail_drain.next()
+    {
+        Ok(EvaluatedExpr::Command(
+            crate::builtin_types::Command::MidiClockStart(port as usize),
+        ))
+    } else {
+        Err(EvalError::invalid_argument(1, "a number (the port)"))
+    }
+}
+
+pub fn midi_clock_stop(
+    _: &FunctionMap,
+    _: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(
+        crate::builtin_types::Command::MidiClockStop,
+    ))
+}
+
+pub fn midi_clock_follow(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(port)))) =
+        tail_drain.next()
+    {
+        Ok(EvaluatedExpr::Command(
+            crate::builtin_types::Command::MidiClockFollow(port as usize),
+        ))
+    } else {
+        Err(EvalError::invalid_argument(1, "a number (the port)"))
+    }
+}
+
+pub fn midi_clock_follow_stop(
+    _: &FunctionMap,
+    _: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(
+        crate::builtin_types::Command::MidiClockFollowStop,
+    ))
+}
+
+pub fn open_midi_out_port(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(port)))) =
+        tail_drain.next()
+    {
+        Ok(EvaluatedExpr::Command(
+            crate::builtin_types::Command::MidiOpenOutput(port as usize),
+        ))
+    } else {
+        Err(EvalError::invalid_argument(1, "a number (the port)"))
+    }
+}
+
+/// route events with a certain tag to midi, i.e.
+/// (midi-route 'bass 0 :ch 2)
+pub fn midi_route(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    let tag = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
+        tail_drain.next()
+    {
+        s
+    } else {
+        return Err(EvalError::invalid_argument(1, "a symbol (the tag)"));
+    };
+
+    let mut port = 0;
+    let mut channel = 1;
+
+    while let Some(thing) = tail_drain.next() {
+        match thing {
+            EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f))) => {
+                port = f as usize;
+            }
+            EvaluatedExpr::Keyword(k) if k.as_str() == "ch" => {
+                if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
+                    tail_drain.next()
+                {
+                    channel = f as u8;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(EvaluatedExpr::Command(
+        crate::builtin_types::Command::MidiRoute(tag, port, channel),
+    ))
+}
+
+pub fn midi_unroute(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
+        tail_drain.next()
+    {
+        Ok(EvaluatedExpr::Command(
+            crate::builtin_types::Command::MidiUnroute(s),
+        ))
+    } else {
+        Err(EvalError::invalid_argument(1, "a symbol (the tag)"))
     }
 }
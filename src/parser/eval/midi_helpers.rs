@@ -1,5 +1,5 @@
 use crate::builtin_types::{Comparable, TypedEntity};
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -10,7 +10,7 @@ pub fn mtof(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
@@ -20,14 +20,17 @@ pub fn mtof(
         if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(base)))) =
             tail_drain.next()
         {
-            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                 Comparable::Float(base * f32::powf(2.0, (note - 69.0) / 12.0)),
             )))
         } else {
-            None
+            Err(EvalError::invalid_argument(
+                2,
+                "a number (the base frequency)",
+            ))
         }
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number (the note)"))
     }
 }
 
@@ -37,7 +40,7 @@ pub fn mtosym(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
@@ -66,11 +69,11 @@ pub fn mtosym(
 
         let pstring = format!("{}{}", pclass, oct);
 
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Symbol(pstring),
         )))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -80,7 +83,7 @@ pub fn veltodyn(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
@@ -99,10 +102,10 @@ pub fn veltodyn(
             "ff"
         };
 
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Symbol(dynsym.to_string()),
         )))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
@@ -1,12 +1,16 @@
 pub mod arithmetic;
 pub mod commands;
 pub mod compose;
+pub mod conditional;
 pub mod constructors;
 pub mod dynpar;
 pub mod events;
+pub mod function;
 pub mod generator_list;
 pub mod generator_modifier;
 pub mod generator_processor;
+pub mod logic;
+pub mod macros;
 pub mod map;
 pub mod matrix;
 pub mod megra_match;
@@ -19,6 +23,7 @@ pub mod progn;
 pub mod resolver;
 pub mod session;
 pub mod string_helpers;
+pub mod tempo_sync;
 pub mod types;
 pub mod vector;
 //pub mod vec;
@@ -8,7 +8,7 @@ use crate::generator::Generator;
 use crate::generator_processor::{GeneratorProcessor, GeneratorWrapperProcessor, PearProcessor};
 use crate::parameter::{DynVal, ParameterValue};
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use super::resolver::resolve_globals;
@@ -87,7 +87,7 @@ pub fn eval_multiplyer(
     tail: &mut Vec<EvaluatedExpr>,
     out_mode: OutputMode,
     globals: &std::sync::Arc<GlobalVariables>,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let last = tail.pop(); // generator or generator list ...
 
     let mut gen_proc_list_list = Vec::new();
@@ -116,7 +116,7 @@ pub fn eval_multiplyer(
         }
     }
 
-    Some(match last {
+    Ok(match last {
         Some(EvaluatedExpr::Typed(TypedEntity::Generator(g))) => {
             let mut gens = Vec::new();
             let mut idx: usize = 0;
@@ -202,7 +202,11 @@ pub fn eval_multiplyer(
             }
             EvaluatedExpr::Typed(TypedEntity::GeneratorList(gens))
         }
-        _ => return None,
+        _ => {
+            return Err(EvalError::failed(
+                "the last argument needs to be a generator",
+            ))
+        }
     })
 }
 
@@ -212,7 +216,7 @@ pub fn eval_xspread(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_multiplyer(spread_gens, tail, out_mode, globals)
 }
@@ -223,7 +227,7 @@ pub fn eval_xdup(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     out_mode: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     resolve_globals(&mut tail[1..], globals);
     eval_multiplyer(|_, _| {}, tail, out_mode, globals)
 }
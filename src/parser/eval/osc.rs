@@ -1,5 +1,6 @@
 use crate::builtin_types::*;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::osc_receiver::OscTransport;
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -10,7 +11,7 @@ pub fn osc_define_sender(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next();
     let sender_name =
@@ -19,7 +20,7 @@ pub fn osc_define_sender(
         {
             s
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a symbol (the sender name)"));
         };
     let host_name =
         if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
@@ -27,10 +28,13 @@ pub fn osc_define_sender(
         {
             s
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(
+                2,
+                "a string (the target address)",
+            ));
         };
 
-    Some(EvaluatedExpr::Command(Command::OscDefineClient(
+    Ok(EvaluatedExpr::Command(Command::OscDefineClient(
         sender_name,
         host_name,
     )))
@@ -42,7 +46,7 @@ pub fn osc_send(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next();
 
@@ -52,14 +56,14 @@ pub fn osc_send(
         {
             s
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a symbol (the sender name)"));
         };
     let addr = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
         tail_drain.next()
     {
         s
     } else {
-        return None;
+        return Err(EvalError::invalid_argument(2, "a string (the OSC address)"));
     };
 
     let mut args = Vec::new();
@@ -87,7 +91,7 @@ pub fn osc_send(
         }
     }
 
-    Some(EvaluatedExpr::Command(Command::OscSendMessage(
+    Ok(EvaluatedExpr::Command(Command::OscSendMessage(
         sender_name,
         addr,
         args,
@@ -100,7 +104,7 @@ pub fn osc_start_receiver(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next();
 
@@ -110,8 +114,97 @@ pub fn osc_start_receiver(
         {
             s
         } else {
-            return None;
+            return Err(EvalError::invalid_argument(
+                1,
+                "a string (the address to listen on)",
+            ));
         };
 
-    Some(EvaluatedExpr::Command(Command::OscStartReceiver(host_name)))
+    let mut transport = OscTransport::Udp;
+
+    while let Some(thing) = tail_drain.next() {
+        if let EvaluatedExpr::Keyword(k) = thing {
+            if k.as_str() == "proto" {
+                match tail_drain.next() {
+                    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s))))
+                        if s == "tcp" =>
+                    {
+                        transport = OscTransport::Tcp;
+                    }
+                    Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s))))
+                        if s == "udp" =>
+                    {
+                        transport = OscTransport::Udp;
+                    }
+                    _ => {
+                        return Err(EvalError::failed("unknown protocol, use 'udp or 'tcp"));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(EvaluatedExpr::Command(Command::OscStartReceiver(
+        host_name, transport,
+    )))
+}
+
+pub fn osc_route(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    let tag = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
+        tail_drain.next()
+    {
+        s
+    } else {
+        return Err(EvalError::invalid_argument(1, "a symbol (the tag)"));
+    };
+
+    let sender_name =
+        if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
+            tail_drain.next()
+        {
+            s
+        } else {
+            return Err(EvalError::invalid_argument(2, "a symbol (the sender name)"));
+        };
+
+    // superdirt by default
+    let addr = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
+        tail_drain.next()
+    {
+        s
+    } else {
+        crate::osc_client::SUPERDIRT_ADDRESS.to_string()
+    };
+
+    Ok(EvaluatedExpr::Command(Command::OscRoute(
+        tag,
+        sender_name,
+        addr,
+    )))
+}
+
+pub fn osc_unroute(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(1..);
+
+    if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(s)))) =
+        tail_drain.next()
+    {
+        Ok(EvaluatedExpr::Command(Command::OscUnroute(s)))
+    } else {
+        Err(EvalError::invalid_argument(1, "a symbol (the tag)"))
+    }
 }
@@ -1,5 +1,5 @@
 use crate::builtin_types::*;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -12,14 +12,14 @@ pub fn print(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
     let mut tail_drain = tail.drain(1..);
 
     if let Some(EvaluatedExpr::Typed(t)) = tail_drain.next() {
-        Some(EvaluatedExpr::Command(Command::Print(t)))
+        Ok(EvaluatedExpr::Command(Command::Print(t)))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a value"))
     }
 }
@@ -1,5 +1,5 @@
 use crate::builtin_types::*;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -10,12 +10,12 @@ pub fn progn(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     // ignore function name
     tail_drain.next();
 
     let exprs: Vec<EvaluatedExpr> = tail_drain.collect();
 
-    Some(EvaluatedExpr::Progn(exprs))
+    Ok(EvaluatedExpr::Progn(exprs))
 }
@@ -10,7 +10,9 @@ use crate::{
 pub fn needs_resolve(tail: &[EvaluatedExpr]) -> bool {
     let mut resolve = false;
     for x in tail.iter() {
-        if let EvaluatedExpr::Identifier(_) = x {
+        if let EvaluatedExpr::Identifier(_) | EvaluatedExpr::Typed(TypedEntity::LazyArithmetic(_)) =
+            x
+        {
             resolve = true;
         }
     }
@@ -24,6 +26,7 @@ fn resolve_float(i: VariableId, globals: &std::sync::Arc<GlobalVariables>, defau
             TypedEntity::Comparable(Comparable::Double(f)) => *f as f32,
             TypedEntity::Comparable(Comparable::Int32(f)) => *f as f32,
             TypedEntity::Comparable(Comparable::Int64(f)) => *f as f32,
+            TypedEntity::Comparable(Comparable::Boolean(b)) => bool_to_float(*b),
             _ => default,
         }
     } else {
@@ -31,6 +34,32 @@ fn resolve_float(i: VariableId, globals: &std::sync::Arc<GlobalVariables>, defau
     }
 }
 
+fn bool_to_float(b: bool) -> f32 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn resolve_val(v: LazyVal, globals: &std::sync::Arc<GlobalVariables>) -> f32 {
+    match v {
+        LazyVal::Val(v) => v,
+        LazyVal::Id(i) => resolve_float(i, globals, 0.0),
+        LazyVal::Arith(a) => resolve_lazy(a, globals),
+    }
+}
+
+// chained, like (lt a b c), so each one needs to be smaller than the next
+fn resolve_comparison(
+    args: Vec<LazyVal>,
+    globals: &std::sync::Arc<GlobalVariables>,
+    cmp: fn(f32, f32) -> bool,
+) -> f32 {
+    let vals: Vec<f32> = args.into_iter().map(|v| resolve_val(v, globals)).collect();
+    bool_to_float(vals.windows(2).all(|w| cmp(w[0], w[1])))
+}
+
 pub fn resolve_lazy(ar: LazyArithmetic, globals: &std::sync::Arc<GlobalVariables>) -> f32 {
     match ar {
         LazyArithmetic::Add(mut args) => {
@@ -131,6 +160,31 @@ pub fn resolve_lazy(ar: LazyArithmetic, globals: &std::sync::Arc<GlobalVariables
             }
             accum
         }
+        LazyArithmetic::Lt(args) => resolve_comparison(args, globals, |a, b| a < b),
+        LazyArithmetic::Gt(args) => resolve_comparison(args, globals, |a, b| a > b),
+        LazyArithmetic::Lte(args) => resolve_comparison(args, globals, |a, b| a <= b),
+        LazyArithmetic::Gte(args) => resolve_comparison(args, globals, |a, b| a >= b),
+        LazyArithmetic::Eq(args) => resolve_comparison(args, globals, |a, b| a == b),
+        LazyArithmetic::And(args) => {
+            bool_to_float(args.into_iter().all(|v| resolve_val(v, globals) != 0.0))
+        }
+        LazyArithmetic::Or(args) => {
+            bool_to_float(args.into_iter().any(|v| resolve_val(v, globals) != 0.0))
+        }
+        LazyArithmetic::Not(mut args) => {
+            bool_to_float(args.is_empty() || resolve_val(args.remove(0), globals) == 0.0)
+        }
+        LazyArithmetic::If(args) => {
+            let mut args = args.into_iter();
+            let cond = args.next().map_or(0.0, |v| resolve_val(v, globals));
+            // only the branch that's taken is resolved
+            let branch = if cond != 0.0 {
+                args.next()
+            } else {
+                args.nth(1)
+            };
+            branch.map_or(0.0, |v| resolve_val(v, globals))
+        }
     }
 }
 
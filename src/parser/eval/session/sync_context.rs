@@ -1,7 +1,7 @@
 use crate::builtin_types::*;
 use crate::generator::Generator;
 use crate::parser::eval::resolver::resolve_globals;
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::session::SyncContext;
 use crate::{OutputMode, SampleAndWavematrixSet};
 
@@ -14,10 +14,11 @@ pub fn sync_context(
     globals: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     // eval-time resolve
     // ignore function name
     resolve_globals(&mut tail[1..], globals);
+    let args = tail.len() - 1;
     let mut tail_drain = tail.drain(1..);
 
     // name is the first symbol
@@ -39,7 +40,7 @@ pub fn sync_context(
         };
 
     if !active {
-        return Some(EvaluatedExpr::SyncContext(SyncContext {
+        return Ok(EvaluatedExpr::SyncContext(SyncContext {
             name,
             generators: Vec::new(),
             sync_to: None,
@@ -67,30 +68,49 @@ pub fn sync_context(
                     "sync" => {
                         collect_solo_tags = false;
                         collect_block_tags = false;
-                        if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Symbol(
-                            sync,
-                        ))) = tail_drain.next().unwrap()
+                        let position = args - tail_drain.len() + 1;
+                        if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                            Comparable::Symbol(sync),
+                        ))) = tail_drain.next()
                         {
                             sync_to = Some(sync);
+                        } else {
+                            return Err(EvalError::invalid_argument(
+                                position,
+                                "a symbol after :sync",
+                            ));
                         }
                     }
                     "resync" => {
                         collect_solo_tags = false;
                         collect_block_tags = false;
-                        if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Boolean(
-                            b,
-                        ))) = tail_drain.next().unwrap()
+                        let position = args - tail_drain.len() + 1;
+                        if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                            Comparable::Boolean(b),
+                        ))) = tail_drain.next()
                         {
                             resync = b;
+                        } else {
+                            return Err(EvalError::invalid_argument(
+                                position,
+                                "a boolean after :resync",
+                            ));
                         }
                     }
                     "shift" => {
                         collect_solo_tags = false;
                         collect_block_tags = false;
-                        if let EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f))) =
-                            tail_drain.next().unwrap()
+                        let position = args - tail_drain.len() + 1;
+                        if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+                            Comparable::Float(f),
+                        ))) = tail_drain.next()
                         {
                             shift = f as i32;
+                        } else {
+                            return Err(EvalError::invalid_argument(
+                                position,
+                                "a number after :shift",
+                            ));
                         }
                     }
                     "solo" => {
@@ -125,11 +145,22 @@ pub fn sync_context(
                 }
                 gens.append(&mut kl);
             }
+            // i.e. from (map ...)
+            EvaluatedExpr::Typed(TypedEntity::Vec(v)) => {
+                collect_solo_tags = false;
+                collect_block_tags = false;
+                for x in v {
+                    if let TypedEntity::Generator(mut k) = *x {
+                        k.id_tags.insert(name.clone());
+                        gens.push(k);
+                    }
+                }
+            }
             _ => println! {"ignored"},
         }
     }
 
-    Some(EvaluatedExpr::SyncContext(SyncContext {
+    Ok(EvaluatedExpr::SyncContext(SyncContext {
         name,
         generators: gens,
         sync_to,
@@ -160,7 +191,7 @@ mod tests {
             .std_lib
             .insert("nuc".to_string(), eval::constructors::nuc::nuc);
         functions.std_lib.insert("bd".to_string(), |_, _, _, _, _| {
-            Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
                 Comparable::String("bd".to_string()),
             )))
         });
@@ -1,5 +1,5 @@
 use crate::builtin_types::{Comparable, TypedEntity};
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{GlobalVariables, OutputMode, SampleAndWavematrixSet};
 
 use std::sync;
@@ -10,7 +10,7 @@ pub fn concat(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
@@ -25,7 +25,7 @@ pub fn concat(
         }
         Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) => s,
         _ => {
-            return None; /* first part needs to be symbol or string */
+            return Err(EvalError::invalid_argument(1, "a symbol or string"));
         }
     };
 
@@ -61,11 +61,11 @@ pub fn concat(
     }
 
     if sym {
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Symbol(accum),
         )))
     } else {
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::String(accum),
         )))
     }
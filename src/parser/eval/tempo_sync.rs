@@ -0,0 +1,62 @@
+use crate::builtin_types::*;
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
+use crate::{OutputMode, SampleAndWavematrixSet};
+
+use std::sync;
+
+/// join a tempo sync session, i.e.
+/// (tempo-sync "127.0.0.1:57300" "127.0.0.1:57301" :quantum 4)
+pub fn tempo_sync_join(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let local = if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s)))) =
+        tail_drain.next()
+    {
+        s
+    } else {
+        return Err(EvalError::invalid_argument(
+            1,
+            "a string (the local address)",
+        ));
+    };
+
+    let mut peers = Vec::new();
+    let mut quantum = 4.0;
+
+    while let Some(thing) = tail_drain.next() {
+        match thing {
+            EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::String(s))) => {
+                peers.push(s);
+            }
+            EvaluatedExpr::Keyword(k) if k.as_str() == "quantum" => {
+                if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
+                    tail_drain.next()
+                {
+                    quantum = f;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(EvaluatedExpr::Command(Command::TempoSyncJoin(
+        local, peers, quantum,
+    )))
+}
+
+pub fn tempo_sync_leave(
+    _: &FunctionMap,
+    _: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    Ok(EvaluatedExpr::Command(Command::TempoSyncLeave))
+}
@@ -2,7 +2,7 @@ use std::sync;
 
 use crate::{
     builtin_types::{Comparable, GlobalVariables, TypedEntity},
-    parser::{EvaluatedExpr, FunctionMap},
+    parser::{EvalError, EvaluatedExpr, FunctionMap},
     sample_set::SampleAndWavematrixSet,
     session::OutputMode,
 };
@@ -13,18 +13,18 @@ pub fn int(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Int32(f as i32),
         )))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -34,18 +34,18 @@ pub fn long(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Int64(f as i64),
         )))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -55,18 +55,18 @@ pub fn double(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
     if let Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) =
         tail_drain.next()
     {
-        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(
+        Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(
             Comparable::Double(f as f64),
         )))
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a number"))
     }
 }
 
@@ -76,20 +76,20 @@ pub fn pair(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(..);
     tail_drain.next(); // don't need the function name
 
     if let Some(EvaluatedExpr::Typed(t1)) = tail_drain.next() {
         if let Some(EvaluatedExpr::Typed(t2)) = tail_drain.next() {
-            Some(EvaluatedExpr::Typed(TypedEntity::Pair(
+            Ok(EvaluatedExpr::Typed(TypedEntity::Pair(
                 Box::new(t1),
                 Box::new(t2),
             )))
         } else {
-            None
+            Err(EvalError::invalid_argument(2, "a value"))
         }
     } else {
-        None
+        Err(EvalError::invalid_argument(1, "a value"))
     }
 }
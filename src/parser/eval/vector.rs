@@ -1,17 +1,52 @@
+use rand::seq::SliceRandom;
 use std::sync;
 
 use crate::builtin_types::*;
 
-use crate::parser::{EvaluatedExpr, FunctionMap};
+use crate::parser::eval::function::apply;
+use crate::parser::eval::logic::{resolve_truth, truth};
+use crate::parser::eval::resolver::resolve_globals;
+use crate::parser::{EvalError, EvaluatedExpr, FunctionMap};
 use crate::{OutputMode, SampleAndWavematrixSet};
 
+type Elements = Vec<Box<TypedEntity>>;
+
+// more than that is most likely a typo, and would eat up the memory
+const MAX_RANGE: usize = 1 << 20;
+
+fn vector_arg(arg: Option<EvaluatedExpr>, position: usize) -> Result<Elements, EvalError> {
+    match arg {
+        Some(EvaluatedExpr::Typed(TypedEntity::Vec(v))) => Ok(v),
+        _ => Err(EvalError::invalid_argument(position, "a vector")),
+    }
+}
+
+fn number_arg(arg: Option<EvaluatedExpr>, position: usize) -> Result<f32, EvalError> {
+    match arg {
+        Some(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => Ok(f),
+        _ => Err(EvalError::invalid_argument(position, "a number")),
+    }
+}
+
+// the elements of a vector can only be values
+fn element(result: EvaluatedExpr) -> Result<Box<TypedEntity>, EvalError> {
+    match result {
+        EvaluatedExpr::Typed(t) => Ok(Box::new(t)),
+        _ => Err(EvalError::failed("the function needs to return a value")),
+    }
+}
+
+fn typed(t: TypedEntity) -> EvaluatedExpr {
+    EvaluatedExpr::Typed(t)
+}
+
 pub fn vec(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let tail_drain = tail.drain(..).skip(1);
 
     let mut pvec = Vec::new();
@@ -22,7 +57,7 @@ pub fn vec(
         }
     }
 
-    Some(EvaluatedExpr::Typed(TypedEntity::Vec(pvec)))
+    Ok(EvaluatedExpr::Typed(TypedEntity::Vec(pvec)))
 }
 
 pub fn push(
@@ -31,7 +66,7 @@ pub fn push(
     _: &sync::Arc<GlobalVariables>,
     _: SampleAndWavematrixSet,
     _: OutputMode,
-) -> Option<EvaluatedExpr> {
+) -> Result<EvaluatedExpr, EvalError> {
     let mut tail_drain = tail.drain(1..);
 
     let place = match tail_drain.next() {
@@ -40,13 +75,397 @@ pub fn push(
             VariableId::Symbol(s)
         }
         _ => {
-            return None;
+            return Err(EvalError::invalid_argument(1, "a vector variable"));
         }
     };
 
     if let Some(EvaluatedExpr::Typed(t)) = tail_drain.next() {
-        Some(EvaluatedExpr::Command(Command::Push(place, t)))
+        Ok(EvaluatedExpr::Command(Command::Push(place, t)))
+    } else {
+        Err(EvalError::invalid_argument(2, "a value"))
+    }
+}
+
+/// `(map f v ...)` calls the function with one element of each vector
+/// at a time, as long as the shortest vector lasts, and collects the results.
+pub fn map(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(tail.get_mut(2..).unwrap_or_default(), globals);
+    let mut tail_drain = tail.drain(1..);
+
+    let Some(f) = tail_drain.next() else {
+        return Err(EvalError::invalid_argument(1, "a function"));
+    };
+    let mut vecs = Vec::new();
+    for (i, arg) in tail_drain.enumerate() {
+        vecs.push(vector_arg(Some(arg), i + 2)?.into_iter());
+    }
+    if vecs.is_empty() {
+        return Err(EvalError::invalid_argument(2, "a vector"));
+    }
+
+    let len = vecs.iter().map(|v| v.len()).min().unwrap_or(0);
+    let mut results = Vec::new();
+    for _ in 0..len {
+        let args = vecs
+            .iter_mut()
+            .filter_map(|v| v.next())
+            .map(|x| typed(*x))
+            .collect();
+        results.push(element(apply(
+            &f,
+            args,
+            functions,
+            globals,
+            sample_set.clone(),
+            out_mode,
+        )?)?);
+    }
+
+    Ok(typed(TypedEntity::Vec(results)))
+}
+
+/// `(filter f v)` the elements the function returns something true for
+pub fn filter(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(tail.get_mut(2..).unwrap_or_default(), globals);
+    let mut tail_drain = tail.drain(1..);
+
+    let Some(f) = tail_drain.next() else {
+        return Err(EvalError::invalid_argument(1, "a function"));
+    };
+    let v = vector_arg(tail_drain.next(), 2)?;
+
+    let mut results = Vec::new();
+    for x in v {
+        let result = apply(
+            &f,
+            vec![typed((*x).clone())],
+            functions,
+            globals,
+            sample_set.clone(),
+            out_mode,
+        )?;
+        let keep = truth(&result).unwrap_or_else(|| resolve_truth(result, globals));
+        if keep {
+            results.push(x);
+        }
+    }
+
+    Ok(typed(TypedEntity::Vec(results)))
+}
+
+/// `(reduce f v)` or `(reduce f start v)`, calls the function with the
+/// result so far and the next element, i.e. `(reduce add (vec 1 2 3))` is 6.
+pub fn reduce(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    sample_set: SampleAndWavematrixSet,
+    out_mode: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(tail.get_mut(2..).unwrap_or_default(), globals);
+    let mut tail_drain = tail.drain(1..);
+
+    let Some(f) = tail_drain.next() else {
+        return Err(EvalError::invalid_argument(1, "a function"));
+    };
+    let (start, v) = match (tail_drain.next(), tail_drain.next()) {
+        (Some(start), Some(v)) => (Some(start), vector_arg(Some(v), 3)?),
+        (v, _) => (None, vector_arg(v, 2)?),
+    };
+
+    let mut elements = v.into_iter().map(|x| typed(*x));
+    let Some(mut result) = start.or_else(|| elements.next()) else {
+        return Err(EvalError::failed(
+            "needs a start value if the vector is empty",
+        ));
+    };
+    for x in elements {
+        result = apply(
+            &f,
+            vec![result, x],
+            functions,
+            globals,
+            sample_set.clone(),
+            out_mode,
+        )?;
+    }
+
+    Ok(result)
+}
+
+/// `(range 4)` is `(vec 0 1 2 3)`, `(range 2 4)` is `(vec 2 3)` and
+/// `(range 0 1 0.25)` is `(vec 0 0.25 0.5 0.75)`
+pub fn range(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+    let args = tail.len() - 1;
+    let mut tail_drain = tail.drain(1..);
+
+    let first = number_arg(tail_drain.next(), 1)?;
+    let (start, end) = if args > 1 {
+        (first, number_arg(tail_drain.next(), 2)?)
+    } else {
+        (0.0, first)
+    };
+    let step = if args > 2 {
+        number_arg(tail_drain.next(), 3)?
     } else {
-        None
+        1.0
+    };
+    if step == 0.0 {
+        return Err(EvalError::invalid_argument(3, "a step other than 0"));
+    }
+    if let Some(i) = [first, end, step][..args.min(3)]
+        .iter()
+        .position(|x| !x.is_finite())
+    {
+        return Err(EvalError::invalid_argument(i + 1, "a finite number"));
+    }
+
+    let count = ((end - start) / step).ceil().max(0.0);
+    if count > MAX_RANGE as f32 {
+        return Err(EvalError::invalid_argument(
+            args.min(3),
+            &format!("at most {MAX_RANGE} elements in the range"),
+        ));
+    }
+    let count = count as usize;
+    Ok(typed(TypedEntity::Vec(
+        (0..count)
+            .map(|i| {
+                Box::new(TypedEntity::Comparable(Comparable::Float(
+                    start + i as f32 * step,
+                )))
+            })
+            .collect(),
+    )))
+}
+
+/// `(nth v 0)` is the first element
+pub fn nth(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+    let mut tail_drain = tail.drain(1..);
+
+    let mut v = vector_arg(tail_drain.next(), 1)?;
+    let idx = number_arg(tail_drain.next(), 2)?;
+    if idx < 0.0 || idx as usize >= v.len() {
+        return Err(EvalError::invalid_argument(
+            2,
+            &format!("an index below the length ({})", v.len()),
+        ));
+    }
+
+    Ok(typed(*v.swap_remove(idx as usize)))
+}
+
+pub fn len(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+    let v = vector_arg(tail.drain(1..).next(), 1)?;
+
+    Ok(typed(TypedEntity::Comparable(Comparable::Float(
+        v.len() as f32
+    ))))
+}
+
+/// `(zip (vec 1 2) (vec 3 4))` is `(vec (vec 1 3) (vec 2 4))`, as long as
+/// the shortest vector lasts
+pub fn zip(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+
+    let mut vecs = Vec::new();
+    for (i, arg) in tail.drain(1..).enumerate() {
+        vecs.push(vector_arg(Some(arg), i + 1)?.into_iter());
+    }
+
+    let len = vecs.iter().map(|v| v.len()).min().unwrap_or(0);
+    let zipped = (0..len)
+        .map(|_| {
+            Box::new(TypedEntity::Vec(
+                vecs.iter_mut().filter_map(|v| v.next()).collect(),
+            ))
+        })
+        .collect();
+
+    Ok(typed(TypedEntity::Vec(zipped)))
+}
+
+/// the elements in random order
+pub fn shuffle(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+    let mut v = vector_arg(tail.drain(1..).next(), 1)?;
+
+    v.shuffle(&mut rand::thread_rng());
+    Ok(typed(TypedEntity::Vec(v)))
+}
+
+/// a random element
+pub fn choose(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    globals: &sync::Arc<GlobalVariables>,
+    _: SampleAndWavematrixSet,
+    _: OutputMode,
+) -> Result<EvaluatedExpr, EvalError> {
+    resolve_globals(&mut tail[1..], globals);
+    let v = vector_arg(tail.drain(1..).next(), 1)?;
+
+    match v.choose(&mut rand::thread_rng()) {
+        Some(x) => Ok(typed((**x).clone())),
+        None => Err(EvalError::failed("can't choose from an empty vector")),
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::eval_from_str;
+    use crate::standard_library::define_standard_library;
+
+    fn floats(result: Result<EvaluatedExpr, EvalError>) -> Vec<f32> {
+        match result {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Vec(v))) => v
+                .into_iter()
+                .map(|x| match *x {
+                    TypedEntity::Comparable(Comparable::Float(f)) => f,
+                    x => panic!("{x:?}"),
+                })
+                .collect(),
+            e => panic!("{e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_vector_functions() {
+        let functions = define_standard_library();
+        let globals = sync::Arc::new(GlobalVariables::new());
+        globals.insert(
+            VariableId::Custom("notes".to_string()),
+            TypedEntity::Vec(vec![
+                Box::new(TypedEntity::Comparable(Comparable::Float(60.0))),
+                Box::new(TypedEntity::Comparable(Comparable::Float(64.0))),
+            ]),
+        );
+
+        let eval = |src: &str| {
+            eval_from_str(
+                src,
+                &functions,
+                &globals,
+                SampleAndWavematrixSet::new(),
+                OutputMode::Stereo,
+            )
+        };
+        let float = |src: &str| match eval(src) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Comparable(Comparable::Float(f)))) => f,
+            e => panic!("{src}: {e:?}"),
+        };
+
+        assert_eq!(floats(eval("(range 4)")), vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(floats(eval("(range 2 4)")), vec![2.0, 3.0]);
+        assert_eq!(floats(eval("(range 0 1 0.25)")), vec![0.0, 0.25, 0.5, 0.75]);
+        assert_eq!(floats(eval("(range 3 0 -1)")), vec![3.0, 2.0, 1.0]);
+        // too many elements, or infinitely many
+        assert!(matches!(
+            eval("(range 0 1 0.0000001)"),
+            Err(EvalError::InvalidArgument { position: 3, expected, .. }) if expected.starts_with("at most")
+        ));
+        assert!(matches!(
+            eval("(range 0 (div 1 0))"),
+            Err(EvalError::InvalidArgument { position: 2, expected, .. }) if expected == "a finite number"
+        ));
+
+        assert_eq!(
+            floats(eval("(map (lambda (x) (mul x 2)) (range 3))")),
+            vec![0.0, 2.0, 4.0]
+        );
+        assert_eq!(
+            floats(eval("(map add (vec 1 2) (vec 10 20 30))")),
+            vec![11.0, 22.0]
+        );
+        assert_eq!(
+            floats(eval("(map (lambda (n) (add n 12)) notes)")),
+            vec![72.0, 76.0]
+        );
+        assert_eq!(
+            floats(eval("(filter (lambda (x) (gt x 1)) (range 4))")),
+            vec![2.0, 3.0]
+        );
+
+        assert_eq!(float("(reduce add (range 5))"), 10.0);
+        assert_eq!(float("(reduce add 10 (vec))"), 10.0);
+        assert_eq!(float("(reduce add (shuffle (vec 1 2 3)))"), 6.0);
+        assert_eq!(float("(len notes)"), 2.0);
+        assert_eq!(float("(nth notes 1)"), 64.0);
+        assert_eq!(float("(choose (vec 5 5))"), 5.0);
+
+        match eval("(zip (vec 1 2) (vec 3 4 5))") {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Vec(v))) => {
+                assert_eq!(v.len(), 2);
+                assert!(matches!(&*v[1], TypedEntity::Vec(pair) if pair.len() == 2));
+            }
+            e => panic!("{e:?}"),
+        }
+
+        // generators made in a loop
+        match eval("(sx 'ab #t (map (lambda (n) (nuc n (saw 100))) (vec 'a 'b)))") {
+            Ok(EvaluatedExpr::SyncContext(s)) => assert_eq!(s.generators.len(), 2),
+            e => panic!("{e:?}"),
+        }
+
+        assert!(matches!(
+            eval("(nth notes 2)"),
+            Err(EvalError::InvalidArgument { position: 2, .. })
+        ));
+        assert!(matches!(
+            eval("(reduce add (vec))"),
+            Err(EvalError::Failed { .. })
+        ));
+        // errors in the function say which one it was
+        assert!(matches!(
+            eval("(map mtof notes)"),
+            Err(EvalError::InvalidArgument { function, .. }) if function == "mtof"
+        ));
     }
 }
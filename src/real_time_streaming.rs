@@ -58,6 +58,24 @@ pub struct Catch<const MAX: usize, const NCHAN: usize> {
     write_interval_ms: f64,
 }
 
+impl<const MAX: usize, const NCHAN: usize> Catch<MAX, NCHAN> {
+    /// Hand whatever arrived so far to `f` and give it back to the
+    /// thrower. Doesn't wait, so it can be called from a UI thread.
+    pub fn drain(&self, mut f: impl FnMut(&StreamItem<MAX, NCHAN>)) {
+        for mut stream_item in self.catch_q.try_iter() {
+            f(&stream_item);
+            stream_item.size = 0;
+            let _ = self.return_q.send(stream_item);
+        }
+    }
+}
+
+/// The output as it's being played, for meters and scopes.
+pub struct OutputMonitor<const MAX: usize, const NCHAN: usize> {
+    pub catch: Catch<MAX, NCHAN>,
+    pub samplerate: u32,
+}
+
 pub struct CatchHandle<const MAX: usize, const NCHAN: usize> {
     pub handle: Option<thread::JoinHandle<Catch<MAX, NCHAN>>>,
     pub running: sync::Arc<AtomicBool>,
@@ -208,4 +226,26 @@ mod tests {
 
         stop_writer_thread(handle);
     }
+
+    #[test]
+    fn test_drain() {
+        let (throw, catch) = init_real_time_stream::<512, 2>(0.01, 0.1);
+        let buf: [[f32; 512]; 2] = [[0.5; 512]; 2];
+
+        for _ in 0..3 {
+            throw.write_samples(&buf, 256);
+        }
+
+        let mut blocks = 0;
+        catch.drain(|item| {
+            assert_eq!(item.size, 256);
+            assert_eq!(item.buffer[1][255], 0.5);
+            blocks += 1;
+        });
+        assert_eq!(blocks, 3);
+
+        // the items are back and can be thrown again
+        throw.write_samples(&buf, 512);
+        catch.drain(|item| assert_eq!(item.size, 512));
+    }
 }
@@ -8,7 +8,7 @@ use rustyline::Editor;
 use crate::interpreter;
 
 use crate::parser;
-use crate::parser::FunctionMap;
+use crate::parser::{EvalError, FunctionMap};
 
 use crate::session::Session;
 
@@ -42,19 +42,17 @@ pub fn start_repl<const BUFSIZE: usize, const NCHAN: usize>(
 
                 match pfa_in {
                     Err(e) => {
-                        // this needs a more elegant way ... like,
-                        // retrieve the actual error from the parser, instead
-                        // of looking for the string ...
                         // if the error is that a closing paren is missing,
                         // assume we're waiting for more lines.
                         // once a complete input is found,
-                        if e.contains("closing paren") {
+                        if let EvalError::UnclosedParen { .. } = e {
                             let mut line_buffer: String = "".to_string();
                             line_buffer.push_str(line.as_str());
                             loop {
                                 let readline_inner = rl.readline(".. ");
                                 match readline_inner {
                                     Ok(line) => {
+                                        line_buffer.push('\n');
                                         line_buffer.push_str(line.as_str());
                                         let inner_pfa_in = parser::eval_from_str(
                                             line_buffer.as_str(),
@@ -74,10 +72,15 @@ pub fn start_repl<const BUFSIZE: usize, const NCHAN: usize>(
                                                 rl.add_history_entry(line_buffer.as_str());
                                                 break;
                                             }
-                                            Err(_) => {
+                                            Err(EvalError::UnclosedParen { .. }) => {
                                                 // wait for more input ...
                                                 continue;
                                             }
+                                            Err(e) => {
+                                                println!("{}", e.report(&line_buffer));
+                                                rl.add_history_entry(line_buffer.as_str());
+                                                break;
+                                            }
                                         }
                                     }
                                     Err(_) => {
@@ -86,7 +89,7 @@ pub fn start_repl<const BUFSIZE: usize, const NCHAN: usize>(
                                 }
                             }
                         } else {
-                            println!("parser error {e}");
+                            println!("{}", e.report(&line));
                         }
                     }
                     Ok(pfa) => {
@@ -71,6 +71,15 @@ impl SampleAndWavematrixSet {
         });
     }
 
+    /// the names of the sets that have samples in them
+    pub fn names(&self) -> Vec<String> {
+        self.subsets
+            .iter()
+            .filter(|s| !s.value().is_empty())
+            .map(|s| s.key().clone())
+            .collect()
+    }
+
     pub fn exists_not_empty(&self, set: &str) -> bool {
         self.subsets.contains_key(set) && !self.subsets.get(set).unwrap().is_empty()
     }
@@ -1,12 +1,14 @@
+use crate::clock::SchedulerClock;
 use crate::generator::Generator;
+use crate::osc_sender::osc_time_at;
 use crate::session::Session;
 use crossbeam::atomic::AtomicCell;
 use dashmap::DashSet;
 use parking_lot::Mutex;
+use rosc::OscTime;
 
 use std::collections::BTreeSet;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
 use std::{sync, thread};
 
 /// A simple time-recursion event scheduler running at a fixed time interval.
@@ -23,7 +25,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Default for Scheduler<BUFSIZE, NC
 
 #[derive(Clone)]
 pub struct SchedulerData<const BUFSIZE: usize, const NCHAN: usize> {
-    pub start_time: std::sync::Arc<Mutex<Instant>>,
+    // start time in terms of the session clock
+    pub start_time: std::sync::Arc<AtomicCell<f64>>,
     pub stream_time: std::sync::Arc<AtomicCell<f64>>,
     pub logical_time: std::sync::Arc<AtomicCell<f64>>,
     pub last_diff: std::sync::Arc<AtomicCell<f64>>,
@@ -37,6 +40,15 @@ pub struct SchedulerData<const BUFSIZE: usize, const NCHAN: usize> {
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
+    /// The OSC time tag for the current step, that is, the logical time
+    /// plus latency, so anything sent via OSC lines up with the audio.
+    pub fn osc_time(&self, clock: &dyn SchedulerClock, latency: f64) -> OscTime {
+        osc_time_at(
+            clock,
+            self.start_time.load() + self.logical_time.load() + latency,
+        )
+    }
+
     /// update this scheduler data with a new generator and shift
     /// adjustments
     pub fn update(
@@ -130,6 +142,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
     pub fn new(
         data: Generator,
         shift: f64,
+        start_time: f64,
         stream_time: f64,
         block_tags_in: BTreeSet<String>,
         solo_tags_in: BTreeSet<String>,
@@ -145,7 +158,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
         }
 
         SchedulerData {
-            start_time: sync::Arc::new(Mutex::new(Instant::now())),
+            start_time: sync::Arc::new(AtomicCell::new(start_time)),
             stream_time: sync::Arc::new(AtomicCell::new(stream_time + shift)),
             logical_time: sync::Arc::new(AtomicCell::new(shift)),
             last_diff: sync::Arc::new(AtomicCell::new(0.0)),
@@ -178,6 +191,11 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Scheduler<BUFSIZE, NCHAN> {
         self.running.store(true, Ordering::SeqCst);
         let running = self.running.clone();
 
+        // the clock needs to know about the new thread before
+        // it's actually running, otherwise a virtual clock might advance
+        // before the first events are scheduled
+        session.clock.enter();
+
         let builder = thread::Builder::new().name(name.into());
 
         self.handle = Some(
@@ -209,11 +227,16 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Scheduler<BUFSIZE, NCHAN> {
 			    if end {
 				sched_data.finished.store(true, Ordering::SeqCst);
 				running.store(false, Ordering::SeqCst);
-				return;
+				break;
 			    }
-			    cur = sched_data.start_time.lock().elapsed().as_secs_f64();
-                            sched_data.last_diff.store(cur - sched_data.logical_time.load());
                             next = sched_result.0;
+                            cur = session.clock.now() - sched_data.start_time.load();
+                            if session.clock.is_virtual() {
+                                // virtual time is never late
+                                sched_data.last_diff.store(0.0);
+                            } else {
+                                sched_data.last_diff.store(cur - sched_data.logical_time.load());
+                            }
                             ldif = sched_data.last_diff.load();
                             // compensate for eventual lateness ...
                             if (next - ldif) < 0.0 {
@@ -222,21 +245,26 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Scheduler<BUFSIZE, NCHAN> {
                                     "{} negative duration found: cur before {} cur after {} {} {} {}",
                                     handle.name().unwrap(),
                                     cur,
-				    sched_data.start_time.lock().elapsed().as_secs_f64(),
+				    session.clock.now() - sched_data.start_time.load(),
                                     sched_data.logical_time.load(),
                                     next,
                                     ldif
                                 );
 				sched_data.finished.store(true, Ordering::SeqCst);
 				running.store(false, Ordering::SeqCst);
-				return;
+				break;
                             }
                             sched_data.logical_time.store(sched_data.logical_time.load() + next);
                             sched_data.stream_time.store(sched_data.stream_time.load() + next);
                         }
 
-                        thread::sleep(Duration::from_secs_f64(next - ldif));
+                        session.clock.wait_until(
+                            sched_data.start_time.load() + sched_data.logical_time.load(),
+                            &running,
+                        );
                     }
+
+                    session.clock.leave();
                 })
                 .unwrap(),
         );
@@ -260,3 +288,150 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Scheduler<BUFSIZE, NCHAN> {
         }
     }
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_types::{GlobalVariables, TypedEntity};
+    use crate::clock::{ManualClock, SchedulerClock};
+    use crate::midi_output::MidiOutputs;
+    use crate::modules::Modules;
+    use crate::osc_client::OscClient;
+    use crate::parser::{eval_from_str, EvaluatedExpr};
+    use crate::sample_set::SampleAndWavematrixSet;
+    use crate::session::{OutputMode, SyncMode};
+    use crate::standard_library::define_standard_library;
+    use dashmap::DashMap;
+    use parking_lot::RwLock;
+    use ruffbox_synth::ruffbox::{init_ruffbox, ReverbMode, RuffboxPlayhead};
+
+    type TestSession = (
+        Session<512, 2>,
+        sync::Arc<ManualClock>,
+        RuffboxPlayhead<512, 2>,
+    );
+
+    fn manual_clock_session() -> TestSession {
+        // keep the playhead, otherwise events can't be sent
+        let (controls, playhead) =
+            init_ruffbox::<512, 2>(1, 3.0, &ReverbMode::FreeVerb, 44100.0, 100, 10, false);
+        let clock = sync::Arc::new(ManualClock::new());
+
+        let session = Session {
+            schedulers: sync::Arc::new(DashMap::new()),
+            contexts: sync::Arc::new(DashMap::new()),
+            osc_client: OscClient::new(),
+            rec_control: sync::Arc::new(Mutex::new(None)),
+            monitor: sync::Arc::new(Mutex::new(None)),
+            globals: sync::Arc::new(GlobalVariables::new()),
+            sample_set: SampleAndWavematrixSet::new(),
+            ruffbox: sync::Arc::new(controls),
+            output_mode: OutputMode::Stereo,
+            sync_mode: SyncMode::NotOnSilence,
+            clock: sync::Arc::clone(&clock) as sync::Arc<dyn SchedulerClock>,
+            tempo_sync: sync::Arc::new(RwLock::new(None)),
+            midi_clock: sync::Arc::new(Mutex::new(None)),
+            midi_clock_follower: sync::Arc::new(Mutex::new(None)),
+            midi_out: MidiOutputs::new(),
+            muted: sync::Arc::new(DashSet::new()),
+            soloed: sync::Arc::new(DashSet::new()),
+            modules: sync::Arc::new(Mutex::new(Modules::new())),
+        };
+
+        (session, clock, playhead)
+    }
+
+    #[test]
+    fn test_manual_clock_drives_scheduler() {
+        let (session, clock, _playhead) = manual_clock_session();
+
+        let functions = define_standard_library();
+        let gen = match eval_from_str(
+            "(nuc 'da (saw 100))",
+            &functions,
+            &session.globals,
+            session.sample_set.clone(),
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Generator(g))) => g,
+            _ => panic!(),
+        };
+        let id_tags = gen.id_tags.clone();
+
+        Session::start_generator_no_sync(gen, &session, 0.0, &BTreeSet::new(), &BTreeSet::new());
+
+        let logical_time = || {
+            session
+                .schedulers
+                .get(&id_tags)
+                .unwrap()
+                .1
+                .logical_time
+                .load()
+        };
+
+        // the first event is scheduled right away, the scheduler waits
+        // for the clock to reach the next one (default duration is 200ms)
+        clock.advance_to(0.0);
+        assert!((logical_time() - 0.2).abs() < 0.0001);
+
+        // nothing happens before the next event is due
+        clock.advance_to(0.19);
+        assert!((logical_time() - 0.2).abs() < 0.0001);
+
+        clock.advance_to(0.5);
+        assert!((logical_time() - 0.6).abs() < 0.0001);
+
+        // several events at once
+        clock.advance(0.55);
+        assert!((logical_time() - 1.2).abs() < 0.0001);
+
+        // virtual time is never late
+        assert_eq!(
+            session.schedulers.get(&id_tags).unwrap().1.last_diff.load(),
+            0.0
+        );
+
+        // stopping doesn't need the clock to move
+        Session::stop_generator(&session, &id_tags);
+        assert!(session.schedulers.is_empty());
+    }
+
+    #[test]
+    fn test_generator_info() {
+        let (session, clock, _playhead) = manual_clock_session();
+
+        let functions = define_standard_library();
+        let gen = match eval_from_str(
+            "(cyc 'bz \"saw:100 sqr:200\")",
+            &functions,
+            &session.globals,
+            session.sample_set.clone(),
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::Typed(TypedEntity::Generator(g))) => g,
+            _ => panic!(),
+        };
+        let id_tags = gen.id_tags.clone();
+
+        Session::start_generator_no_sync(gen, &session, 0.0, &BTreeSet::new(), &BTreeSet::new());
+        clock.advance_to(0.0);
+
+        let info = Session::generator_info(&session);
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].id_tags, id_tags);
+        assert!(info[0].current_symbol.is_some());
+        assert!(!info[0].muted && !info[0].soloed);
+
+        Session::mute_generator(&session, &id_tags, true);
+        Session::solo_generator(&session, &id_tags, true);
+        let info = Session::generator_info(&session);
+        assert!(info[0].muted && info[0].soloed);
+
+        // stopping forgets about it
+        Session::stop_generator(&session, &id_tags);
+        assert!(Session::generator_info(&session).is_empty());
+        assert!(session.muted.is_empty() && session.soloed.is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use dashmap::{DashMap, DashSet};
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use std::collections::{BTreeSet, HashMap};
 use std::{sync, thread};
 
@@ -7,16 +7,23 @@ use ruffbox_synth::building_blocks::{SynthParameterLabel, SynthParameterValue};
 use ruffbox_synth::ruffbox::RuffboxControls;
 
 use crate::builtin_types::{Command, ConfigParameter, GlobalVariables, VariableId};
+use crate::clock::SchedulerClock;
 use crate::commands;
 use crate::event::InterpretableEvent;
 use crate::event_helpers::*;
 use crate::generator::Generator;
+use crate::midi_clock::MidiClockOutput;
+use crate::midi_output::MidiOutputs;
+use crate::modules::Modules;
 use crate::osc_client::OscClient;
 use crate::parameter::*;
 use crate::real_time_streaming;
 use crate::scheduler::{Scheduler, SchedulerData};
+use crate::tempo_sync::TempoSync;
+use crate::visualizer_client::GeneratorGraph;
 use crate::SampleAndWavematrixSet;
 use crate::TypedEntity;
+use midir::MidiInputConnection;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum OutputMode {
@@ -65,6 +72,36 @@ pub struct Session<const BUFSIZE: usize, const NCHAN: usize> {
     pub osc_client: OscClient,
     pub rec_control:
         sync::Arc<Mutex<Option<real_time_streaming::RecordingControl<BUFSIZE, NCHAN>>>>,
+    // the output as it's played, if there's an audio output
+    pub monitor: sync::Arc<Mutex<Option<real_time_streaming::OutputMonitor<BUFSIZE, NCHAN>>>>,
+    // the time source the schedulers follow
+    pub clock: sync::Arc<dyn SchedulerClock>,
+    // shared tempo and beat grid with other peers, if any
+    pub tempo_sync: sync::Arc<RwLock<Option<TempoSync>>>,
+    // midi clock output, if any
+    pub midi_clock: sync::Arc<Mutex<Option<MidiClockOutput>>>,
+    // incoming midi clock the tempo follows, if any
+    pub midi_clock_follower: sync::Arc<Mutex<Option<MidiInputConnection<()>>>>,
+    // midi output ports and routes
+    pub midi_out: MidiOutputs,
+    // generators muted or soloed by hand (i.e. in the editor), these stay
+    // that way when the generator is re-evaluated
+    pub muted: sync::Arc<DashSet<BTreeSet<String>>>,
+    pub soloed: sync::Arc<DashSet<BTreeSet<String>>>,
+    // the files loaded with import
+    pub modules: sync::Arc<Mutex<Modules>>,
+}
+
+/// A snapshot of a running generator, for display.
+pub struct GeneratorInfo {
+    pub id_tags: BTreeSet<String>,
+    pub context: Option<String>,
+    // the label of the current symbol, if it has one
+    pub current_symbol: Option<String>,
+    pub block_tags: BTreeSet<String>,
+    pub solo_tags: BTreeSet<String>,
+    pub muted: bool,
+    pub soloed: bool,
 }
 
 // naive disjoint test, assume unsorted
@@ -114,8 +151,11 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
         latency = global_latency.evaluate_numerical() as f64;
     }
 
+    // for everything that leaves via osc
+    let timetag = data.osc_time(&*session.clock, latency);
+
     // GENERATOR LOCK !!!
-    let (time, mut events, end_state) = {
+    let (time, mut events, end_state, silenced) = {
         // HERE IT IS ... LOCK, LOCK, LOCK
         let mut gen = data.generator.lock();
 
@@ -127,12 +167,12 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
             if let Some(cli) = session.osc_client.vis.try_read() {
                 if let Some(ref vc) = *cli {
                     if gen.root_generator.is_modified() {
-                        vc.create_or_update(&gen);
+                        vc.create_or_update(&gen, timetag);
                         gen.root_generator.clear_modified()
                     }
-                    vc.update_active_node(&gen);
+                    vc.update_active_node(&gen, timetag);
                     for (_, proc) in gen.processors.iter_mut() {
-                        proc.visualize_if_possible(vc);
+                        proc.visualize_if_possible(vc, timetag);
                     }
                 }
             }
@@ -148,11 +188,14 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
 
         // retrieve the current events
         let events = gen.current_events(&session.globals);
+        // muted by hand, or something else is soloed
+        let silenced = session.muted.contains(&gen.id_tags)
+            || (!session.soloed.is_empty() && !session.soloed.contains(&gen.id_tags));
         //if events.is_empty() {
         //    println!("really no events");
         //}
         let end_state = gen.reached_end_state();
-        (time, events, end_state)
+        (time, events, end_state, silenced)
     }; // END GENERATOR LOCK ...
 
     // the sync flag will be returned alongside the
@@ -198,6 +241,24 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                     continue;
                 }
 
+                if silenced {
+                    continue;
+                }
+
+                // midi events (or events routed to midi) don't go to the synth
+                if session
+                    .midi_out
+                    .handle_event(s, data.stream_time.load() + latency)
+                {
+                    continue;
+                }
+
+                // same for events routed to osc, which are sent as
+                // bundles, so the receiver can play them in time
+                if session.osc_client.handle_event(s, timetag) {
+                    continue;
+                }
+
                 // if this is a sampler event and contains a sample lookup,
                 // resolve it NOW ... at the very end, finally ...
                 let mut bufnum: usize = 0;
@@ -283,6 +344,7 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                             }
                             Command::Tmod(p) => {
                                 commands::set_global_tmod(&session.globals, p);
+                                commands::publish_tempo(session);
                             }
                             Command::GlobRes(v) => {
                                 commands::set_global_lifemodel_resources(&session.globals, v);
@@ -305,6 +367,15 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                                 //println!("handle once from gen");
                                 commands::once(session, &mut s, &c);
                             }
+                            Command::OscSendMessage(client_name, osc_addr, args) => {
+                                commands::send_osc_message(
+                                    session,
+                                    &client_name,
+                                    osc_addr,
+                                    &args,
+                                    Some(timetag),
+                                );
+                            }
 
                             _ => {
                                 println!("ignore command")
@@ -388,6 +459,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
                 }
             } // END INTERNAL SYNC
 
+            // unsynced generators start on the next bar of the
+            // shared beat grid, if there is one
+            let start_shift = ctx.shift as f64 * 0.001 + commands::tempo_sync_phase(session);
+
             // HANDLE NEWCOMERS
             if let Some(ext_sync) = external_sync.clone() {
                 // external sync has precedence
@@ -416,7 +491,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
                     Session::start_generator_no_sync(
                         gen,
                         session,
-                        ctx.shift as f64 * 0.001,
+                        start_shift,
                         &ctx.block_tags,
                         &ctx.solo_tags,
                     );
@@ -518,7 +593,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
                         Session::start_generator_no_sync(
                             gen,
                             session,
-                            ctx.shift as f64 * 0.001,
+                            start_shift,
                             &ctx.block_tags,
                             &ctx.solo_tags,
                         );
@@ -705,6 +780,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         let sched_data = SchedulerData::<BUFSIZE, NCHAN>::new(
             gen,
             shift,
+            session.clock.now(),
             session.ruffbox.get_now(),
             block_tags.clone(),
             solo_tags.clone(),
@@ -770,6 +846,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         }
         println!("\'");
 
+        session.muted.remove(gen_name);
+        session.soloed.remove(gen_name);
+
         if let Some((_, (mut sched, data))) = session.schedulers.remove(gen_name) {
             if session
                 .osc_client
@@ -812,6 +891,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         let mut sched_proxies = Vec::new();
 
         for name in gen_names.iter() {
+            session.muted.remove(name);
+            session.soloed.remove(name);
+
             if let Some((_, v)) = session.schedulers.remove(name) {
                 sched_proxies.push(v);
             }
@@ -856,6 +938,80 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         }
     }
 
+    pub fn mute_generator(
+        session: &Session<BUFSIZE, NCHAN>,
+        gen_name: &BTreeSet<String>,
+        mute: bool,
+    ) {
+        if mute {
+            session.muted.insert(gen_name.clone());
+        } else {
+            session.muted.remove(gen_name);
+        }
+    }
+
+    /// Soloed generators are the only ones that can be heard, several
+    /// generators can be soloed at once.
+    pub fn solo_generator(
+        session: &Session<BUFSIZE, NCHAN>,
+        gen_name: &BTreeSet<String>,
+        solo: bool,
+    ) {
+        if solo {
+            session.soloed.insert(gen_name.clone());
+        } else {
+            session.soloed.remove(gen_name);
+        }
+    }
+
+    /// what's running at the moment, sorted by id
+    pub fn generator_info(session: &Session<BUFSIZE, NCHAN>) -> Vec<GeneratorInfo> {
+        let mut info = Vec::new();
+        for sc in session.schedulers.iter() {
+            let (id_tags, (_, data)) = sc.pair();
+
+            let context = session
+                .contexts
+                .iter()
+                .find(|ctx| ctx.value().contains(id_tags))
+                .map(|ctx| ctx.key().clone());
+
+            let current_symbol = {
+                let gen = data.generator.lock();
+                let root = &gen.root_generator;
+                root.last_symbol.map(|sym| {
+                    root.label_mapping
+                        .as_ref()
+                        .and_then(|labels| labels.get(&sym).cloned())
+                        .unwrap_or_else(|| sym.to_string())
+                })
+            };
+
+            info.push(GeneratorInfo {
+                id_tags: id_tags.clone(),
+                context,
+                current_symbol,
+                block_tags: data.block_tags.iter().map(|t| t.key().clone()).collect(),
+                solo_tags: data.solo_tags.iter().map(|t| t.key().clone()).collect(),
+                muted: session.muted.contains(id_tags),
+                soloed: session.soloed.contains(id_tags),
+            });
+        }
+        info.sort_by(|a, b| a.id_tags.cmp(&b.id_tags));
+        info
+    }
+
+    /// the graphs of what's running at the moment, sorted by id
+    pub fn generator_graphs(session: &Session<BUFSIZE, NCHAN>) -> Vec<GeneratorGraph> {
+        let mut graphs: Vec<GeneratorGraph> = session
+            .schedulers
+            .iter()
+            .map(|sc| GeneratorGraph::from_generator(&sc.value().1.generator.lock()))
+            .collect();
+        graphs.sort_by(|a, b| a.id_tags.cmp(&b.id_tags));
+        graphs
+    }
+
     pub fn clear_session(session: Session<BUFSIZE, NCHAN>) {
         for mut sc in session.schedulers.iter_mut() {
             let (sched, _) = sc.value_mut();
@@ -892,5 +1048,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
 
         session.schedulers.clear();
         session.contexts.clear();
+        session.muted.clear();
+        session.soloed.clear();
+        session.modules.lock().stop_watching();
     }
 }
@@ -50,6 +50,7 @@ pub fn define_standard_library() -> FunctionMap {
     standard_library.std_lib.insert("import-sample-set".to_string(), eval::commands::import_sample_set);
     standard_library.std_lib.insert("print".to_string(), eval::print::print);
     standard_library.std_lib.insert("load-file".to_string(), eval::commands::load_file);
+    standard_library.std_lib.insert("import".to_string(), eval::commands::import);
 
     // progn and other constructs
     standard_library.std_lib.insert("progn".to_string(), eval::progn::progn);
@@ -61,6 +62,14 @@ pub fn define_standard_library() -> FunctionMap {
     // container structs
     standard_library.std_lib.insert("vec".to_string(), eval::vector::vec);
     standard_library.std_lib.insert("push".to_string(), eval::vector::push);
+    standard_library.std_lib.insert("filter".to_string(), eval::vector::filter);
+    standard_library.std_lib.insert("reduce".to_string(), eval::vector::reduce);
+    standard_library.std_lib.insert("range".to_string(), eval::vector::range);
+    standard_library.std_lib.insert("nth".to_string(), eval::vector::nth);
+    standard_library.std_lib.insert("len".to_string(), eval::vector::len);
+    standard_library.std_lib.insert("zip".to_string(), eval::vector::zip);
+    standard_library.std_lib.insert("shuffle".to_string(), eval::vector::shuffle);
+    standard_library.std_lib.insert("choose".to_string(), eval::vector::choose);
     standard_library.std_lib.insert("map".to_string(), eval::map::map);
     standard_library.std_lib.insert("insert".to_string(), eval::map::insert);
     
@@ -87,6 +96,7 @@ pub fn define_standard_library() -> FunctionMap {
     standard_library.std_lib.insert("white".to_string(), eval::events::sound::sound);
     standard_library.std_lib.insert("brown".to_string(), eval::events::sound::sound);
     standard_library.std_lib.insert("mosc".to_string(), eval::events::sound::sound);
+    standard_library.std_lib.insert("midi-note".to_string(), eval::events::sound::sound);
 
     // modulators
     standard_library.std_lib.insert("lfo~".to_string(), eval::events::modulators::lfo_modulator);
@@ -354,6 +364,15 @@ pub fn define_standard_library() -> FunctionMap {
     standard_library.std_lib.insert("max".to_string(), eval::arithmetic::max);
     standard_library.std_lib.insert("min".to_string(), eval::arithmetic::min);
 
+    // comparison and logic, if/when/cond/and/or are special forms in the parser
+    for name in ["lt", "gt", "lte", "gte", "eq", "<", ">", "<=", ">=", "="] {
+        standard_library.std_lib.insert(name.to_string(), eval::logic::compare);
+    }
+    standard_library.std_lib.insert("not".to_string(), eval::logic::not);
+    for name in ["number?", "string?", "symbol?", "boolean?", "keyword?", "event?", "generator?", "vec?", "map?", "function?"] {
+        standard_library.std_lib.insert(name.to_string(), eval::logic::type_predicate);
+    }
+
     // midi helpers
     standard_library.std_lib.insert("mtof".to_string(), eval::midi_helpers::mtof);
     standard_library.std_lib.insert("mtosym".to_string(), eval::midi_helpers::mtosym);
@@ -366,10 +385,23 @@ pub fn define_standard_library() -> FunctionMap {
     standard_library.std_lib.insert("osc-sender".to_string(), eval::osc::osc_define_sender);
     standard_library.std_lib.insert("osc-send".to_string(), eval::osc::osc_send);
     standard_library.std_lib.insert("osc-receiver".to_string(), eval::osc::osc_start_receiver);
+    standard_library.std_lib.insert("osc-route".to_string(), eval::osc::osc_route);
+    standard_library.std_lib.insert("osc-unroute".to_string(), eval::osc::osc_unroute);
 
     // midi
     standard_library.std_lib.insert("list-midi-ports".to_string(), eval::midi::eval_list_midi_ports);
     standard_library.std_lib.insert("open-midi-port".to_string(), eval::midi::open_midi_port);
+    standard_library.std_lib.insert("open-midi-out".to_string(), eval::midi::open_midi_out_port);
+    standard_library.std_lib.insert("midi-route".to_string(), eval::midi::midi_route);
+    standard_library.std_lib.insert("midi-unroute".to_string(), eval::midi::midi_unroute);
+    standard_library.std_lib.insert("midi-clock-start".to_string(), eval::midi::midi_clock_start);
+    standard_library.std_lib.insert("midi-clock-stop".to_string(), eval::midi::midi_clock_stop);
+    standard_library.std_lib.insert("midi-clock-follow".to_string(), eval::midi::midi_clock_follow);
+    standard_library.std_lib.insert("midi-clock-follow-stop".to_string(), eval::midi::midi_clock_follow_stop);
+
+    // tempo sync with other peers
+    standard_library.std_lib.insert("tempo-sync".to_string(), eval::tempo_sync::tempo_sync_join);
+    standard_library.std_lib.insert("tempo-sync-stop".to_string(), eval::tempo_sync::tempo_sync_leave);
 
         
     // types for osc and other stuff
@@ -0,0 +1,477 @@
+//! Share tempo and beat phase with other Mégra instances over UDP.
+//!
+//! This is a small peer protocol in the spirit of Ableton Link (but not
+//! compatible with it). Every peer knows a list of other peers, and
+//! periodically sends them its view of the shared timeline. All messages
+//! are OSC messages:
+//!
+//! * `/megra/tempo <peer id> <bpm> <tmod> <origin> <version>`
+//!   the timeline: tempo in bpm, global time modifier, the network time of
+//!   beat zero, and the network time of the last change. The timeline with
+//!   the highest version wins, on equal versions the one from the peer with
+//!   the smallest id.
+//! * `/megra/ping <peer id> <t0>` and `/megra/pong <peer id> <t0> <t1>`
+//!   are used to estimate the offset to the network time, which is the
+//!   system time of the peer with the smallest id.
+//!
+//! All numbers are doubles, all times are in seconds.
+
+use crossbeam::atomic::AtomicCell;
+use parking_lot::Mutex;
+use rosc::{OscMessage, OscPacket, OscType};
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{sync, thread};
+
+use crate::builtin_types::{ConfigParameter, GlobalVariables, TypedEntity, VariableId};
+use crate::commands;
+use crate::parameter::DynVal;
+
+// how often the timeline is sent to the other peers
+const ANNOUNCE_INTERVAL: f64 = 0.5;
+// peers that haven't been heard of for this long are considered gone
+const PEER_TIMEOUT: f64 = 3.0;
+// number of ping measurements to keep for the offset estimation
+const PING_HISTORY: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeline {
+    pub bpm: f64,
+    pub tmod: f64,
+    pub origin: f64,
+    pub version: f64,
+    pub author: String,
+}
+
+impl Timeline {
+    fn beat_duration(&self) -> f64 {
+        60.0 / self.bpm
+    }
+
+    /// whether this timeline should be replaced by the other one
+    fn is_superseded_by(&self, other: &Timeline) -> bool {
+        other.version > self.version
+            || (other.version == self.version && other.author < self.author)
+    }
+}
+
+#[derive(Clone)]
+pub struct TempoSync {
+    pub peer_id: String,
+    pub quantum: f64,
+    socket: sync::Arc<UdpSocket>,
+    peers: sync::Arc<Vec<SocketAddr>>,
+    timeline: sync::Arc<Mutex<Timeline>>,
+    offset: sync::Arc<AtomicCell<f64>>,
+    running: sync::Arc<AtomicBool>,
+    thread: sync::Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+}
+
+/// the local system time in seconds
+fn system_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn get_double(args: &[OscType], idx: usize) -> Option<f64> {
+    match args.get(idx) {
+        Some(OscType::Double(d)) => Some(*d),
+        Some(OscType::Float(f)) => Some(*f as f64),
+        _ => None,
+    }
+}
+
+fn get_string(args: &[OscType], idx: usize) -> Option<String> {
+    match args.get(idx) {
+        Some(OscType::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+impl TempoSync {
+    /// Join a tempo sync session, listening on the local address and
+    /// talking to the given peers. The tempo of the session is
+    /// initialized from the global variables.
+    pub fn join(
+        local: &str,
+        peers: &[String],
+        quantum: f64,
+        globals: &sync::Arc<GlobalVariables>,
+    ) -> Result<TempoSync, anyhow::Error> {
+        TempoSync::join_with_socket(UdpSocket::bind(local)?, peers, quantum, globals)
+    }
+
+    /// like `join`, on a socket that's already bound
+    fn join_with_socket(
+        socket: UdpSocket,
+        peers: &[String],
+        quantum: f64,
+        globals: &sync::Arc<GlobalVariables>,
+    ) -> Result<TempoSync, anyhow::Error> {
+        socket.set_read_timeout(Some(Duration::from_millis(50)))?;
+
+        let mut peer_addrs = Vec::new();
+        for peer in peers {
+            peer_addrs.push(peer.parse::<SocketAddr>()?);
+        }
+
+        // the local address plus the process id should be unique enough
+        let peer_id = format!("{}-{}", socket.local_addr()?, std::process::id());
+
+        let bpm = if let Some(thing) = globals.get(&VariableId::DefaultDuration) {
+            if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(dur)) = thing.value() {
+                60000.0 / *dur as f64
+            } else {
+                300.0
+            }
+        } else {
+            300.0
+        };
+
+        let tmod = if let Some(thing) = globals.get(&VariableId::GlobalTimeModifier) {
+            if let TypedEntity::ConfigParameter(ConfigParameter::Dynamic(d)) = thing.value() {
+                d.static_val as f64
+            } else {
+                1.0
+            }
+        } else {
+            1.0
+        };
+
+        // version zero, so an existing session always wins
+        let timeline = Timeline {
+            bpm,
+            tmod,
+            origin: system_time(),
+            version: 0.0,
+            author: peer_id.clone(),
+        };
+
+        let tempo_sync = TempoSync {
+            peer_id,
+            quantum,
+            socket: sync::Arc::new(socket),
+            peers: sync::Arc::new(peer_addrs),
+            timeline: sync::Arc::new(Mutex::new(timeline)),
+            offset: sync::Arc::new(AtomicCell::new(0.0)),
+            running: sync::Arc::new(AtomicBool::new(true)),
+            thread: sync::Arc::new(Mutex::new(None)),
+        };
+
+        let tempo_sync2 = tempo_sync.clone();
+        let globals2 = sync::Arc::clone(globals);
+        let handle = thread::Builder::new()
+            .name("tempo sync".to_string())
+            .spawn(move || tempo_sync2.run(globals2))?;
+        *tempo_sync.thread.lock() = Some(handle);
+
+        println!(
+            "joined tempo sync as {} ({} peers)",
+            tempo_sync.peer_id,
+            tempo_sync.peers.len()
+        );
+
+        Ok(tempo_sync)
+    }
+
+    /// Stop talking to the other peers. Waits for the thread, so the
+    /// socket is closed once the last handle is gone and the local
+    /// address can be used again.
+    pub fn leave(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread.lock().take() {
+            if handle.thread().id() != thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    /// the shared time, in seconds
+    pub fn network_time(&self) -> f64 {
+        system_time() + self.offset.load()
+    }
+
+    pub fn timeline(&self) -> Timeline {
+        self.timeline.lock().clone()
+    }
+
+    /// the current position on the shared beat grid, in beats
+    pub fn beat(&self) -> f64 {
+        let timeline = self.timeline.lock();
+        (self.network_time() - timeline.origin) / timeline.beat_duration()
+    }
+
+    /// Time until the next quantum boundary (i.e. the next bar) is audible.
+    /// The latency is subtracted, as events are triggered with that delay.
+    pub fn time_to_next_quantum(&self, latency: f64) -> f64 {
+        let timeline = self.timeline.lock();
+        let quantum_duration = self.quantum * timeline.beat_duration();
+        let pos = (self.network_time() + latency - timeline.origin).rem_euclid(quantum_duration);
+        let time = quantum_duration - pos;
+        // very close to the boundary counts as on the boundary
+        if time > quantum_duration - 0.001 {
+            0.0
+        } else {
+            time
+        }
+    }
+
+    /// Change the shared tempo. The beat phase is kept, so the
+    /// other peers don't jump around.
+    pub fn set_tempo(&self, bpm: f64, tmod: f64) {
+        {
+            let mut timeline = self.timeline.lock();
+            if timeline.bpm == bpm && timeline.tmod == tmod {
+                return;
+            }
+            let now = self.network_time();
+            let beat = (now - timeline.origin) / timeline.beat_duration();
+            timeline.bpm = bpm;
+            timeline.tmod = tmod;
+            timeline.origin = now - beat * timeline.beat_duration();
+            timeline.version = now;
+            timeline.author = self.peer_id.clone();
+        }
+        self.announce();
+    }
+
+    fn send(&self, addr: &SocketAddr, osc_addr: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: osc_addr.to_string(),
+            args,
+        });
+        if let Ok(buf) = rosc::encoder::encode(&packet) {
+            // peers that aren't there yet are no reason to complain
+            let _ = self.socket.send_to(&buf, addr);
+        }
+    }
+
+    fn announce(&self) {
+        let timeline = self.timeline();
+        for peer in self.peers.iter() {
+            self.send(
+                peer,
+                "/megra/tempo",
+                vec![
+                    OscType::String(self.peer_id.clone()),
+                    OscType::Double(timeline.bpm),
+                    OscType::Double(timeline.tmod),
+                    OscType::Double(timeline.origin),
+                    OscType::Double(timeline.version),
+                ],
+            );
+        }
+    }
+
+    fn adopt(&self, timeline: Timeline, globals: &sync::Arc<GlobalVariables>) {
+        println!(
+            "tempo sync: follow {} (bpm {} tmod {})",
+            timeline.author, timeline.bpm, timeline.tmod
+        );
+        commands::set_default_duration(globals, (60000.0 / timeline.bpm) as f32);
+        commands::set_global_tmod(globals, DynVal::with_value(timeline.tmod as f32));
+        *self.timeline.lock() = timeline;
+    }
+
+    fn run(&self, globals: sync::Arc<GlobalVariables>) {
+        let mut buf = [0u8; rosc::decoder::MTU];
+        let mut last_announce: Option<Instant> = None;
+        let mut last_seen: HashMap<String, (Instant, SocketAddr)> = HashMap::new();
+        // round trip time and offset
+        let mut pings: VecDeque<(f64, f64)> = VecDeque::new();
+        let mut leader = self.peer_id.clone();
+
+        while self.running.load(Ordering::SeqCst) {
+            let announce_due = if let Some(t) = last_announce {
+                t.elapsed().as_secs_f64() > ANNOUNCE_INTERVAL
+            } else {
+                true
+            };
+
+            if announce_due {
+                last_seen.retain(|_, (t, _)| t.elapsed().as_secs_f64() < PEER_TIMEOUT);
+
+                // the peer with the smallest id provides the network time
+                let new_leader = last_seen
+                    .keys()
+                    .chain(std::iter::once(&self.peer_id))
+                    .min()
+                    .unwrap()
+                    .clone();
+
+                if new_leader != leader {
+                    pings.clear();
+                    leader = new_leader;
+                }
+
+                if leader == self.peer_id {
+                    self.offset.store(0.0);
+                } else if let Some((_, addr)) = last_seen.get(&leader) {
+                    self.send(
+                        addr,
+                        "/megra/ping",
+                        vec![
+                            OscType::String(self.peer_id.clone()),
+                            OscType::Double(system_time()),
+                        ],
+                    );
+                }
+
+                self.announce();
+                last_announce = Some(Instant::now());
+            }
+
+            let (size, from) = match self.socket.recv_from(&mut buf) {
+                Ok(res) => res,
+                Err(_) => continue, // timeout, most likely
+            };
+
+            let msg = match rosc::decoder::decode_udp(&buf[..size]) {
+                Ok((_, OscPacket::Message(msg))) => msg,
+                _ => continue, // not for us
+            };
+
+            let sender = match get_string(&msg.args, 0) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            match msg.addr.as_str() {
+                "/megra/tempo" => {
+                    if let (Some(bpm), Some(tmod), Some(origin), Some(version)) = (
+                        get_double(&msg.args, 1),
+                        get_double(&msg.args, 2),
+                        get_double(&msg.args, 3),
+                        get_double(&msg.args, 4),
+                    ) {
+                        last_seen.insert(sender.clone(), (Instant::now(), from));
+                        if bpm <= 0.0 {
+                            continue;
+                        }
+                        let remote = Timeline {
+                            bpm,
+                            tmod,
+                            origin,
+                            version,
+                            author: sender,
+                        };
+                        let superseded = self.timeline.lock().is_superseded_by(&remote);
+                        if superseded {
+                            self.adopt(remote, &globals);
+                        }
+                    }
+                }
+                "/megra/ping" => {
+                    if let Some(t0) = get_double(&msg.args, 1) {
+                        self.send(
+                            &from,
+                            "/megra/pong",
+                            vec![
+                                OscType::String(self.peer_id.clone()),
+                                OscType::Double(t0),
+                                OscType::Double(self.network_time()),
+                            ],
+                        );
+                    }
+                }
+                "/megra/pong" => {
+                    if let (Some(t0), Some(t1)) =
+                        (get_double(&msg.args, 1), get_double(&msg.args, 2))
+                    {
+                        if sender != leader {
+                            continue;
+                        }
+                        let t2 = system_time();
+                        let (rtt, offset) = (t2 - t0, t1 - (t0 + t2) * 0.5);
+                        // the times come from the network, don't trust them
+                        if !rtt.is_finite() || !offset.is_finite() {
+                            continue;
+                        }
+                        pings.push_back((rtt, offset));
+                        if pings.len() > PING_HISTORY {
+                            pings.pop_front();
+                        }
+                        // the measurement with the shortest round trip
+                        // is the most trustworthy
+                        if let Some((_, offset)) = pings.iter().min_by(|a, b| a.0.total_cmp(&b.0)) {
+                            self.offset.store(*offset);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        println!("left tempo sync");
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_peers_on_loopback() {
+        let globals_a = sync::Arc::new(GlobalVariables::new());
+        let globals_b = sync::Arc::new(GlobalVariables::new());
+
+        // whatever ports are free
+        let socket_a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket_b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr_a = socket_a.local_addr().unwrap().to_string();
+        let addr_b = socket_b.local_addr().unwrap().to_string();
+
+        let peer_a = TempoSync::join_with_socket(socket_a, &[addr_b], 4.0, &globals_a).unwrap();
+        let peer_b = TempoSync::join_with_socket(socket_b, &[addr_a], 4.0, &globals_b).unwrap();
+
+        peer_a.set_tempo(120.0, 1.0);
+
+        let start = Instant::now();
+        while peer_b.timeline().bpm != 120.0 && start.elapsed().as_secs_f64() < 5.0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(peer_a.timeline(), peer_b.timeline());
+
+        // the default duration follows the shared tempo
+        if let Some(thing) = globals_b.get(&VariableId::DefaultDuration) {
+            if let TypedEntity::ConfigParameter(ConfigParameter::Numeric(dur)) = thing.value() {
+                assert_eq!(*dur, 500.0);
+            } else {
+                panic!();
+            }
+        } else {
+            panic!();
+        }
+
+        // both are on the same beat grid
+        assert!((peer_a.beat() - peer_b.beat()).abs() < 0.01);
+        // (mind the wraparound at the bar line)
+        let diff = (peer_a.time_to_next_quantum(0.05) - peer_b.time_to_next_quantum(0.05)).abs();
+        assert!(!(0.01..=1.99).contains(&diff));
+
+        peer_a.leave();
+        peer_b.leave();
+    }
+
+    #[test]
+    fn test_rejoin_on_same_address() {
+        let globals = sync::Arc::new(GlobalVariables::new());
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let local = socket.local_addr().unwrap().to_string();
+        let peer = TempoSync::join_with_socket(socket, &[], 4.0, &globals).unwrap();
+
+        peer.leave();
+        drop(peer);
+
+        let peer = TempoSync::join(&local, &[], 4.0, &globals).unwrap();
+        peer.leave();
+    }
+}
@@ -1,12 +1,53 @@
 use crate::generator::Generator;
 
-use rosc::encoder::{self, encode};
+use rosc::encoder::encode;
 use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
 
 use std::collections::BTreeSet;
 use std::net;
 use std::str::FromStr;
-use std::time::SystemTime;
+
+/// The graph of a generator's root, as the visualizers show it.
+/// Nodes and edges are identified by the label hashes of the PFA.
+pub struct GeneratorGraph {
+    pub id_tags: BTreeSet<String>,
+    pub nodes: Vec<(u64, String)>,
+    // source, destination, symbol, probability
+    pub edges: Vec<(u64, u64, char, f32)>,
+    pub active: Option<u64>,
+}
+
+impl GeneratorGraph {
+    pub fn from_generator(g: &Generator) -> Self {
+        let pfa = &g.root_generator.generator;
+
+        let mut nodes: Vec<(u64, String)> = pfa
+            .labels
+            .iter()
+            .map(|(key, label)| (*key, label.iter().collect()))
+            .collect();
+        // hash map order changes all the time, which would make the
+        // layout jump around
+        nodes.sort();
+
+        let mut edges = Vec::new();
+        for (src, children) in pfa.children.iter() {
+            for ch in children.iter() {
+                if let Some(sym) = ch.child.last() {
+                    edges.push((*src, ch.child_hash, *sym, ch.prob));
+                }
+            }
+        }
+        edges.sort_by_key(|e| (e.0, e.1));
+
+        GeneratorGraph {
+            id_tags: g.id_tags.clone(),
+            nodes,
+            edges,
+            active: pfa.current_state,
+        }
+    }
+}
 
 pub struct VisualizerClient {
     pub host_addr: net::SocketAddrV4,
@@ -33,7 +74,16 @@ impl VisualizerClient {
         }
     }
 
-    pub fn create_or_update(&self, g: &Generator) {
+    /// Send the messages as a bundle, so the visualizer can show them
+    /// at the time the corresponding sound is played.
+    /// The visualizer might just not be running, so errors are ignored.
+    fn send_bundle(&self, timetag: OscTime, content: Vec<OscPacket>) {
+        if let Ok(msg_buf) = encode(&OscPacket::Bundle(OscBundle { timetag, content })) {
+            let _ = self.socket.send_to(&msg_buf, self.to_addr);
+        }
+    }
+
+    pub fn create_or_update(&self, g: &Generator, timetag: OscTime) {
         let gen_name = tags_to_string(&g.id_tags);
         // switch view
         let mut all_msgs: Vec<OscPacket> = Vec::new();
@@ -44,32 +94,32 @@ impl VisualizerClient {
             args: vec![OscType::String(gen_name.clone())],
         }));
 
+        let graph = GeneratorGraph::from_generator(g);
+
         // nodes
-        for (key, label) in g.root_generator.generator.labels.iter() {
+        for (key, label) in graph.nodes {
             all_msgs.push(OscPacket::Message(OscMessage {
                 addr: "/node/add".to_string(),
                 args: vec![
                     // needs full tag id
                     OscType::String(gen_name.clone()),
-                    OscType::Int(*key as i32),
-                    OscType::String(label.iter().collect()),
+                    OscType::Int(key as i32),
+                    OscType::String(label),
                 ],
             }));
         }
         // edges
-        for (src, children) in g.root_generator.generator.children.iter() {
-            for ch in children.iter() {
-                all_msgs.push(OscPacket::Message(OscMessage {
-                    addr: "/edge/add".to_string(),
-                    args: vec![
-                        OscType::String(gen_name.clone()),
-                        OscType::Int(*src as i32),
-                        OscType::Int(ch.child_hash as i32),
-                        OscType::String(ch.child.last().unwrap().to_string()),
-                        OscType::Int((ch.prob * 100.0) as i32),
-                    ],
-                }));
-            }
+        for (src, dest, sym, prob) in graph.edges {
+            all_msgs.push(OscPacket::Message(OscMessage {
+                addr: "/edge/add".to_string(),
+                args: vec![
+                    OscType::String(gen_name.clone()),
+                    OscType::Int(src as i32),
+                    OscType::Int(dest as i32),
+                    OscType::String(sym.to_string()),
+                    OscType::Int((prob * 100.0) as i32),
+                ],
+            }));
         }
 
         // send render command ...
@@ -81,36 +131,29 @@ impl VisualizerClient {
             ],
         }));
 
-        // send render command ...
-        let msg_buf_render = encode(&OscPacket::Bundle(OscBundle {
-            timetag: OscTime::try_from(SystemTime::now()).unwrap(),
-            content: all_msgs,
-        }))
-        .unwrap();
-        self.socket.send_to(&msg_buf_render, self.to_addr).unwrap();
+        self.send_bundle(timetag, all_msgs);
     }
 
-    pub fn update_active_node(&self, g: &Generator) {
+    pub fn update_active_node(&self, g: &Generator, timetag: OscTime) {
         let gen_name = tags_to_string(&g.id_tags);
         if let Some(h) = g.root_generator.generator.current_state {
-            let msg_buf_active_node = encoder::encode(&OscPacket::Message(OscMessage {
-                addr: "/node/active".to_string(),
-                args: vec![OscType::String(gen_name), OscType::Int(h as i32)],
-            }))
-            .unwrap();
-            self.socket
-                .send_to(&msg_buf_active_node, self.to_addr)
-                .unwrap();
+            self.send_bundle(
+                timetag,
+                vec![OscPacket::Message(OscMessage {
+                    addr: "/node/active".to_string(),
+                    args: vec![OscType::String(gen_name), OscType::Int(h as i32)],
+                })],
+            );
         }
     }
 
     pub fn clear(&self, id_tags: &BTreeSet<String>) {
         let gen_name = tags_to_string(id_tags);
-        let msg_buf_clear = encoder::encode(&OscPacket::Message(OscMessage {
+        if let Ok(msg_buf_clear) = encode(&OscPacket::Message(OscMessage {
             addr: "/clear".to_string(),
             args: vec![OscType::String(gen_name)],
-        }))
-        .unwrap();
-        self.socket.send_to(&msg_buf_clear, self.to_addr).unwrap();
+        })) {
+            let _ = self.socket.send_to(&msg_buf_clear, self.to_addr);
+        }
     }
 }